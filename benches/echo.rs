@@ -2,7 +2,6 @@ use std::{thread::spawn, time::Duration};
 
 use criterion::{async_executor::FuturesExecutor, *};
 use futures::executor::block_on;
-use reactors::Reactor;
 
 async fn setup_tokio_server() -> anyhow::Result<()> {
     use tokio::io::AsyncWriteExt;
@@ -100,17 +99,19 @@ async fn reactor_client(reactor: reactors::io::IoReactor) -> anyhow::Result<()>
 
 fn bench_reactors(c: &mut Criterion) {
     // pretty_env_logger::init();
-    use reactors::io::IoReactor;
+    use reactors::io::{IoReactor, PollThreadPolicy};
 
     let reactor = IoReactor::default();
 
-    let mut server_background_reactor = reactor.clone();
-
-    spawn(move || loop {
-        server_background_reactor
-            .poll_once(Duration::from_millis(600))
-            .unwrap();
-    });
+    reactor
+        .spawn_poll_thread_with(
+            Duration::from_millis(600),
+            PollThreadPolicy::Retry {
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(100),
+            },
+        )
+        .unwrap();
 
     let server_reactor = reactor.clone();
 
@@ -0,0 +1,37 @@
+use criterion::*;
+use futures::task::noop_waker;
+use reactors::io::{EventName, IoReactor};
+
+fn bench_register(c: &mut Criterion) {
+    let mut reactor = IoReactor::default();
+
+    let waker = noop_waker();
+
+    c.bench_function("register read/write with once", |b| {
+        b.iter(|| {
+            reactor
+                .once(0, EventName::Read, waker.clone(), None)
+                .unwrap();
+
+            reactor
+                .once(0, EventName::Write, waker.clone(), None)
+                .unwrap();
+        })
+    });
+
+    let mut reactor = IoReactor::default();
+
+    c.bench_function("register read/write with once_many", |b| {
+        b.iter(|| {
+            reactor
+                .once_many(&[
+                    (0, EventName::Read, waker.clone(), None),
+                    (0, EventName::Write, waker.clone(), None),
+                ])
+                .unwrap();
+        })
+    });
+}
+
+criterion_group!(benches, bench_register);
+criterion_main!(benches);
@@ -0,0 +1,396 @@
+//! Multi-thread executor, each worker thread drives its own [`IoReactor`].
+
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    future::Future,
+    io::Result,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    task::{Context, Poll},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use futures::{
+    future::BoxFuture,
+    task::{waker_ref, ArcWake, FutureObj, Spawn, SpawnError},
+    FutureExt,
+};
+
+use crate::{io::IoReactor, Reactor};
+
+/// Max duration an idle worker thread blocks in [`Reactor::poll_once`] before trying to steal tasks.
+const WORKER_POLL_DURATION: Duration = Duration::from_millis(10);
+
+/// Max number of tasks a worker runs before polling its reactor again.
+const WORKER_BATCH: usize = 64;
+
+thread_local! {
+    /// Worker index of current thread.
+    static WORKER: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Spawned future.
+struct Task {
+    future: Mutex<Option<BoxFuture<'static, ()>>>,
+    shared: Weak<Shared>,
+    queued: AtomicBool,
+}
+
+impl ArcWake for Task {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        if arc_self.queued.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        if let Some(shared) = arc_self.shared.upgrade() {
+            shared.schedule(arc_self.clone());
+        }
+    }
+}
+
+impl Task {
+    fn run(self: Arc<Self>) {
+        // Clear flag before polling, so wakeups raised while polling reschedule this task.
+        self.queued.store(false, Ordering::SeqCst);
+
+        let mut future = self.future.lock().unwrap();
+
+        if let Some(fut) = future.as_mut() {
+            let waker = waker_ref(&self);
+
+            let mut cx = Context::from_waker(&waker);
+
+            // A panicking task must not take down the worker and the sockets of its reactor.
+            match catch_unwind(AssertUnwindSafe(|| fut.as_mut().poll(&mut cx))) {
+                Ok(Poll::Pending) => {}
+                Ok(Poll::Ready(())) => *future = None,
+                Err(_) => {
+                    log::error!("spawned task panicked, dropping it");
+
+                    *future = None;
+                }
+            }
+        }
+    }
+}
+
+/// State shared by executor, workers and tasks.
+struct Shared {
+    injector: Mutex<VecDeque<Arc<Task>>>,
+    locals: Vec<Mutex<VecDeque<Arc<Task>>>>,
+    reactors: Vec<IoReactor>,
+    next_reactor: AtomicUsize,
+    next_injected: AtomicUsize,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    fn schedule(&self, task: Arc<Task>) {
+        let index = match WORKER.with(|worker| worker.get()) {
+            Some(index) if index < self.locals.len() => {
+                self.locals[index].lock().unwrap().push_back(task);
+
+                index
+            }
+            _ => {
+                self.injector.lock().unwrap().push_back(task);
+
+                self.next_injected.fetch_add(1, Ordering::Relaxed) % self.reactors.len()
+            }
+        };
+
+        // Workers block in `poll_once`, wake the one expected to run the task through its reactor.
+        if let Err(err) = self.reactors[index].notify() {
+            log::error!("worker({}) notify reactor error: {}", index, err);
+        }
+    }
+
+    fn spawn(self: &Arc<Self>, future: BoxFuture<'static, ()>) {
+        let task = Arc::new(Task {
+            future: Mutex::new(Some(future)),
+            shared: Arc::downgrade(self),
+            queued: AtomicBool::new(true),
+        });
+
+        self.schedule(task);
+    }
+
+    /// Pop task from local queue, then global queue, then steal from other workers.
+    fn find_task(&self, index: usize) -> Option<Arc<Task>> {
+        if let Some(task) = self.locals[index].lock().unwrap().pop_front() {
+            return Some(task);
+        }
+
+        if let Some(task) = self.injector.lock().unwrap().pop_front() {
+            return Some(task);
+        }
+
+        for offset in 1..self.locals.len() {
+            let victim = (index + offset) % self.locals.len();
+
+            if let Some(task) = self.locals[victim].lock().unwrap().pop_back() {
+                return Some(task);
+            }
+        }
+
+        None
+    }
+
+    fn run_worker(&self, index: usize) {
+        WORKER.with(|worker| worker.set(Some(index)));
+
+        let mut reactor = self.reactors[index].clone();
+
+        while !self.shutdown.load(Ordering::SeqCst) {
+            let mut ran = 0;
+
+            while ran < WORKER_BATCH {
+                match self.find_task(index) {
+                    Some(task) => task.run(),
+                    None => break,
+                }
+
+                ran += 1;
+            }
+
+            let duration = if ran > 0 {
+                Duration::ZERO
+            } else {
+                WORKER_POLL_DURATION
+            };
+
+            if let Err(err) = reactor.poll_once(duration) {
+                log::error!("worker({}) poll reactor error: {}", index, err);
+            }
+        }
+
+        WORKER.with(|worker| worker.set(None));
+    }
+}
+
+/// Work-stealing multi-thread executor.
+///
+/// Each worker thread owns one [`IoReactor`] and polls it between running tasks,
+/// use [`reactor`](ThreadPoolExecutor::reactor) to shard sockets over workers.
+pub struct ThreadPoolExecutor {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPoolExecutor {
+    /// Create executor with `threads` worker threads.
+    pub fn new(threads: usize) -> Result<Self> {
+        let threads = threads.max(1);
+
+        let mut reactors = vec![];
+
+        for _ in 0..threads {
+            reactors.push(IoReactor::new(Duration::from_secs(1))?);
+        }
+
+        let shared = Arc::new(Shared {
+            injector: Default::default(),
+            locals: (0..threads).map(|_| Default::default()).collect(),
+            reactors,
+            next_reactor: Default::default(),
+            next_injected: Default::default(),
+            shutdown: Default::default(),
+        });
+
+        let mut workers = vec![];
+
+        for index in 0..threads {
+            let shared = shared.clone();
+
+            let worker = std::thread::Builder::new()
+                .name(format!("reactors-worker-{}", index))
+                .spawn(move || shared.run_worker(index))?;
+
+            workers.push(worker);
+        }
+
+        Ok(Self { shared, workers })
+    }
+
+    /// Get worker reactor in round-robin order.
+    pub fn reactor(&self) -> IoReactor {
+        let next = self.shared.next_reactor.fetch_add(1, Ordering::Relaxed);
+
+        self.shared.reactors[next % self.shared.reactors.len()].clone()
+    }
+
+    /// Get all worker reactors.
+    pub fn reactors(&self) -> &[IoReactor] {
+        &self.shared.reactors
+    }
+
+    /// Spawn `future` onto worker threads.
+    pub fn spawn<Fut>(&self, future: Fut)
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.shared.spawn(future.boxed())
+    }
+
+    /// Get spawner handle, which can be moved into spawned tasks.
+    pub fn spawner(&self) -> Spawner {
+        Spawner(Arc::downgrade(&self.shared))
+    }
+
+    /// Run `future` to completion on current thread, io is driven by worker threads.
+    pub fn block_on<Fut: Future>(&self, future: Fut) -> Fut::Output {
+        futures::executor::block_on(future)
+    }
+}
+
+impl Spawn for ThreadPoolExecutor {
+    fn spawn_obj(&self, future: FutureObj<'static, ()>) -> std::result::Result<(), SpawnError> {
+        self.shared.spawn(future.boxed());
+
+        Ok(())
+    }
+}
+
+impl Drop for ThreadPoolExecutor {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+
+        for reactor in &self.shared.reactors {
+            _ = reactor.notify();
+        }
+
+        for worker in self.workers.drain(..) {
+            _ = worker.join();
+        }
+    }
+}
+
+/// Weak spawn handle of [`ThreadPoolExecutor`], spawning fails after executor dropped.
+#[derive(Clone)]
+pub struct Spawner(Weak<Shared>);
+
+impl Spawn for Spawner {
+    fn spawn_obj(&self, future: FutureObj<'static, ()>) -> std::result::Result<(), SpawnError> {
+        let shared = self.0.upgrade().ok_or_else(SpawnError::shutdown)?;
+
+        shared.spawn(future.boxed());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{future::join_all, task::SpawnExt, AsyncReadExt, AsyncWriteExt, TryStreamExt};
+
+    use crate::io::socket::tcp::{TcpAcceptor, TcpStream};
+
+    use super::*;
+
+    #[test]
+    fn test_echo_connections() {
+        _ = pretty_env_logger::try_init();
+
+        let executor = ThreadPoolExecutor::new(4).unwrap();
+
+        let listen_addr = "127.0.0.1:1905".parse().unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(executor.reactor(), listen_addr, Some(executor.reactor())).unwrap();
+
+        let spawner = executor.spawner();
+
+        executor.spawn(async move {
+            while let Some((conn, _)) = acceptor.try_next().await.unwrap() {
+                spawner
+                    .spawn(async move {
+                        let mut reader = conn.to_read_stream(None);
+                        let mut writer = conn.to_write_stream(None);
+
+                        let mut buff = [0u8; 11];
+
+                        reader.read_exact(&mut buff).await.unwrap();
+
+                        writer.write_all(&buff).await.unwrap();
+                    })
+                    .unwrap();
+            }
+        });
+
+        let mut clients = vec![];
+
+        for _ in 0..100 {
+            let reactor = executor.reactor();
+
+            let client = executor
+                .spawn_with_handle(async move {
+                    let conn = TcpStream::connect(reactor, listen_addr, None, None)
+                        .await
+                        .unwrap();
+
+                    let mut reader = conn.to_read_stream(None);
+                    let mut writer = conn.to_write_stream(None);
+
+                    writer.write_all(b"hello world").await.unwrap();
+
+                    let mut buff = [0u8; 11];
+
+                    reader.read_exact(&mut buff).await.unwrap();
+
+                    buff
+                })
+                .unwrap();
+
+            clients.push(client);
+        }
+
+        let echoed = executor.block_on(join_all(clients));
+
+        assert_eq!(echoed.len(), 100);
+
+        assert!(echoed.iter().all(|buff| buff == b"hello world"));
+    }
+
+    #[test]
+    fn test_panicking_task() {
+        _ = pretty_env_logger::try_init();
+
+        let executor = ThreadPoolExecutor::new(1).unwrap();
+
+        executor.spawn(async { panic!("task panic") });
+
+        // The only worker survives and still runs tasks.
+        let handle = executor.spawn_with_handle(async { 1 }).unwrap();
+
+        assert_eq!(executor.block_on(handle), 1);
+    }
+
+    #[test]
+    fn test_spawn_wakes_polling_worker() {
+        _ = pretty_env_logger::try_init();
+
+        let executor = ThreadPoolExecutor::new(2).unwrap();
+
+        // Let workers go idle in `poll_once`.
+        std::thread::sleep(Duration::from_millis(50));
+
+        let start = std::time::Instant::now();
+
+        for i in 0..500 {
+            let handle = executor.spawn_with_handle(async move { i }).unwrap();
+
+            assert_eq!(executor.block_on(handle), i);
+        }
+
+        // Without a reactor notify most spawns wait for a worker poll to time out.
+        // With it the loop takes a few milliseconds, the bound leaves room for busy test threads.
+        let elapsed = start.elapsed();
+
+        assert!(elapsed < WORKER_POLL_DURATION * 25, "{:?}", elapsed);
+    }
+}
@@ -0,0 +1,139 @@
+//! Readiness of raw fds owned by other code (an external eventfd, a timerfd, a pipe ..), polled by [`IoReactor`].
+//!
+//! [`AsyncFd`] only waits, the owner does the actual reads and writes on the fd.
+
+use std::{
+    io::Result,
+    task::{Context, Poll},
+};
+
+use futures::future::poll_fn;
+
+use super::{EventName, IoReactor, RawFd};
+
+/// Raw fd registered to [`IoReactor`] for readiness notifications, the fd is borrowed, not closed on drop.
+#[derive(Debug)]
+pub struct AsyncFd {
+    reactor: IoReactor,
+    fd: RawFd,
+}
+
+impl AsyncFd {
+    /// Register `fd` to `reactor`.
+    ///
+    /// `fd` should be in nonblocking mode, so the owner can read or write until `EAGAIN` after each readiness.
+    /// It must stay open until the returned value is dropped, which deregisters it.
+    pub fn new(mut reactor: IoReactor, fd: RawFd) -> Result<Self> {
+        reactor.on_open_fd(fd)?;
+
+        Ok(Self { reactor, fd })
+    }
+
+    /// Get the watched fd.
+    pub fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Check if the reactor reported `fd` readable, otherwise register `cx` waker for it.
+    pub fn poll_readable(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_ready(cx, EventName::Read, "async_fd_read")
+    }
+
+    /// Check if the reactor reported `fd` writable, otherwise register `cx` waker for it.
+    pub fn poll_writable(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_ready(cx, EventName::Write, "async_fd_write")
+    }
+
+    /// Wait until `fd` is readable.
+    pub async fn readable(&mut self) -> Result<()> {
+        poll_fn(|cx| self.poll_readable(cx)).await
+    }
+
+    /// Wait until `fd` is writable.
+    pub async fn writable(&mut self) -> Result<()> {
+        poll_fn(|cx| self.poll_writable(cx)).await
+    }
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut Context<'_>,
+        name: EventName,
+        label: &'static str,
+    ) -> Poll<Result<()>> {
+        if let Some(event) = self.reactor.poll_io_event(self.fd, name.clone())? {
+            return Poll::Ready(event.message);
+        }
+
+        self.reactor
+            .once_labeled(self.fd, name, cx.waker().clone(), None, label)?;
+
+        Poll::Pending
+    }
+}
+
+impl Drop for AsyncFd {
+    fn drop(&mut self) {
+        self.reactor.on_close_fd(self.fd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread::spawn, time::Duration};
+
+    use futures::FutureExt;
+
+    use crate::Reactor;
+
+    use super::*;
+
+    #[futures_test::test]
+    async fn test_pipe_readable() {
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let mut fds = [0; 2];
+
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+
+        unsafe { crate::io::noblock(fds[0]).unwrap() };
+
+        let mut async_fd = AsyncFd::new(reactor.clone(), fds[0]).unwrap();
+
+        assert!(async_fd.readable().now_or_never().is_none());
+
+        let write_fd = fds[1];
+
+        spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+
+            assert_eq!(
+                unsafe { libc::write(write_fd, b"x".as_ptr() as *const _, 1) },
+                1
+            );
+        });
+
+        async_fd.readable().await.unwrap();
+
+        let mut buf = [0u8; 1];
+
+        assert_eq!(
+            unsafe { libc::read(fds[0], buf.as_mut_ptr() as *mut _, 1) },
+            1
+        );
+
+        assert_eq!(&buf, b"x");
+
+        drop(async_fd);
+
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Async dns resolver with result cache.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    io::{Error, ErrorKind, Result},
+    net::{SocketAddr, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{
+    executor::ThreadPool,
+    future::{BoxFuture, Shared},
+    task::SpawnExt,
+    FutureExt,
+};
+use once_cell::sync::OnceCell;
+
+/// Number of threads running blocking `getaddrinfo` calls, shared by all resolvers.
+const RESOLVER_THREADS: usize = 2;
+
+/// Cached lookup result, io errors are not `Clone`, so negative results keep kind and message only.
+type CachedResult = std::result::Result<Vec<SocketAddr>, (ErrorKind, String)>;
+
+/// In-flight `getaddrinfo` call, awaited by every concurrent lookup of the same host.
+type PendingLookup = Shared<BoxFuture<'static, CachedResult>>;
+
+enum Entry {
+    Resolved {
+        result: CachedResult,
+        expires: Instant,
+    },
+    Pending(PendingLookup),
+}
+
+/// Dns resolver, which runs `getaddrinfo` on a blocking thread pool and caches results.
+///
+/// Successful lookups are cached for `ttl`, failures for `negative_ttl`. Concurrent lookups of
+/// the same host share one `getaddrinfo` call. Cloned resolvers share the same cache,
+/// all resolvers share the same thread pool.
+#[derive(Clone)]
+pub struct Resolver {
+    pool: &'static ThreadPool,
+    cache: Arc<Mutex<HashMap<(String, u16), Entry>>>,
+    lookups: Arc<AtomicUsize>,
+    ttl: Duration,
+    negative_ttl: Duration,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60), Duration::from_secs(5)).unwrap()
+    }
+}
+
+impl Resolver {
+    /// Create new resolver with cache `ttl` and failure cache `negative_ttl`.
+    ///
+    /// The thread pool is created by the first resolver.
+    pub fn new(ttl: Duration, negative_ttl: Duration) -> Result<Self> {
+        static POOL: OnceCell<ThreadPool> = OnceCell::new();
+
+        let pool = POOL.get_or_try_init(|| {
+            ThreadPool::builder()
+                .pool_size(RESOLVER_THREADS)
+                .name_prefix("reactors-dns-")
+                .create()
+        })?;
+
+        Ok(Self {
+            pool,
+            cache: Default::default(),
+            lookups: Default::default(),
+            ttl,
+            negative_ttl,
+        })
+    }
+
+    /// Resolve `host` to socket addresses with `port`, served from cache if not expired.
+    pub fn resolve(&self, host: &str, port: u16) -> impl Future<Output = Result<Vec<SocketAddr>>> {
+        let key = (host.to_owned(), port);
+
+        let this = self.clone();
+
+        async move {
+            let lookup = this.lookup(&key)?;
+
+            let result = lookup.clone().await;
+
+            let ttl = match &result {
+                Ok(_) => this.ttl,
+                Err(_) => this.negative_ttl,
+            };
+
+            let mut cache = this.cache.lock().unwrap();
+
+            // The first waiter to complete caches the result, unless the entry was cleared meanwhile.
+            let current = matches!(
+                cache.get(&key),
+                Some(Entry::Pending(pending)) if pending.ptr_eq(&lookup)
+            );
+
+            if current {
+                cache.insert(
+                    key,
+                    Entry::Resolved {
+                        result: result.clone(),
+                        expires: Instant::now() + ttl,
+                    },
+                );
+            }
+
+            Self::to_result(&result)
+        }
+    }
+
+    /// Get the number of `getaddrinfo` calls made by this resolver.
+    pub fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::SeqCst)
+    }
+
+    /// Remove all cached entries.
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// Get the cached result of `key` as a ready lookup, the in-flight lookup, or start a new one.
+    fn lookup(&self, key: &(String, u16)) -> Result<PendingLookup> {
+        let mut cache = self.cache.lock().unwrap();
+
+        match cache.get(key) {
+            Some(Entry::Resolved { result, expires }) if *expires > Instant::now() => {
+                return Ok(futures::future::ready(result.clone()).boxed().shared());
+            }
+            Some(Entry::Pending(lookup)) => return Ok(lookup.clone()),
+            _ => {}
+        }
+
+        let lookups = self.lookups.clone();
+
+        let (host, port) = key.clone();
+
+        let lookup = self
+            .pool
+            .spawn_with_handle(async move {
+                lookups.fetch_add(1, Ordering::SeqCst);
+
+                (host.as_str(), port)
+                    .to_socket_addrs()
+                    .map(|addrs| addrs.collect::<Vec<_>>())
+                    .map_err(|err| (err.kind(), err.to_string()))
+            })
+            .map_err(Error::other)?
+            .boxed()
+            .shared();
+
+        cache.insert(key.clone(), Entry::Pending(lookup.clone()));
+
+        Ok(lookup)
+    }
+
+    fn to_result(result: &CachedResult) -> Result<Vec<SocketAddr>> {
+        match result {
+            Ok(addrs) => Ok(addrs.clone()),
+            Err((kind, message)) => Err(Error::new(*kind, message.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[futures_test::test]
+    async fn test_resolve_cached() {
+        let resolver = Resolver::default();
+
+        let addrs = resolver.resolve("localhost", 80).await.unwrap();
+
+        assert!(!addrs.is_empty());
+
+        assert!(addrs.iter().all(|addr| addr.port() == 80));
+
+        assert_eq!(resolver.lookups(), 1);
+
+        let cached = resolver.resolve("localhost", 80).await.unwrap();
+
+        assert_eq!(cached, addrs);
+
+        assert_eq!(resolver.lookups(), 1);
+
+        resolver.clear();
+
+        resolver.resolve("localhost", 80).await.unwrap();
+
+        assert_eq!(resolver.lookups(), 2);
+    }
+
+    #[futures_test::test]
+    async fn test_resolve_coalesced() {
+        let resolver = Resolver::default();
+
+        let (first, second) = futures::join!(
+            resolver.resolve("localhost", 80),
+            resolver.resolve("localhost", 80)
+        );
+
+        assert_eq!(first.unwrap(), second.unwrap());
+
+        assert_eq!(resolver.lookups(), 1);
+
+        // Other resolvers have their own cache on the same thread pool.
+        let other = Resolver::default();
+
+        assert!(std::ptr::eq(resolver.pool, other.pool));
+
+        other.resolve("localhost", 80).await.unwrap();
+
+        assert_eq!(other.lookups(), 1);
+    }
+}
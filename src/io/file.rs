@@ -4,7 +4,12 @@ mod impls;
 pub use impls::*;
 
 pub mod sys {
-    use std::{fs::OpenOptions, io::Result, path::PathBuf};
+    use std::{
+        fs::OpenOptions,
+        io::Result,
+        path::PathBuf,
+        task::{Context, Poll},
+    };
 
     use crate::io::IoReactor;
 
@@ -16,6 +21,28 @@ pub mod sys {
             path: P,
             ops: &mut OpenOptions,
         ) -> Result<Self>;
+
+        /// Flush file data and metadata to the underlying storage.
+        fn sync(&self) -> Result<()>;
+
+        /// Truncate or extend the file to `size` bytes, the file cursor is not moved.
+        fn set_len(&self, size: u64) -> Result<()>;
+
+        /// Read into `buffer` from `offset` of the file, the file cursor is not moved.
+        fn poll_read_at(
+            &mut self,
+            cx: &mut Context<'_>,
+            buffer: &mut [u8],
+            offset: u64,
+        ) -> Poll<Result<usize>>;
+
+        /// Write `buffer` at `offset` of the file, the file cursor is not moved.
+        fn poll_write_at(
+            &mut self,
+            cx: &mut Context<'_>,
+            buffer: &[u8],
+            offset: u64,
+        ) -> Poll<Result<usize>>;
     }
 }
 
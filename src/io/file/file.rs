@@ -41,6 +41,37 @@ impl File {
         .map(|h| Self(h))
     }
 
+    /// Open `path` with asynchronous read/write support and the independent `ops` flags
+    /// (`read`, `write`, `append`, `truncate`, `create`, `create_new`, and `mode` on unix).
+    ///
+    /// `ops` is not modified, platform flags needed for asynchronous io are added to a copy.
+    pub fn open_with<PB: Into<PathBuf>>(
+        poller: IoReactor,
+        path: PB,
+        ops: &OpenOptions,
+    ) -> Result<Self> {
+        use super::sys::File;
+
+        Handle::new(poller, path.into(), &mut ops.clone()).map(Self)
+    }
+
+    /// Sync and close the current file, then open `path` with `ops` in its place,
+    /// e.g. to rotate an append-mode log file.
+    ///
+    /// On error the current file stays open and unchanged.
+    /// Streams created by [`to_read_stream`](Self::to_read_stream)/[`to_write_stream`](Self::to_write_stream)
+    /// keep referring to the old file, which is closed when the last of them is dropped.
+    /// As with [`open_with`](Self::open_with), `ops` is not modified.
+    pub async fn reopen<PB: Into<PathBuf>>(&mut self, path: PB, ops: &OpenOptions) -> Result<()> {
+        use super::sys::File;
+
+        self.0.sync()?;
+
+        self.0 = Handle::new(self.0.reactor.clone(), path.into(), &mut ops.clone())?;
+
+        Ok(())
+    }
+
     /// Convert file handle to [`AsyncRead`]
     pub fn to_read_stream<T: Into<Option<Duration>>>(&self, timeout: T) -> FileReader {
         FileReader(self.0.clone(), timeout.into())
@@ -50,6 +81,31 @@ impl File {
     pub fn to_write_stream<T: Into<Option<Duration>>>(&self, timeout: T) -> FileWriter {
         FileWriter(self.0.clone(), timeout.into())
     }
+
+    /// Truncate or extend the file to `size` bytes, zero filled, whatever the current seek position is.
+    pub fn set_len(&self, size: u64) -> Result<()> {
+        use super::sys::File;
+
+        self.0.set_len(size)
+    }
+
+    /// Read into `buf` from `offset`, the file cursor used by the read/write streams is not moved.
+    pub async fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        use super::sys::File;
+
+        let mut handle = self.0.clone();
+
+        futures::future::poll_fn(|cx| handle.poll_read_at(cx, buf, offset)).await
+    }
+
+    /// Write `buf` at `offset`, the file cursor used by the read/write streams is not moved.
+    pub async fn write_at(&self, buf: &[u8], offset: u64) -> Result<usize> {
+        use super::sys::File;
+
+        let mut handle = self.0.clone();
+
+        futures::future::poll_fn(|cx| handle.poll_write_at(cx, buf, offset)).await
+    }
 }
 
 /// File reader stream with operator timeout support
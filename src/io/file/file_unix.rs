@@ -41,7 +41,6 @@ impl Drop for Handle {
 impl Handle {
     fn close(&mut self) {
         unsafe {
-            #[cfg(not(target_os = "linux"))]
             self.reactor.on_close_fd(*self.fd);
             close(*self.fd);
         }
@@ -50,10 +49,118 @@ impl Handle {
     fn to_raw_fd(&self) -> RawFd {
         *self.fd as RawFd
     }
+
+    /// Read at the file cursor, or at `offset` with `pread` if any.
+    fn poll_read_file(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+        buffer: &mut [u8],
+        offset: Option<u64>,
+        timeout: Option<std::time::Duration>,
+    ) -> Poll<Result<usize>> {
+        let fd = self.to_raw_fd();
+
+        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Read)? {
+            event.message?;
+        }
+
+        if self.reactor.trace_enabled() {
+            log::trace!("file({:?}) read({}) offset({:?})", fd, buffer.len(), offset);
+        }
+
+        unsafe {
+            let len = match offset {
+                Some(offset) => pread(
+                    *self.fd,
+                    buffer.as_mut_ptr() as *mut c_void,
+                    buffer.len(),
+                    offset as off_t,
+                ),
+                None => read(*self.fd, buffer.as_mut_ptr() as *mut c_void, buffer.len()),
+            };
+
+            if len < 0 {
+                let e = errno::errno();
+
+                set_errno(e);
+
+                if e.0 == EAGAIN || e.0 == EWOULDBLOCK {
+                    self.reactor.once_labeled(
+                        fd,
+                        EventName::Read,
+                        cx.waker().clone(),
+                        timeout,
+                        "file read",
+                    )?;
+                    return Poll::Pending;
+                } else {
+                    return Poll::Ready(Err(Error::from_raw_os_error(e.0)));
+                }
+            } else {
+                return Poll::Ready(Ok(len as usize));
+            }
+        }
+    }
+
+    /// Write at the file cursor, or at `offset` with `pwrite` if any.
+    fn poll_write_file(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+        buffer: &[u8],
+        offset: Option<u64>,
+        timeout: Option<std::time::Duration>,
+    ) -> Poll<Result<usize>> {
+        let fd = self.to_raw_fd();
+
+        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Write)? {
+            event.message?;
+        }
+
+        if self.reactor.trace_enabled() {
+            log::trace!(
+                "file({:?}) write({}) offset({:?})",
+                fd,
+                buffer.len(),
+                offset
+            );
+        }
+
+        unsafe {
+            let len = match offset {
+                Some(offset) => pwrite(
+                    *self.fd,
+                    buffer.as_ptr() as *const c_void,
+                    buffer.len(),
+                    offset as off_t,
+                ),
+                None => write(*self.fd, buffer.as_ptr() as *const c_void, buffer.len()),
+            };
+
+            if len < 0 {
+                let e = errno::errno();
+
+                set_errno(e);
+
+                if e.0 == EAGAIN || e.0 == EWOULDBLOCK {
+                    self.reactor.once_labeled(
+                        fd,
+                        EventName::Write,
+                        cx.waker().clone(),
+                        timeout,
+                        "file write",
+                    )?;
+                    return Poll::Pending;
+                } else {
+                    return Poll::Ready(Err(Error::from_raw_os_error(e.0)));
+                }
+            } else {
+                return Poll::Ready(Ok(len as usize));
+            }
+        }
+    }
 }
 
 impl sys::File for Handle {
-    #[allow(unused_mut)]
     fn new<P: Into<std::path::PathBuf>>(
         mut reactor: IoReactor,
         path: P,
@@ -70,13 +177,12 @@ impl sys::File for Handle {
                 }
             }
 
-            #[cfg(not(target_os = "linux"))]
-            match reactor.on_open_fd(raw_fd) {
-                Err(err) => {
+            // Regular files never block on epoll systems, pipes/fifos/ttys opened as files do.
+            if reactor.supports(raw_fd) {
+                if let Err(err) = reactor.on_open_fd(raw_fd) {
                     close(raw_fd);
                     return Err(err);
                 }
-                _ => {}
             }
         }
 
@@ -88,6 +194,40 @@ impl sys::File for Handle {
 
         Ok(handle)
     }
+
+    fn sync(&self) -> Result<()> {
+        if unsafe { fsync(self.to_raw_fd()) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn set_len(&self, size: u64) -> Result<()> {
+        if unsafe { ftruncate(self.to_raw_fd(), size as off_t) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn poll_read_at(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+        buffer: &mut [u8],
+        offset: u64,
+    ) -> Poll<Result<usize>> {
+        self.poll_read_file(cx, buffer, Some(offset), None)
+    }
+
+    fn poll_write_at(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+        buffer: &[u8],
+        offset: u64,
+    ) -> Poll<Result<usize>> {
+        self.poll_write_file(cx, buffer, Some(offset), None)
+    }
 }
 
 impl ReactorHandle for Handle {
@@ -118,33 +258,7 @@ impl ReactorHandle for Handle {
         buffer: Self::ReadBuffer<'cx>,
         timeout: Option<std::time::Duration>,
     ) -> std::task::Poll<std::io::Result<usize>> {
-        let fd = self.to_raw_fd();
-
-        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Read)? {
-            event.message?;
-        }
-
-        log::trace!("file({:?}) read({})", fd, buffer.len(),);
-
-        unsafe {
-            let len = read(*self.fd, buffer.as_mut_ptr() as *mut c_void, buffer.len());
-
-            if len < 0 {
-                let e = errno::errno();
-
-                set_errno(e);
-
-                if e.0 == EAGAIN || e.0 == EWOULDBLOCK {
-                    self.reactor
-                        .once(fd, EventName::Read, cx.waker().clone(), timeout);
-                    return Poll::Pending;
-                } else {
-                    return Poll::Ready(Err(Error::from_raw_os_error(e.0)));
-                }
-            } else {
-                return Poll::Ready(Ok(len as usize));
-            }
-        }
+        self.poll_read_file(cx, buffer, None, timeout)
     }
 
     fn poll_write<'cx>(
@@ -153,33 +267,7 @@ impl ReactorHandle for Handle {
         buffer: Self::WriteBuffer<'cx>,
         timeout: Option<std::time::Duration>,
     ) -> std::task::Poll<std::io::Result<usize>> {
-        let fd = self.to_raw_fd();
-
-        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Write)? {
-            event.message?;
-        }
-
-        log::trace!("file({:?}) read({})", fd, buffer.len(),);
-
-        unsafe {
-            let len = write(*self.fd, buffer.as_ptr() as *mut c_void, buffer.len());
-
-            if len < 0 {
-                let e = errno::errno();
-
-                set_errno(e);
-
-                if e.0 == EAGAIN || e.0 == EWOULDBLOCK {
-                    self.reactor
-                        .once(fd, EventName::Write, cx.waker().clone(), timeout);
-                    return Poll::Pending;
-                } else {
-                    return Poll::Ready(Err(Error::from_raw_os_error(e.0)));
-                }
-            } else {
-                return Poll::Ready(Ok(len as usize));
-            }
-        }
+        self.poll_write_file(cx, buffer, None, timeout)
     }
 }
 
@@ -15,7 +15,7 @@ use crate::{
 };
 
 use winapi::{
-    shared::winerror::ERROR_IO_PENDING,
+    shared::{ntdef::LARGE_INTEGER, winerror::ERROR_IO_PENDING},
     um::{errhandlingapi::GetLastError, fileapi::*, handleapi::*},
     um::{minwinbase::*, winbase::*},
 };
@@ -54,67 +54,25 @@ impl Handle {
     fn to_raw_fd(&self) -> RawFd {
         *self.fd as RawFd
     }
-}
-
-impl sys::File for Handle {
-    fn new<P: Into<std::path::PathBuf>>(
-        mut reactor: IoReactor,
-        path: P,
-        ops: &mut std::fs::OpenOptions,
-    ) -> std::io::Result<Self> {
-        let raw_fd = ops
-            .custom_flags(FILE_FLAG_OVERLAPPED)
-            .open(path.into())?
-            .into_raw_handle() as *mut winapi::ctypes::c_void;
 
+    /// Set the file position of overlapped operation, overlapped handles ignore the file cursor.
+    fn set_offset(overlapped: *mut ReactorOverlapped, offset: u64) {
         unsafe {
-            match reactor.on_open_fd(raw_fd) {
-                Err(err) => {
-                    CloseHandle(raw_fd);
-                    return Err(err);
-                }
-                _ => {}
-            }
-        }
-
-        let handle = Handle {
-            reactor,
-            fd: Arc::new(raw_fd),
-            closed: Default::default(),
-        };
+            let position = (*overlapped).overlapped.u.s_mut();
 
-        Ok(handle)
-    }
-}
-
-impl ReactorHandle for Handle {
-    type ReadBuffer<'cx> = &'cx mut [u8];
-
-    type WriteBuffer<'cx> = &'cx [u8];
-
-    fn poll_close(
-        mut self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> Poll<Result<()>> {
-        match self
-            .closed
-            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
-        {
-            Err(_) => Poll::Ready(Ok(())),
-            _ => {
-                self.close();
-
-                Poll::Ready(Ok(()))
-            }
+            position.Offset = offset as u32;
+            position.OffsetHigh = (offset >> 32) as u32;
         }
     }
 
-    fn poll_read<'cx>(
-        mut self: std::pin::Pin<&mut Self>,
+    /// Read with overlapped `ReadFile`, from `offset` if set.
+    fn poll_read_file(
+        &mut self,
         cx: &mut std::task::Context<'_>,
-        buffer: Self::ReadBuffer<'cx>,
+        buffer: &mut [u8],
+        offset: Option<u64>,
         timeout: Option<std::time::Duration>,
-    ) -> std::task::Poll<std::io::Result<usize>> {
+    ) -> Poll<Result<usize>> {
         let fd = self.to_raw_fd();
 
         if let Some(event) = self.reactor.poll_io_event(fd, EventName::Read)? {
@@ -128,9 +86,15 @@ impl ReactorHandle for Handle {
             }
         }
 
-        let overlapped = ReactorOverlapped::new_raw(fd, EventName::Read);
+        let overlapped = self.reactor.overlapped_pool().acquire(fd, EventName::Read);
 
-        log::trace!("file({:?}) read({})", fd, buffer.len(),);
+        if let Some(offset) = offset {
+            Self::set_offset(overlapped, offset);
+        }
+
+        if self.reactor.trace_enabled() {
+            log::trace!("file({:?}) read({})", fd, buffer.len(),);
+        }
 
         unsafe {
             let mut number_of_bytes_read = 0u32;
@@ -142,35 +106,44 @@ impl ReactorHandle for Handle {
                 overlapped as *mut OVERLAPPED,
             );
 
-            log::trace!("file({:?}) read({}) result({})", fd, buffer.len(), ret);
+            if self.reactor.trace_enabled() {
+                log::trace!("file({:?}) read({}) result({})", fd, buffer.len(), ret);
+            }
 
             //  operation has completed immediately
             if ret != 0 {
-                let _: Box<ReactorOverlapped> = overlapped.into();
+                // A completion packet is still queued, the poller returns overlapped to pool.
 
                 return Poll::Ready(Ok(number_of_bytes_read as usize));
             } else {
                 if GetLastError() == ERROR_IO_PENDING {
-                    self.reactor
-                        .once(fd, EventName::Read, cx.waker().clone(), timeout);
+                    self.reactor.once_labeled(
+                        fd,
+                        EventName::Read,
+                        cx.waker().clone(),
+                        timeout,
+                        "file read",
+                    )?;
 
                     return Poll::Pending;
                 }
 
                 // Release overlapped
-                let _: Box<ReactorOverlapped> = overlapped.into();
+                self.reactor.overlapped_pool().release(overlapped);
 
                 return Poll::Ready(Err(Error::last_os_error()));
             }
         }
     }
 
-    fn poll_write<'cx>(
-        mut self: std::pin::Pin<&mut Self>,
+    /// Write with overlapped `WriteFile`, at `offset` if set.
+    fn poll_write_file(
+        &mut self,
         cx: &mut std::task::Context<'_>,
-        buffer: Self::WriteBuffer<'cx>,
+        buffer: &[u8],
+        offset: Option<u64>,
         timeout: Option<std::time::Duration>,
-    ) -> std::task::Poll<std::io::Result<usize>> {
+    ) -> Poll<Result<usize>> {
         let fd = self.to_raw_fd();
 
         if let Some(event) = self.reactor.poll_io_event(fd, EventName::Write)? {
@@ -184,9 +157,15 @@ impl ReactorHandle for Handle {
             }
         }
 
-        let overlapped = ReactorOverlapped::new_raw(fd, EventName::Write);
+        let overlapped = self.reactor.overlapped_pool().acquire(fd, EventName::Write);
 
-        log::trace!("file({:?}) write({})", fd, buffer.len(),);
+        if let Some(offset) = offset {
+            Self::set_offset(overlapped, offset);
+        }
+
+        if self.reactor.trace_enabled() {
+            log::trace!("file({:?}) write({})", fd, buffer.len(),);
+        }
 
         unsafe {
             let mut number_of_bytes_written = 0u32;
@@ -198,23 +177,30 @@ impl ReactorHandle for Handle {
                 overlapped as *mut OVERLAPPED,
             );
 
-            log::trace!("file({:?}) write({}) result({})", fd, buffer.len(), ret);
+            if self.reactor.trace_enabled() {
+                log::trace!("file({:?}) write({}) result({})", fd, buffer.len(), ret);
+            }
 
             //  operation has completed immediately
             if ret != 0 {
-                let _: Box<ReactorOverlapped> = overlapped.into();
+                // A completion packet is still queued, the poller returns overlapped to pool.
 
                 return Poll::Ready(Ok(number_of_bytes_written as usize));
             } else {
                 if GetLastError() == ERROR_IO_PENDING {
-                    self.reactor
-                        .once(fd, EventName::Write, cx.waker().clone(), timeout);
+                    self.reactor.once_labeled(
+                        fd,
+                        EventName::Write,
+                        cx.waker().clone(),
+                        timeout,
+                        "file write",
+                    )?;
 
                     return Poll::Pending;
                 }
 
                 // Release overlapped
-                let _: Box<ReactorOverlapped> = overlapped.into();
+                self.reactor.overlapped_pool().release(overlapped);
 
                 return Poll::Ready(Err(Error::last_os_error()));
             }
@@ -222,6 +208,136 @@ impl ReactorHandle for Handle {
     }
 }
 
+impl sys::File for Handle {
+    fn new<P: Into<std::path::PathBuf>>(
+        mut reactor: IoReactor,
+        path: P,
+        ops: &mut std::fs::OpenOptions,
+    ) -> std::io::Result<Self> {
+        let raw_fd = ops
+            .custom_flags(FILE_FLAG_OVERLAPPED)
+            .open(path.into())?
+            .into_raw_handle() as *mut winapi::ctypes::c_void;
+
+        unsafe {
+            match reactor.on_open_fd(raw_fd) {
+                Err(err) => {
+                    CloseHandle(raw_fd);
+                    return Err(err);
+                }
+                _ => {}
+            }
+        }
+
+        let handle = Handle {
+            reactor,
+            fd: Arc::new(raw_fd),
+            closed: Default::default(),
+        };
+
+        Ok(handle)
+    }
+
+    fn sync(&self) -> Result<()> {
+        if unsafe { FlushFileBuffers(self.to_raw_fd()) } == 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    fn set_len(&self, size: u64) -> Result<()> {
+        let fd = self.to_raw_fd();
+
+        unsafe {
+            let mut distance: LARGE_INTEGER = std::mem::zeroed();
+            let mut current: LARGE_INTEGER = std::mem::zeroed();
+
+            // `SetEndOfFile` truncates at the file pointer, save it to restore afterwards.
+            if SetFilePointerEx(fd, distance, &mut current, FILE_CURRENT) == 0 {
+                return Err(Error::last_os_error());
+            }
+
+            *distance.QuadPart_mut() = size as i64;
+
+            if SetFilePointerEx(fd, distance, std::ptr::null_mut(), FILE_BEGIN) == 0 {
+                return Err(Error::last_os_error());
+            }
+
+            let result = if SetEndOfFile(fd) == 0 {
+                Err(Error::last_os_error())
+            } else {
+                Ok(())
+            };
+
+            if SetFilePointerEx(fd, current, std::ptr::null_mut(), FILE_BEGIN) == 0 {
+                return Err(Error::last_os_error());
+            }
+
+            result
+        }
+    }
+
+    fn poll_read_at(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+        buffer: &mut [u8],
+        offset: u64,
+    ) -> Poll<Result<usize>> {
+        self.poll_read_file(cx, buffer, Some(offset), None)
+    }
+
+    fn poll_write_at(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+        buffer: &[u8],
+        offset: u64,
+    ) -> Poll<Result<usize>> {
+        self.poll_write_file(cx, buffer, Some(offset), None)
+    }
+}
+
+impl ReactorHandle for Handle {
+    type ReadBuffer<'cx> = &'cx mut [u8];
+
+    type WriteBuffer<'cx> = &'cx [u8];
+
+    fn poll_close(
+        mut self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<()>> {
+        match self
+            .closed
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        {
+            Err(_) => Poll::Ready(Ok(())),
+            _ => {
+                self.close();
+
+                Poll::Ready(Ok(()))
+            }
+        }
+    }
+
+    fn poll_read<'cx>(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buffer: Self::ReadBuffer<'cx>,
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        self.poll_read_file(cx, buffer, None, timeout)
+    }
+
+    fn poll_write<'cx>(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buffer: Self::WriteBuffer<'cx>,
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        self.poll_write_file(cx, buffer, None, timeout)
+    }
+}
+
 impl ReactorHandleSeekable for Handle {
     fn seek(
         &mut self,
@@ -0,0 +1,187 @@
+//! Pass listening sockets between processes over unix domain socket (`SCM_RIGHTS`), for graceful upgrade.
+//!
+//! The old process calls [`send_listener`] for each listener and keeps serving in-flight connections,
+//! the new process calls [`recv_listener`] and starts accepting on the same listening socket.
+//! Both calls block on `unix_sock`.
+
+use std::{
+    ffi::c_void,
+    io::{Error, ErrorKind, Result},
+    mem::size_of,
+    os::{fd::AsRawFd, unix::net::UnixStream},
+};
+
+use libc::*;
+
+use super::{
+    socket::{tcp::TcpAcceptor, Handle},
+    IoReactor, RawFd,
+};
+
+/// Payload sent along with the fd, some systems don't deliver ancillary data without payload.
+const HANDOFF_TAG: u8 = b'L';
+
+/// Send `listener` to the peer of `unix_sock`.
+///
+/// The peer receives a duplicate of the listening socket, `listener` stays valid in this process.
+pub fn send_listener(unix_sock: &UnixStream, listener: &TcpAcceptor) -> Result<()> {
+    send_fd(unix_sock, listener.to_raw_fd())
+}
+
+/// Receive a listening socket sent by [`send_listener`] and bind it to `reactor`.
+///
+/// The received fd is adopted as is (nonblocking and registered to `reactor`), `listen` is not called again.
+pub fn recv_listener(unix_sock: &UnixStream, reactor: IoReactor) -> Result<TcpAcceptor> {
+    let fd = recv_fd(unix_sock)?;
+
+    match Handle::is_listener(fd) {
+        Ok(true) => {}
+        Ok(false) => {
+            unsafe { close(fd) };
+
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("received fd({}) is not a listening socket", fd),
+            ));
+        }
+        Err(err) => {
+            unsafe { close(fd) };
+
+            return Err(err);
+        }
+    }
+
+    Ok(TcpAcceptor::from(Handle::adopt(fd, reactor)?))
+}
+
+fn send_fd(unix_sock: &UnixStream, fd: RawFd) -> Result<()> {
+    let mut payload = [HANDOFF_TAG];
+
+    let mut iov = iovec {
+        iov_base: payload.as_mut_ptr() as *mut c_void,
+        iov_len: payload.len(),
+    };
+
+    let mut control = vec![0u8; unsafe { CMSG_SPACE(size_of::<RawFd>() as u32) } as usize];
+
+    unsafe {
+        let mut msg: msghdr = std::mem::zeroed();
+
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = control.len() as _;
+
+        let cmsg = CMSG_FIRSTHDR(&msg);
+
+        (*cmsg).cmsg_level = SOL_SOCKET;
+        (*cmsg).cmsg_type = SCM_RIGHTS;
+        (*cmsg).cmsg_len = CMSG_LEN(size_of::<RawFd>() as u32) as _;
+
+        std::ptr::write_unaligned(CMSG_DATA(cmsg) as *mut RawFd, fd);
+
+        if sendmsg(unix_sock.as_raw_fd(), &msg, 0) < 0 {
+            return Err(Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+fn recv_fd(unix_sock: &UnixStream) -> Result<RawFd> {
+    let mut payload = [0u8; 1];
+
+    let mut iov = iovec {
+        iov_base: payload.as_mut_ptr() as *mut c_void,
+        iov_len: payload.len(),
+    };
+
+    let mut control = vec![0u8; unsafe { CMSG_SPACE(size_of::<RawFd>() as u32) } as usize];
+
+    unsafe {
+        let mut msg: msghdr = std::mem::zeroed();
+
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = control.len() as _;
+
+        // `MSG_CMSG_CLOEXEC` is not portable, `Handle::adopt` sets close-on-exec instead.
+        let len = recvmsg(unix_sock.as_raw_fd(), &mut msg, 0);
+
+        if len < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let cmsg = CMSG_FIRSTHDR(&msg);
+
+        if len == 0
+            || payload[0] != HANDOFF_TAG
+            || cmsg.is_null()
+            || (*cmsg).cmsg_level != SOL_SOCKET
+            || (*cmsg).cmsg_type != SCM_RIGHTS
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "unix socket message does not carry a handoff fd",
+            ));
+        }
+
+        Ok(std::ptr::read_unaligned(CMSG_DATA(cmsg) as *const RawFd))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread::spawn, time::Duration};
+
+    use futures::TryStreamExt;
+
+    use crate::{
+        io::{socket::tcp::TcpStream, IoReactor},
+        Reactor,
+    };
+
+    use super::*;
+
+    #[futures_test::test]
+    async fn test_listener_handoff() {
+        _ = pretty_env_logger::try_init();
+
+        let (old_sock, new_sock) = UnixStream::pair().unwrap();
+
+        let listen_addr = "127.0.0.1:1915".parse().unwrap();
+
+        // Old process.
+        let old_reactor = IoReactor::default();
+
+        let listener = TcpAcceptor::new(old_reactor.clone(), listen_addr, None).unwrap();
+
+        let sender = spawn(move || {
+            send_listener(&old_sock, &listener).unwrap();
+
+            drop(listener);
+        });
+
+        // New process.
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let mut acceptor = recv_listener(&new_sock, reactor.clone()).unwrap();
+
+        sender.join().unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+        client.unwrap();
+
+        assert!(accept.unwrap().is_some());
+    }
+}
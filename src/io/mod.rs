@@ -2,8 +2,20 @@
 mod poller;
 pub use poller::*;
 
+#[cfg(target_family = "unix")]
+pub mod async_fd;
+pub mod dns;
 pub mod file;
+#[cfg(target_family = "unix")]
+pub mod handoff;
+pub mod mpsc;
 pub mod socket;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod timerfd;
+pub mod util;
+
+#[cfg(feature = "rustls")]
+pub mod tls;
 
 #[cfg(target_family = "unix")]
 unsafe fn noblock(fd: i32) -> std::io::Result<()> {
@@ -0,0 +1,224 @@
+//! Bounded multi-producer single-consumer channel, bridging blocking producer threads to an async consumer.
+//!
+//! The receiver is a [`Stream`], senders wake its task from any thread through the task waker,
+//! so the consumer can run on any executor driven by an [`IoReactor`](super::IoReactor).
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        mpsc::{SendError, TrySendError},
+        Arc, Condvar, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use futures::Stream;
+
+#[derive(Debug)]
+struct State<T> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    receiver_waker: Option<Waker>,
+    senders: usize,
+    receiver_closed: bool,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    not_full: Condvar,
+}
+
+/// Create a channel queueing up to `capacity` items, [`Sender::send`] blocks while the queue is full.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity.max(1)),
+            capacity: capacity.max(1),
+            receiver_waker: None,
+            senders: 1,
+            receiver_closed: false,
+        }),
+        not_full: Condvar::new(),
+    });
+
+    (Sender(shared.clone()), Receiver(shared))
+}
+
+/// Sending half of [`channel`], can be cloned and moved to other threads.
+#[derive(Debug)]
+pub struct Sender<T>(Arc<Shared<T>>);
+
+impl<T> Sender<T> {
+    /// Push `value` to the channel, blocking the calling thread while the queue is full.
+    ///
+    /// Returns the value back if the receiver is dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut state = self.0.state.lock().unwrap();
+
+        while !state.receiver_closed && state.queue.len() >= state.capacity {
+            state = self.0.not_full.wait(state).unwrap();
+        }
+
+        if state.receiver_closed {
+            return Err(SendError(value));
+        }
+
+        state.queue.push_back(value);
+
+        let waker = state.receiver_waker.take();
+
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+
+    /// Push `value` to the channel without blocking.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut state = self.0.state.lock().unwrap();
+
+        if state.receiver_closed {
+            return Err(TrySendError::Disconnected(value));
+        }
+
+        if state.queue.len() >= state.capacity {
+            return Err(TrySendError::Full(value));
+        }
+
+        state.queue.push_back(value);
+
+        let waker = state.receiver_waker.take();
+
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.0.state.lock().unwrap().senders += 1;
+
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+
+        state.senders -= 1;
+
+        // Last sender gone, wake the receiver to end the stream.
+        let waker = if state.senders == 0 {
+            state.receiver_waker.take()
+        } else {
+            None
+        };
+
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Receiving half of [`channel`], yields items in send order and ends after all senders are dropped.
+#[derive(Debug)]
+pub struct Receiver<T>(Arc<Shared<T>>);
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.0.state.lock().unwrap();
+
+        if let Some(value) = state.queue.pop_front() {
+            drop(state);
+
+            self.0.not_full.notify_one();
+
+            return Poll::Ready(Some(value));
+        }
+
+        if state.senders == 0 {
+            return Poll::Ready(None);
+        }
+
+        match &state.receiver_waker {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            _ => state.receiver_waker = Some(cx.waker().clone()),
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().receiver_closed = true;
+
+        // Unblock senders waiting for room.
+        self.0.not_full.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::spawn;
+
+    use futures::StreamExt;
+
+    use super::*;
+
+    #[futures_test::test]
+    async fn test_channel() {
+        let (sender, receiver) = channel(16);
+
+        let producer = spawn(move || {
+            for i in 0..1000 {
+                sender.send(i).unwrap();
+            }
+        });
+
+        let received = receiver.collect::<Vec<_>>().await;
+
+        producer.join().unwrap();
+
+        assert_eq!(received, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_receiver_dropped() {
+        let (sender, receiver) = channel(1);
+
+        sender.send(1).unwrap();
+
+        assert!(matches!(sender.try_send(2), Err(TrySendError::Full(2))));
+
+        let blocked = {
+            let sender = sender.clone();
+
+            spawn(move || sender.send(3))
+        };
+
+        drop(receiver);
+
+        assert_eq!(blocked.join().unwrap(), Err(SendError(3)));
+
+        assert!(matches!(
+            sender.try_send(4),
+            Err(TrySendError::Disconnected(4))
+        ));
+    }
+}
@@ -8,16 +8,24 @@ mod os;
 pub use os::*;
 
 use std::{
-    collections::HashMap,
+    cell::Cell,
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Debug,
     hash::Hash,
     io::{Error, ErrorKind, Result},
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
     task::{Poll, Waker},
-    time::{Duration, SystemTime},
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
-use crate::{timewheel::TimeWheel, Reactor};
+use crate::{
+    timewheel::{TimeWheel, TimeoutHandle},
+    Reactor,
+};
 
 /// Cross-platform raw file description type.
 #[cfg(target_family = "unix")]
@@ -70,66 +78,296 @@ impl Event {
     }
 }
 
+/// Readiness of one fd reported by [`IoReactor::poll_ready_fds`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Readiness {
+    /// Read (or accept/recvfrom on iocp) registration is ready.
+    pub readable: bool,
+    /// Write (or connect/sendto on iocp) registration is ready.
+    pub writable: bool,
+}
+
+impl Readiness {
+    fn insert(&mut self, name: &EventName) {
+        #[cfg(target_family = "unix")]
+        let readable = *name == EventName::Read;
+
+        #[cfg(target_family = "windows")]
+        let readable = matches!(
+            name,
+            EventName::Read | EventName::RecvFrom | EventName::Accept
+        );
+
+        if readable {
+            self.readable = true;
+        } else {
+            self.writable = true;
+        }
+    }
+}
+
+/// What the thread of [`IoReactor::spawn_poll_thread_with`] does when a poll fails.
+///
+/// A [`close`](IoReactor::close)d reactor stops the thread under any policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PollThreadPolicy {
+    /// Stop the thread, its joined result is the poll error.
+    #[default]
+    Fail,
+    /// Log the error and poll again after `initial_backoff`, doubled after each consecutive failure
+    /// up to `max_backoff`. A successful poll resets the backoff.
+    Retry {
+        initial_backoff: Duration,
+        max_backoff: Duration,
+    },
+}
+
+/// Poller backend description returned by [`IoReactor::backend_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackendInfo {
+    /// Backend name, `epoll`, `kqueue` or `iocp`.
+    pub name: &'static str,
+    /// Regular files can be registered, otherwise file io should go to a blocking pool.
+    pub supports_files: bool,
+    /// Registrations are edge triggered.
+    pub supports_edge_trigger: bool,
+    /// Max events dequeued by one system call, `usize::MAX` if the buffer grows with pending registrations.
+    pub max_batch: usize,
+}
+
+/// Duration of each poll made by [`IoReactor::poll_until_idle`].
+const IDLE_POLL_DURATION: Duration = Duration::from_millis(10);
+
+/// Default slots per [`TimeWheel`] level.
+const TIME_WHEEL_STEPS: u64 = 3600;
+
+/// Default [`TimeWheel`] levels, covers `3600^3` ticks.
+const TIME_WHEEL_LEVELS: usize = 3;
+
+/// Default max events pulled from the backend per [`poll_once`](Reactor::poll_once).
+const DEFAULT_MAX_EVENTS: usize = 1024;
+
+/// Entry of [`EventLoop`] time wheel.
+#[derive(Debug, PartialEq)]
+enum Timeout {
+    /// Timeout of io registration.
+    Io(Key),
+    /// Standalone timer, see [`IoReactor::add_timer`].
+    Timer(u64),
+}
+
+/// Standalone timers with their own wheel and tick, see [`IoReactor::timer_category`].
+#[derive(Debug)]
+struct TimerCategory {
+    tick_duration: Duration,
+    time_wheel: TimeWheel<u64>,
+    last_poll_time: Instant,
+}
+
 #[derive(Debug)]
 struct EventLoop {
     sending: HashMap<Key, Waker>,
     received: HashMap<Key, Event>,
-    time_wheel: TimeWheel<Key>,
-    last_poll_time: SystemTime,
+    labels: HashMap<Key, &'static str>,
+    time_wheel: TimeWheel<Timeout>,
+    /// Time wheel entry of each registration with timeout.
+    io_timeouts: HashMap<Key, TimeoutHandle>,
+    timers: HashMap<u64, Waker>,
+    fired_timers: HashSet<u64>,
+    next_timer: u64,
+    last_poll_time: Instant,
+    max_registrations: Option<usize>,
+    fds: HashSet<RawFd>,
+    max_wakes_per_poll: Option<usize>,
+    /// Wakers of resolved operations over the `max_wakes_per_poll` limit, woken by following polls.
+    deferred_wakes: VecDeque<Waker>,
+    /// A poll is blocking on the backend with a snapshot of the registrations, and was not woken yet.
+    polling: bool,
+    /// Set by [`IoReactor::close`], registrations and polls fail from now on.
+    closed: bool,
+    categories: HashMap<&'static str, TimerCategory>,
+    /// Category of pending timers not in the default wheel.
+    timer_categories: HashMap<u64, &'static str>,
+    /// Time wheel entry of each pending timer, in the wheel of its category if any.
+    timer_timeouts: HashMap<u64, TimeoutHandle>,
 }
 
 impl EventLoop {
-    fn new(steps: u64) -> Self {
+    fn new(steps: u64, levels: usize) -> Self {
         Self {
             sending: Default::default(),
             received: Default::default(),
-            time_wheel: TimeWheel::new(steps),
-            last_poll_time: SystemTime::now(),
+            labels: Default::default(),
+            time_wheel: TimeWheel::with_levels(steps, levels),
+            io_timeouts: Default::default(),
+            timers: Default::default(),
+            fired_timers: Default::default(),
+            next_timer: 0,
+            last_poll_time: Instant::now(),
+            max_registrations: None,
+            fds: Default::default(),
+            max_wakes_per_poll: None,
+            deferred_wakes: Default::default(),
+            polling: false,
+            closed: false,
+            categories: Default::default(),
+            timer_categories: Default::default(),
+            timer_timeouts: Default::default(),
+        }
+    }
+
+    /// Remove the pending timeout of registration `key` from the time wheel, if any.
+    fn cancel_io_timeout(&mut self, key: &Key) {
+        if let Some(handle) = self.io_timeouts.remove(key) {
+            self.time_wheel.remove(handle);
+        }
+    }
+}
+
+thread_local! {
+    /// Set while the current thread runs [`IoReactor::poll_once`](Reactor::poll_once).
+    static POLLING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks the current thread as polling until dropped.
+struct PollingGuard;
+
+impl PollingGuard {
+    /// Returns [`None`] if the current thread is already polling.
+    fn enter() -> Option<Self> {
+        if POLLING.with(|polling| polling.replace(true)) {
+            None
+        } else {
+            Some(Self)
         }
     }
 }
 
+impl Drop for PollingGuard {
+    fn drop(&mut self) {
+        POLLING.with(|polling| polling.set(false));
+    }
+}
+
 /// Io reactor implementation.
 #[derive(Clone, Debug)]
 pub struct IoReactor {
     poller: SysPoller,
     event_loop: Arc<Mutex<EventLoop>>,
     tick_duration: Duration,
+    trace: Arc<AtomicBool>,
 }
 
 impl IoReactor {
+    /// Number of whole `tick_duration` ticks in `duration`, ticks may be shorter than one millisecond.
+    fn ticks(duration: Duration, tick_duration: &Duration) -> u64 {
+        (duration.as_nanos() / tick_duration.as_nanos().max(1)) as u64
+    }
+
+    /// Time until the earliest timeout or timer of all wheels is due, [`None`] if none is pending.
+    fn next_timeout(event_loop: &EventLoop, tick_duration: &Duration) -> Option<Duration> {
+        let default = event_loop
+            .time_wheel
+            .next_expiration()
+            .map(|ticks| (event_loop.last_poll_time, *tick_duration, ticks));
+
+        let categories = event_loop.categories.values().filter_map(|category| {
+            category
+                .time_wheel
+                .next_expiration()
+                .map(|ticks| (category.last_poll_time, category.tick_duration, ticks))
+        });
+
+        let now = Instant::now();
+
+        default
+            .into_iter()
+            .chain(categories)
+            .filter_map(|(last_poll_time, tick_duration, ticks)| {
+                let ticks = u32::try_from(ticks).unwrap_or(u32::MAX);
+
+                last_poll_time.checked_add(tick_duration.saturating_mul(ticks))
+            })
+            .map(|deadline| deadline.saturating_duration_since(now))
+            .min()
+    }
+
     fn poll_timeout(
         event_loop: &mut MutexGuard<EventLoop>,
         tick_duration: &Duration,
     ) -> Vec<Waker> {
-        let elapsed = event_loop.last_poll_time.elapsed().unwrap();
+        let elapsed = event_loop.last_poll_time.elapsed();
 
-        // update last poll time
-        event_loop.last_poll_time = SystemTime::now();
+        // Ticks beyond `u32::MAX` are left to the following polls.
+        let steps = u32::try_from(Self::ticks(elapsed, tick_duration)).unwrap_or(u32::MAX);
 
-        let steps = (elapsed.as_millis() / tick_duration.as_millis()) as u64;
+        // Keep the remainder of a partial tick, polls shorter than one tick must not stall the time wheel.
+        event_loop.last_poll_time += *tick_duration * steps;
 
         let mut wakers = vec![];
 
-        for _ in 0..steps {
-            if let Poll::Ready(keys) = event_loop.time_wheel.tick() {
-                for key in keys {
-                    // Get waker
-                    if let Some(waker) = event_loop.sending.remove(&key) {
+        for timeout in event_loop.time_wheel.advance(steps.into()) {
+            let key = match timeout {
+                Timeout::Io(key) => {
+                    event_loop.io_timeouts.remove(&key);
+
+                    key
+                }
+                Timeout::Timer(id) => {
+                    event_loop.timer_timeouts.remove(&id);
+
+                    if let Some(waker) = event_loop.timers.remove(&id) {
+                        event_loop.fired_timers.insert(id);
                         wakers.push(waker);
                     }
 
-                    // Insert timeout result
-                    event_loop.received.insert(
-                        key.clone(),
-                        Event::from_error(
-                            key.clone(),
-                            Error::new(
-                                ErrorKind::TimedOut,
-                                format!("fd({}) {:?} timeout", key.0 as usize, key.1),
-                            ),
+                    continue;
+                }
+            };
+
+            // Get waker, the operation may already be resolved by an io event in this poll.
+            match event_loop.sending.remove(&key) {
+                Some(waker) => wakers.push(waker),
+                None => continue,
+            }
+
+            let label = event_loop.labels.remove(&key).unwrap_or_default();
+
+            // Insert timeout result
+            event_loop.received.insert(
+                key.clone(),
+                Event::from_error(
+                    key.clone(),
+                    Error::new(
+                        ErrorKind::TimedOut,
+                        format!(
+                            "fd({}) {:?} label({}) timeout",
+                            key.0 as usize, key.1, label
                         ),
-                    );
+                    ),
+                ),
+            );
+        }
+
+        let event_loop = &mut **event_loop;
+
+        // Each category advances at its own tick, coarse wheels are rarely ticked.
+        for category in event_loop.categories.values_mut() {
+            let steps = u32::try_from(Self::ticks(
+                category.last_poll_time.elapsed(),
+                &category.tick_duration,
+            ))
+            .unwrap_or(u32::MAX);
+
+            category.last_poll_time += category.tick_duration * steps;
+
+            for id in category.time_wheel.advance(steps.into()) {
+                event_loop.timer_categories.remove(&id);
+                event_loop.timer_timeouts.remove(&id);
+
+                if let Some(waker) = event_loop.timers.remove(&id) {
+                    event_loop.fired_timers.insert(id);
+                    wakers.push(waker);
                 }
             }
         }
@@ -147,14 +385,53 @@ impl Default for IoReactor {
 impl IoReactor {
     /// Create new [`IoReactor`] instance with `tick_duration`.
     ///
-    /// - `tick_duration` The time precision of [`TimeWheel`] that will be used for the timeout operation.
+    /// - `tick_duration` The time precision of [`TimeWheel`] that will be used for the timeout operation,
+    ///   measured with the monotonic clock. Sub-millisecond ticks (e.g. `Duration::from_micros(100)`) are
+    ///   supported, idle ticks are skipped at once by [`poll_once`](Reactor::poll_once).
+    ///   A zero `tick_duration` fails with `InvalidInput`.
     pub fn new(tick_duration: Duration) -> Result<Self> {
-        let poller = SysPoller::new()?;
+        Self::with_time_wheel(tick_duration, TIME_WHEEL_STEPS, TIME_WHEEL_LEVELS)
+    }
+
+    /// Create new [`IoReactor`] instance with a [`TimeWheel`] of `levels` cascading levels, `steps` slots each.
+    ///
+    /// Timeouts up to `tick_duration * steps^levels` are scheduled without revisiting.
+    pub fn with_time_wheel(tick_duration: Duration, steps: u64, levels: usize) -> Result<Self> {
+        Self::with_poller(SysPoller::new()?, tick_duration, steps, levels)
+    }
+
+    /// Create new [`IoReactor`] instance pulling at most `max_events` events from the backend per
+    /// [`poll_once`](Reactor::poll_once), default 1024.
+    ///
+    /// The backend output buffer is allocated once with this size. Events beyond the limit are not lost,
+    /// the following polls return them, so a burst of ready fds can't make one poll arbitrarily long.
+    pub fn with_max_events(tick_duration: Duration, max_events: usize) -> Result<Self> {
+        Self::with_poller(
+            SysPoller::with_max_events(max_events)?,
+            tick_duration,
+            TIME_WHEEL_STEPS,
+            TIME_WHEEL_LEVELS,
+        )
+    }
+
+    fn with_poller(
+        poller: SysPoller,
+        tick_duration: Duration,
+        steps: u64,
+        levels: usize,
+    ) -> Result<Self> {
+        if tick_duration.is_zero() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "reactor tick duration must not be zero",
+            ));
+        }
 
         Ok(Self {
             poller,
-            event_loop: Arc::new(Mutex::new(EventLoop::new(3600))),
+            event_loop: Arc::new(Mutex::new(EventLoop::new(steps, levels))),
             tick_duration,
+            trace: Arc::new(AtomicBool::new(true)),
         })
     }
     pub fn on_close_fd(&mut self, fd: super::RawFd) {
@@ -162,6 +439,8 @@ impl IoReactor {
 
         let mut event_loop = self.event_loop.lock().unwrap();
 
+        event_loop.fds.remove(&fd);
+
         let mut keys = vec![];
 
         for (key, _) in &event_loop.sending {
@@ -172,128 +451,880 @@ impl IoReactor {
 
         for key in keys {
             event_loop.sending.remove(&key);
+            event_loop.labels.remove(&key);
+            event_loop.cancel_io_timeout(&key);
         }
     }
 
     pub fn on_open_fd(&mut self, fd: super::RawFd) -> Result<()> {
-        self.poller.on_open_fd(fd)
+        self.poller.on_open_fd(fd)?;
+
+        self.event_loop.lock().unwrap().fds.insert(fd);
+
+        Ok(())
     }
 
-    pub fn once(
-        &mut self,
-        fd: super::RawFd,
-        name: EventName,
-        waker: Waker,
-        timeout: Option<Duration>,
-    ) {
-        log::debug!(
-            "fd({:?}) register event({:?}) timeout({:?})",
-            fd,
-            name,
-            timeout
-        );
+    /// Same as [`on_open_fd`](IoReactor::on_open_fd), for a fd watched by several reactors (e.g. a shared listener).
+    ///
+    /// On linux the fd is added with `EPOLLEXCLUSIVE`, so a readiness change wakes one of the
+    /// reactors instead of all of them. The fd is only in the epoll set while an operation waits on it,
+    /// so pending connections of a listener not accepting (e.g. out of limiter permits) don't wake every poll.
+    #[cfg(target_family = "unix")]
+    pub fn on_open_fd_exclusive(&mut self, fd: super::RawFd) -> Result<()> {
+        self.poller.on_open_fd_exclusive(fd)?;
 
-        let mut event_loop = self.event_loop.lock().unwrap();
+        self.event_loop.lock().unwrap().fds.insert(fd);
 
-        let key = Key(fd, name.clone());
+        Ok(())
+    }
 
-        event_loop.sending.insert(key.clone(), waker);
+    /// Duplicate all fds bound to this reactor, for handing them off to a successor process.
+    ///
+    /// The returned fds are close-on-exec duplicates owned by the caller, closing the
+    /// reactor handles afterwards does not affect them. The handoff protocol is:
+    ///
+    /// 1. Stop accepting new connections and call `export_fds`.
+    /// 2. Pass the fds to the successor, e.g. via `SCM_RIGHTS` or by clearing `FD_CLOEXEC` before `exec`.
+    /// 3. Successor adopts each fd with [`Handle::adopt`](crate::io::socket::Handle::adopt),
+    ///    [`Handle::is_listener`](crate::io::socket::Handle::is_listener) tells listeners from connections.
+    /// 4. Predecessor drops its handles and exits.
+    #[cfg(target_family = "unix")]
+    pub fn export_fds(&self) -> Result<Vec<RawFd>> {
+        let fds = {
+            let event_loop = self.event_loop.lock().unwrap();
 
-        if let Some(timeout) = timeout {
-            let timeout = (timeout.as_millis() / self.tick_duration.as_millis()) as u64;
+            let mut fds = event_loop.fds.iter().copied().collect::<Vec<_>>();
 
-            event_loop.time_wheel.add(timeout, key);
+            fds.sort();
+
+            fds
+        };
+
+        let mut exported = vec![];
+
+        for fd in fds {
+            let dup = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+
+            if dup < 0 {
+                let err = Error::last_os_error();
+
+                for fd in exported {
+                    unsafe { libc::close(fd) };
+                }
+
+                return Err(err);
+            }
+
+            exported.push(dup);
         }
+
+        Ok(exported)
     }
 
-    pub fn remove_once(&mut self, fd: super::RawFd, name: EventName) {
-        let mut event_loop = self.event_loop.lock().unwrap();
+    /// Get [`ReactorOverlapped`] pool shared by all handles bound to this reactor.
+    #[cfg(target_family = "windows")]
+    pub(crate) fn overlapped_pool(&self) -> &OverlappedPool {
+        self.poller.overlapped_pool()
+    }
 
-        let key = Key(fd, name.clone());
+    /// Set max iocp completions dequeued per system call, see [`SysPoller::set_batch_size`].
+    #[cfg(target_family = "windows")]
+    pub fn set_completion_batch_size(&self, batch_size: usize) {
+        self.poller.set_batch_size(batch_size)
+    }
 
-        event_loop.sending.remove(&key);
+    /// Enable or disable per-operation diagnostic logs of this reactor and its handles (enabled by default).
+    ///
+    /// When disabled, hot-path log records are skipped before formatting, regardless of the `log` level.
+    pub fn set_trace(&self, enable: bool) {
+        self.trace.store(enable, Ordering::Relaxed);
     }
 
-    pub fn poll_io_event(&mut self, fd: super::RawFd, name: EventName) -> Result<Option<Event>> {
-        let mut event_loop = self.event_loop.lock().unwrap();
+    /// Check if per-operation diagnostic logs are enabled, see [`set_trace`](IoReactor::set_trace).
+    pub fn trace_enabled(&self) -> bool {
+        self.trace.load(Ordering::Relaxed)
+    }
 
-        Ok(event_loop.received.remove(&Key(fd, name)))
+    /// Set the max number of pending registrations, `None` means unlimited (default).
+    ///
+    /// When the limit is reached, [`once`](IoReactor::once) returns an error instead of registering.
+    pub fn set_max_registrations(&self, max: Option<usize>) {
+        self.event_loop.lock().unwrap().max_registrations = max;
     }
-}
 
-impl Reactor for IoReactor {
-    fn poll_once(&mut self, duration: Duration) -> Result<usize> {
-        let event_keys = {
-            let event_loop = self.event_loop.lock().unwrap();
+    /// Set the max number of tasks woken by one [`poll_once`](Reactor::poll_once), `None` means unlimited (default).
+    ///
+    /// Operations resolved over the limit keep their events, their tasks are woken by the following polls
+    /// (which don't block while any wake is deferred), so one busy poll doesn't delay the driver loop.
+    pub fn set_max_wakes_per_poll(&self, max: Option<usize>) {
+        self.event_loop.lock().unwrap().max_wakes_per_poll = max.map(|max| max.max(1));
+    }
+
+    /// Get the number of tasks whose operations are resolved but not woken yet, see [`set_max_wakes_per_poll`](Self::set_max_wakes_per_poll).
+    pub fn deferred_wakes(&self) -> usize {
+        self.event_loop.lock().unwrap().deferred_wakes.len()
+    }
+
+    /// Close this reactor for all clones, pending operations resolve with an [`ErrorKind::Interrupted`] error.
+    ///
+    /// Pending timers fire, and polls blocking in other threads return. Registrations and polls made
+    /// afterwards fail with [`ErrorKind::NotConnected`], which unlike `Interrupted` isn't retried (e.g. by
+    /// [`copy_with`](super::util::copy_with)), so relays, driver loops and [`spawn_poll_thread`](Self::spawn_poll_thread)
+    /// stop. The poller handle (epoll/kqueue fd, iocp handle) is closed once the blocking polls returned.
+    pub fn close(&self) {
+        let wakers = {
+            let mut event_loop = self.event_loop.lock().unwrap();
+
+            if event_loop.closed {
+                return;
+            }
+
+            event_loop.closed = true;
+
+            let mut wakers = vec![];
+
+            let pending = event_loop.sending.drain().collect::<Vec<_>>();
 
-            let mut keys = vec![];
+            for (key, waker) in pending {
+                let label = event_loop.labels.remove(&key).unwrap_or_default();
 
-            for (k, _) in &event_loop.sending {
-                keys.push(k.clone());
+                event_loop.cancel_io_timeout(&key);
+
+                event_loop.received.insert(
+                    key.clone(),
+                    Event::from_error(
+                        key.clone(),
+                        Error::new(
+                            ErrorKind::Interrupted,
+                            format!(
+                                "fd({}) {:?} label({}) reactor closed",
+                                key.0 as usize, key.1, label
+                            ),
+                        ),
+                    ),
+                );
+
+                wakers.push(waker);
             }
 
-            keys
-        };
+            event_loop.timer_categories.clear();
+            event_loop.timer_timeouts.clear();
 
-        let events = if !event_keys.is_empty() {
-            log::debug!("poll event keys({:?})", event_keys);
-            self.poller.poll_once(&event_keys, duration)?
-        } else {
-            vec![]
+            let timers = event_loop.timers.drain().collect::<Vec<_>>();
+
+            for (id, waker) in timers {
+                event_loop.fired_timers.insert(id);
+
+                wakers.push(waker);
+            }
+
+            wakers.extend(event_loop.deferred_wakes.drain(..));
+
+            wakers
         };
 
-        let (wakers, timeout_wakers) = {
-            let mut wakers = vec![];
-            let mut event_loop = self.event_loop.lock().unwrap();
+        if let Err(err) = self.poller.close() {
+            log::error!("close poller failed: {}", err);
+        }
 
-            for event in events {
-                if let Some(waker) = event_loop.sending.remove(event.key()) {
-                    log::debug!("wakeup {:?}", event.key);
-                    wakers.push(waker);
+        for waker in wakers {
+            waker.wake();
+        }
+    }
 
-                    event_loop.received.insert(event.key().clone(), event);
+    /// Interrupt a [`poll_once`](Reactor::poll_once) blocking in another thread, or make the next one return at once.
+    ///
+    /// Registrations made while a poll is blocking already notify it, this is for state changes
+    /// the reactor doesn't see, e.g. a driver loop checking a stop flag between polls.
+    pub fn notify(&self) -> Result<()> {
+        self.poller.wakeup()
+    }
+
+    /// Get the number of pending registrations.
+    pub fn registrations(&self) -> usize {
+        self.event_loop.lock().unwrap().sending.len()
+    }
+
+    /// Time until the earliest registration timeout or timer is due, [`None`] if none is pending.
+    ///
+    /// An external event loop can pass it as [`poll_once`](Reactor::poll_once) duration to wake when
+    /// the timeout is due. Precision is the `tick_duration` of the wheel holding it.
+    pub fn time_until_next_timeout(&self) -> Option<Duration> {
+        Self::next_timeout(&self.event_loop.lock().unwrap(), &self.tick_duration)
+    }
+
+    /// Poll until no io registration or timer is pending, or `max_iters` polls are made,
+    /// returns the number of polls made.
+    ///
+    /// Useful to drive futures deterministically in tests: poll the futures, then poll the reactor until idle.
+    pub fn poll_until_idle(&mut self, max_iters: usize) -> Result<usize> {
+        let mut iters = 0;
+
+        while iters < max_iters && !self.is_idle() {
+            self.poll_once(IDLE_POLL_DURATION)?;
+
+            iters += 1;
+        }
+
+        Ok(iters)
+    }
+
+    /// Spawn a thread polling this reactor with `duration`, until all clones of this reactor are dropped.
+    ///
+    /// The thread holds no strong reference between polls, so it exits within about `duration`
+    /// after the last clone drops. The joined result is the poll error which stopped the thread, if any.
+    pub fn spawn_poll_thread(&self, duration: Duration) -> Result<JoinHandle<Result<()>>> {
+        self.spawn_poll_thread_with(duration, PollThreadPolicy::Fail)
+    }
+
+    /// Same as [`spawn_poll_thread`](Self::spawn_poll_thread), with `policy` deciding whether a poll error
+    /// stops the thread, e.g. so a transient backend error doesn't stop all io of this reactor.
+    pub fn spawn_poll_thread_with(
+        &self,
+        duration: Duration,
+        policy: PollThreadPolicy,
+    ) -> Result<JoinHandle<Result<()>>> {
+        let poller = self.poller.clone();
+        let event_loop = Arc::downgrade(&self.event_loop);
+        let tick_duration = self.tick_duration;
+        let trace = self.trace.clone();
+
+        std::thread::Builder::new()
+            .name("reactors-poll".to_owned())
+            .spawn(move || {
+                let mut backoff = None;
+
+                while let Some(event_loop) = event_loop.upgrade() {
+                    let mut reactor = IoReactor {
+                        poller: poller.clone(),
+                        event_loop,
+                        tick_duration,
+                        trace: trace.clone(),
+                    };
+
+                    let err = match reactor.poll_once(duration) {
+                        Ok(_) => {
+                            backoff = None;
+
+                            continue;
+                        }
+                        Err(err) => err,
+                    };
+
+                    let closed = reactor.event_loop.lock().unwrap().closed;
+
+                    let delay = match policy {
+                        PollThreadPolicy::Retry {
+                            initial_backoff,
+                            max_backoff,
+                        } if !closed => backoff.map_or(initial_backoff, |backoff: Duration| {
+                            backoff.saturating_mul(2).min(max_backoff)
+                        }),
+                        _ => return Err(err),
+                    };
+
+                    log::error!("poll thread error({}), retry in {:?}", err, delay);
+
+                    backoff = Some(delay);
+
+                    // Don't keep the reactor alive while waiting.
+                    drop(reactor);
+
+                    std::thread::sleep(delay);
                 }
+
+                log::debug!("all reactor clones dropped, poll thread exits");
+
+                Ok(())
+            })
+    }
+
+    fn is_idle(&self) -> bool {
+        let event_loop = self.event_loop.lock().unwrap();
+
+        event_loop.sending.is_empty()
+            && event_loop.timers.is_empty()
+            && event_loop.deferred_wakes.is_empty()
+    }
+
+    pub fn once(
+        &mut self,
+        fd: super::RawFd,
+        name: EventName,
+        waker: Waker,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        self.register_once(fd, name, waker, timeout, None)
+    }
+
+    /// Same as [`once`](IoReactor::once), but attach a `label` describing the logical operation to the registration.
+    ///
+    /// The label is included in log output, timeout errors and [`debug_dump`](IoReactor::debug_dump).
+    pub fn once_labeled(
+        &mut self,
+        fd: super::RawFd,
+        name: EventName,
+        waker: Waker,
+        timeout: Option<Duration>,
+        label: &'static str,
+    ) -> Result<()> {
+        self.register_once(fd, name, waker, timeout, Some(label))
+    }
+
+    /// Register all `ops` with one event loop lock, useful for tasks waiting on several interests.
+    ///
+    /// Either all operations are registered or none, if [`max_registrations`](IoReactor::set_max_registrations)
+    /// would be exceeded an error is returned.
+    pub fn once_many(
+        &mut self,
+        ops: &[(super::RawFd, EventName, Waker, Option<Duration>)],
+    ) -> Result<()> {
+        let mut event_loop = self.event_loop.lock().unwrap();
+
+        if event_loop.closed {
+            return Err(Error::new(ErrorKind::NotConnected, "reactor closed"));
+        }
+
+        if let Some(max) = event_loop.max_registrations {
+            let added = ops
+                .iter()
+                .map(|(fd, name, _, _)| Key(*fd, name.clone()))
+                .filter(|key| !event_loop.sending.contains_key(key))
+                .collect::<HashSet<_>>()
+                .len();
+
+            if event_loop.sending.len() + added > max {
+                return Err(Error::other(format!(
+                    "{} registrations exceed max registrations({})",
+                    ops.len(),
+                    max
+                )));
             }
+        }
 
-            let timeout_wakers = Self::poll_timeout(&mut event_loop, &self.tick_duration);
+        for (fd, name, waker, timeout) in ops {
+            self.insert_once(
+                &mut event_loop,
+                *fd,
+                name.clone(),
+                waker.clone(),
+                *timeout,
+                None,
+            );
+        }
 
-            (wakers, timeout_wakers)
-        };
+        Ok(())
+    }
 
-        for waker in &wakers {
-            waker.wake_by_ref();
+    fn register_once(
+        &mut self,
+        fd: super::RawFd,
+        name: EventName,
+        waker: Waker,
+        timeout: Option<Duration>,
+        label: Option<&'static str>,
+    ) -> Result<()> {
+        let mut event_loop = self.event_loop.lock().unwrap();
+
+        if event_loop.closed {
+            return Err(Error::new(
+                ErrorKind::NotConnected,
+                format!("fd({:?}) {:?} reactor closed", fd, name),
+            ));
         }
 
-        for waker in &timeout_wakers {
-            waker.wake_by_ref();
+        if let Some(max) = event_loop.max_registrations {
+            let key = Key(fd, name.clone());
+
+            if event_loop.sending.len() >= max && !event_loop.sending.contains_key(&key) {
+                return Err(Error::other(format!(
+                    "fd({:?}) {:?} exceeds max registrations({})",
+                    fd, name, max
+                )));
+            }
         }
 
-        Ok(wakers.len() + timeout_wakers.len())
+        self.insert_once(&mut event_loop, fd, name, waker, timeout, label);
+
+        Ok(())
     }
-}
 
-#[cfg(all(test, target_family = "macos"))]
-mod tests {
-    use futures::task::noop_waker;
+    fn insert_once(
+        &self,
+        event_loop: &mut EventLoop,
+        fd: super::RawFd,
+        name: EventName,
+        waker: Waker,
+        timeout: Option<Duration>,
+        label: Option<&'static str>,
+    ) {
+        if self.trace_enabled() {
+            log::debug!(
+                "fd({:?}) register event({:?}) label({}) timeout({:?})",
+                fd,
+                name,
+                label.unwrap_or_default(),
+                timeout
+            );
+        }
 
-    use super::*;
+        let key = Key(fd, name);
 
-    use std::io::ErrorKind;
+        // New interest or timeout is not watched by the blocking poll, make it start over with this one.
+        if event_loop.polling && (timeout.is_some() || !event_loop.sending.contains_key(&key)) {
+            event_loop.polling = false;
 
-    #[test]
-    fn test_timeout() {
-        let mut reactor = IoReactor::default();
+            if let Err(err) = self.notify() {
+                log::error!("wakeup poller failed: {}", err);
+            }
+        }
 
-        reactor.once(
-            0,
-            EventName::Read,
-            noop_waker(),
-            Some(Duration::from_secs(1)),
-        );
+        event_loop.sending.insert(key.clone(), waker);
 
-        let raised = reactor.poll_once(Duration::from_secs(2)).unwrap();
+        match label {
+            Some(label) => event_loop.labels.insert(key.clone(), label),
+            None => event_loop.labels.remove(&key),
+        };
 
-        assert_eq!(raised, 1);
+        if let Some(timeout) = timeout {
+            // The wheel is at the last poll, which may be long ago while a poll blocks.
+            let timeout = Self::ticks(
+                timeout + event_loop.last_poll_time.elapsed(),
+                &self.tick_duration,
+            );
+
+            // A re-registration replaces the previous timeout, which would fire spuriously otherwise.
+            event_loop.cancel_io_timeout(&key);
+
+            let handle = event_loop.time_wheel.add(timeout, Timeout::Io(key.clone()));
+
+            event_loop.io_timeouts.insert(key, handle);
+        }
+    }
+
+    /// Add a standalone timer, `waker` is woken once `timeout` elapsed, returns timer id.
+    ///
+    /// Timer resolution is the reactor `tick_duration`, check expiration with [`poll_timer`](IoReactor::poll_timer).
+    /// Timers of a [`close`](IoReactor::close)d reactor fire immediately.
+    pub fn add_timer(&mut self, timeout: Duration, waker: Waker) -> u64 {
+        self.insert_timer(None, timeout, waker)
+    }
+
+    /// Get the handle of timer category `name`, created with `tick_duration` if it doesn't exist yet.
+    ///
+    /// Each category has its own [`TimeWheel`] advanced at its own tick rate, so e.g. long
+    /// application timers on a coarse "app" category don't add wheel ticks to fine io timeouts.
+    /// An existing category keeps its tick duration. Timers of all categories share ids,
+    /// check and cancel them with [`poll_timer`](IoReactor::poll_timer) and [`remove_timer`](IoReactor::remove_timer).
+    pub fn timer_category(&self, name: &'static str, tick_duration: Duration) -> TimerHandle {
+        self.event_loop
+            .lock()
+            .unwrap()
+            .categories
+            .entry(name)
+            .or_insert_with(|| TimerCategory {
+                tick_duration,
+                time_wheel: TimeWheel::with_levels(TIME_WHEEL_STEPS, TIME_WHEEL_LEVELS),
+                last_poll_time: Instant::now(),
+            });
+
+        TimerHandle {
+            reactor: self.clone(),
+            name,
+        }
+    }
+
+    fn insert_timer(
+        &mut self,
+        category: Option<&'static str>,
+        timeout: Duration,
+        waker: Waker,
+    ) -> u64 {
+        let mut event_loop = self.event_loop.lock().unwrap();
+
+        let id = event_loop.next_timer;
+
+        event_loop.next_timer += 1;
+
+        // Nothing polls a closed reactor, fire at once instead of never.
+        if event_loop.closed {
+            event_loop.fired_timers.insert(id);
+
+            return id;
+        }
+
+        event_loop.timers.insert(id, waker);
+
+        // The blocking poll may end after this timer is due, make it start over.
+        if event_loop.polling {
+            event_loop.polling = false;
+
+            if let Err(err) = self.notify() {
+                log::error!("wakeup poller failed: {}", err);
+            }
+        }
+
+        let event_loop = &mut *event_loop;
+
+        let category = category.and_then(|name| Some((name, event_loop.categories.get_mut(name)?)));
+
+        let handle = match category {
+            Some((name, timers)) => {
+                let ticks = Self::ticks(
+                    timeout + timers.last_poll_time.elapsed(),
+                    &timers.tick_duration,
+                );
+
+                event_loop.timer_categories.insert(id, name);
+
+                timers.time_wheel.add(ticks, id)
+            }
+            None => {
+                let ticks = Self::ticks(
+                    timeout + event_loop.last_poll_time.elapsed(),
+                    &self.tick_duration,
+                );
+
+                event_loop.time_wheel.add(ticks, Timeout::Timer(id))
+            }
+        };
+
+        event_loop.timer_timeouts.insert(id, handle);
+
+        id
+    }
+
+    /// Check if timer `id` has fired, otherwise replace its waker with `waker`.
+    pub fn poll_timer(&mut self, id: u64, waker: Waker) -> Poll<()> {
+        let mut event_loop = self.event_loop.lock().unwrap();
+
+        if event_loop.fired_timers.remove(&id) {
+            return Poll::Ready(());
+        }
+
+        event_loop.timers.insert(id, waker);
+
+        Poll::Pending
+    }
+
+    /// Cancel timer `id`, no-op if it has fired.
+    pub fn remove_timer(&mut self, id: u64) {
+        let mut event_loop = self.event_loop.lock().unwrap();
+
+        event_loop.timers.remove(&id);
+
+        if let Some(handle) = event_loop.timer_timeouts.remove(&id) {
+            let event_loop = &mut *event_loop;
+
+            match event_loop.timer_categories.remove(&id) {
+                Some(name) => {
+                    if let Some(timers) = event_loop.categories.get_mut(name) {
+                        timers.time_wheel.remove(handle);
+                    }
+                }
+                None => {
+                    event_loop.time_wheel.remove(handle);
+                }
+            }
+        }
+
+        event_loop.fired_timers.remove(&id);
+    }
+
+    /// Replace waker of the pending registration of `fd` `name`, returns false if no registration is pending.
+    ///
+    /// Unlike [`once`](IoReactor::once), the registration timeout is kept.
+    pub fn refresh_waker(&mut self, fd: super::RawFd, name: EventName, waker: &Waker) -> bool {
+        let mut event_loop = self.event_loop.lock().unwrap();
+
+        match event_loop.sending.get_mut(&Key(fd, name)) {
+            Some(current) => {
+                if !current.will_wake(waker) {
+                    *current = waker.clone();
+                }
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the pending registration of `fd` `name` with its timeout, e.g. when the waiting future is dropped.
+    pub fn remove_once(&mut self, fd: super::RawFd, name: EventName) {
+        let mut event_loop = self.event_loop.lock().unwrap();
+
+        let key = Key(fd, name.clone());
+
+        event_loop.sending.remove(&key);
+        event_loop.labels.remove(&key);
+        event_loop.cancel_io_timeout(&key);
+    }
+
+    /// Check if `fd` is a type the active poller backend can poll.
+    ///
+    /// e.g. epoll can't poll regular files, kqueue can, iocp needs handle association.
+    /// Higher layers can use this to choose a blocking fallback before calling [`on_open_fd`](IoReactor::on_open_fd).
+    pub fn supports(&self, fd: super::RawFd) -> bool {
+        self.poller.supports(fd)
+    }
+
+    /// Describe the poller backend this reactor runs on, e.g. to log it at startup.
+    pub fn backend_info(&self) -> BackendInfo {
+        self.poller.backend_info()
+    }
+
+    pub fn poll_io_event(&mut self, fd: super::RawFd, name: EventName) -> Result<Option<Event>> {
+        let mut event_loop = self.event_loop.lock().unwrap();
+
+        Ok(event_loop.received.remove(&Key(fd, name)))
+    }
+
+    /// Poll registered fds once and append the ready ones to `out`, returns the number of appended fds.
+    ///
+    /// Unlike [`poll_once`](Reactor::poll_once), wakers are dropped without being woken,
+    /// the caller (e.g. a custom scheduler) dispatches ready fds itself, `out` can be reused across calls.
+    /// Events are kept for the owners, so the next read/write on a ready fd consumes its result.
+    /// Registration timeouts are only handled by [`poll_once`](Reactor::poll_once).
+    pub fn poll_ready_fds(
+        &mut self,
+        out: &mut Vec<(RawFd, Readiness)>,
+        duration: Duration,
+    ) -> Result<usize> {
+        let event_keys = self.registered_keys();
+
+        if event_keys.is_empty() {
+            return Ok(0);
+        }
+
+        let events = self.poller.poll_once(&event_keys, duration)?;
+
+        let start = out.len();
+
+        let mut event_loop = self.event_loop.lock().unwrap();
+
+        for event in events {
+            if event_loop.sending.remove(event.key()).is_none() {
+                continue;
+            }
+
+            event_loop.labels.remove(event.key());
+            event_loop.cancel_io_timeout(event.key());
+
+            let Key(fd, name) = event.key();
+
+            // Backends report events of the same fd consecutively.
+            match out[start..].last_mut() {
+                Some((last, readiness)) if *last == *fd => readiness.insert(name),
+                _ => {
+                    let mut readiness = Readiness::default();
+
+                    readiness.insert(name);
+
+                    out.push((*fd, readiness));
+                }
+            }
+
+            event_loop.received.insert(event.key().clone(), event);
+        }
+
+        Ok(out.len() - start)
+    }
+
+    fn registered_keys(&self) -> Vec<Key> {
+        let event_loop = self.event_loop.lock().unwrap();
+
+        event_loop.sending.keys().cloned().collect()
+    }
+
+    /// Dump pending registrations and unconsumed events, one per line, for debugging stuck operations.
+    pub fn debug_dump(&self) -> String {
+        let event_loop = self.event_loop.lock().unwrap();
+
+        let mut lines = vec![];
+
+        for key in event_loop.sending.keys() {
+            lines.push(format!(
+                "pending fd({:?}) event({:?}) label({})",
+                key.0,
+                key.1,
+                event_loop.labels.get(key).copied().unwrap_or_default()
+            ));
+        }
+
+        for key in event_loop.received.keys() {
+            lines.push(format!("received fd({:?}) event({:?})", key.0, key.1));
+        }
+
+        lines.sort();
+
+        lines.join("\n")
+    }
+}
+
+/// Timer category of an [`IoReactor`], see [`IoReactor::timer_category`].
+#[derive(Clone, Debug)]
+pub struct TimerHandle {
+    reactor: IoReactor,
+    name: &'static str,
+}
+
+impl TimerHandle {
+    /// Get the category name.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Get the tick duration of this category wheel.
+    pub fn tick_duration(&self) -> Duration {
+        self.reactor.event_loop.lock().unwrap().categories[self.name].tick_duration
+    }
+
+    /// Add a timer to this category, same as [`IoReactor::add_timer`] with the category tick as resolution.
+    pub fn add_timer(&mut self, timeout: Duration, waker: Waker) -> u64 {
+        self.reactor.insert_timer(Some(self.name), timeout, waker)
+    }
+}
+
+impl Reactor for IoReactor {
+    /// Poll events and timeouts once, then wake the tasks waiting for them.
+    ///
+    /// A waker calling `poll_once` again on the same thread (e.g. a task driving the reactor itself
+    /// from its wakeup) gets an [`ErrorKind::Deadlock`] error instead of nesting polls, this applies
+    /// to all [`IoReactor`]s on that thread.
+    fn poll_once(&mut self, duration: Duration) -> Result<usize> {
+        let Some(_guard) = PollingGuard::enter() else {
+            return Err(Error::new(
+                ErrorKind::Deadlock,
+                "poll_once re-entered on the polling thread",
+            ));
+        };
+
+        let (event_keys, duration) = {
+            let mut event_loop = self.event_loop.lock().unwrap();
+
+            if event_loop.closed {
+                return Err(Error::new(ErrorKind::NotConnected, "reactor closed"));
+            }
+
+            // Deferred wakes are due now, only check the backend without blocking.
+            let duration = if event_loop.deferred_wakes.is_empty() {
+                duration
+            } else {
+                Duration::ZERO
+            };
+
+            // Return when the next timeout is due, not after the whole `duration`.
+            let duration = match Self::next_timeout(&event_loop, &self.tick_duration) {
+                Some(next) => duration.min(next),
+                None => duration,
+            };
+
+            // Registrations made while blocking wake the backend, see `insert_once`.
+            event_loop.polling = !duration.is_zero();
+
+            (
+                event_loop.sending.keys().cloned().collect::<Vec<_>>(),
+                duration,
+            )
+        };
+
+        if self.trace_enabled() {
+            log::debug!("poll event keys({:?})", event_keys);
+        }
+
+        // Without registrations this still blocks, until a new registration wakes it or `duration` elapses.
+        let events = self.poller.poll_once(&event_keys, duration);
+
+        let wakers = {
+            let mut wakers = vec![];
+            let mut event_loop = self.event_loop.lock().unwrap();
+
+            event_loop.polling = false;
+
+            let events = events?;
+
+            if self.trace_enabled() {
+                log::trace!("raised {:?}", events);
+            }
+
+            for event in events {
+                if let Some(waker) = event_loop.sending.remove(event.key()) {
+                    let label = event_loop.labels.remove(event.key()).unwrap_or_default();
+
+                    if self.trace_enabled() {
+                        log::debug!("wakeup {:?} label({})", event.key, label);
+                    }
+
+                    wakers.push(waker);
+
+                    // Io event takes precedence over timeout fired in the same poll.
+                    event_loop.cancel_io_timeout(event.key());
+
+                    event_loop.received.insert(event.key().clone(), event);
+                }
+            }
+
+            wakers.extend(Self::poll_timeout(&mut event_loop, &self.tick_duration));
+
+            match event_loop.max_wakes_per_poll {
+                // Earlier deferred wakes go first.
+                Some(max) if event_loop.deferred_wakes.len() + wakers.len() > max => {
+                    event_loop.deferred_wakes.extend(wakers);
+
+                    let wakers = event_loop.deferred_wakes.drain(..max).collect::<Vec<_>>();
+
+                    if self.trace_enabled() {
+                        log::trace!(
+                            "wake({}) deferred({})",
+                            wakers.len(),
+                            event_loop.deferred_wakes.len()
+                        );
+                    }
+
+                    wakers
+                }
+                _ => {
+                    let mut deferred = event_loop.deferred_wakes.drain(..).collect::<Vec<_>>();
+
+                    deferred.extend(wakers);
+
+                    deferred
+                }
+            }
+        };
+
+        for waker in &wakers {
+            waker.wake_by_ref();
+        }
+
+        Ok(wakers.len())
+    }
+}
+
+#[cfg(all(test, target_family = "macos"))]
+mod tests {
+    use futures::task::noop_waker;
+
+    use super::*;
+
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_timeout() {
+        let mut reactor = IoReactor::default();
+
+        reactor
+            .once(
+                0,
+                EventName::Read,
+                noop_waker(),
+                Some(Duration::from_secs(1)),
+            )
+            .unwrap();
+
+        let raised = reactor.poll_once(Duration::from_secs(2)).unwrap();
+
+        assert_eq!(raised, 1);
 
         assert_eq!(
             reactor
@@ -307,3 +1338,978 @@ mod tests {
         );
     }
 }
+
+#[cfg(all(test, target_family = "unix"))]
+mod unix_tests {
+    use futures::task::noop_waker;
+
+    use super::*;
+
+    #[test]
+    fn test_once_many() {
+        let mut reactor = IoReactor::default();
+
+        reactor
+            .once_many(&[
+                (0, EventName::Read, noop_waker(), None),
+                (
+                    1,
+                    EventName::Write,
+                    noop_waker(),
+                    Some(Duration::from_secs(1)),
+                ),
+            ])
+            .unwrap();
+
+        assert_eq!(reactor.registrations(), 2);
+
+        let dump = reactor.debug_dump();
+
+        assert!(dump.contains("fd(0)"));
+        assert!(dump.contains("fd(1)"));
+
+        reactor.set_max_registrations(Some(3));
+
+        // Re-registering fd(0) replaces its waker, but fd(2) and fd(3) exceed the limit, nothing is registered.
+        assert!(reactor
+            .once_many(&[
+                (0, EventName::Read, noop_waker(), None),
+                (2, EventName::Read, noop_waker(), None),
+                (3, EventName::Read, noop_waker(), None),
+            ])
+            .is_err());
+
+        assert_eq!(reactor.registrations(), 2);
+    }
+
+    #[test]
+    fn test_poll_zero_duration() {
+        use std::os::fd::AsRawFd;
+
+        let mut reactor = IoReactor::default();
+
+        let start = std::time::Instant::now();
+
+        assert_eq!(reactor.poll_once(Duration::ZERO).unwrap(), 0);
+
+        // With a pending registration, the backend is asked once without blocking.
+        let (sock, _peer) = std::os::unix::net::UnixStream::pair().unwrap();
+
+        let fd = sock.as_raw_fd();
+
+        reactor.on_open_fd(fd).unwrap();
+
+        reactor
+            .once(fd, EventName::Read, noop_waker(), None)
+            .unwrap();
+
+        assert_eq!(reactor.poll_once(Duration::ZERO).unwrap(), 0);
+
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        reactor.on_close_fd(fd);
+    }
+
+    #[test]
+    fn test_max_wakes_per_poll() {
+        use std::{io::Write, os::fd::AsRawFd, os::unix::net::UnixStream};
+
+        use futures_test::task::new_count_waker;
+
+        let mut reactor = IoReactor::default();
+
+        reactor.set_max_wakes_per_poll(Some(10));
+
+        let (counter_waker, counter) = new_count_waker();
+
+        let pairs = (0..100)
+            .map(|_| UnixStream::pair().unwrap())
+            .collect::<Vec<_>>();
+
+        for (sock, peer) in &pairs {
+            reactor.on_open_fd(sock.as_raw_fd()).unwrap();
+
+            reactor
+                .once(
+                    sock.as_raw_fd(),
+                    EventName::Read,
+                    counter_waker.clone(),
+                    None,
+                )
+                .unwrap();
+
+            (&*peer).write_all(b"x").unwrap();
+        }
+
+        let mut polls = 0;
+
+        while counter.get() < 100 {
+            let woken = reactor.poll_once(Duration::from_millis(100)).unwrap();
+
+            assert!(woken <= 10, "{}", woken);
+
+            polls += 1;
+
+            assert!(polls <= 20, "wakes not drained after {} polls", polls);
+        }
+
+        assert_eq!(counter.get(), 100);
+        assert!((10..=11).contains(&polls), "{}", polls);
+        assert_eq!(reactor.deferred_wakes(), 0);
+
+        // Deferred operations keep their events.
+        for (sock, _) in &pairs {
+            assert!(reactor
+                .poll_io_event(sock.as_raw_fd(), EventName::Read)
+                .unwrap()
+                .is_some());
+
+            reactor.on_close_fd(sock.as_raw_fd());
+        }
+    }
+
+    #[test]
+    fn test_backend_info() {
+        let info = IoReactor::default().backend_info();
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            assert_eq!(info.name, "epoll");
+            assert!(!info.supports_files);
+        }
+
+        #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+        {
+            assert_eq!(info.name, "kqueue");
+            assert!(info.supports_files);
+        }
+    }
+
+    #[test]
+    fn test_poll_thread_exits_on_last_drop() {
+        let reactor = IoReactor::new(Duration::from_millis(10)).unwrap();
+
+        let poll_thread = reactor
+            .spawn_poll_thread(Duration::from_millis(10))
+            .unwrap();
+
+        // Timers are driven by the poll thread.
+        futures::executor::block_on(crate::io::util::sleep(
+            reactor.clone(),
+            Duration::from_millis(50),
+        ));
+
+        let clone = reactor.clone();
+
+        drop(reactor);
+
+        std::thread::sleep(Duration::from_millis(100));
+
+        assert!(!poll_thread.is_finished());
+
+        drop(clone);
+
+        for _ in 0..100 {
+            if poll_thread.is_finished() {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(poll_thread.is_finished());
+
+        poll_thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_debug_dump_label() {
+        let mut reactor = IoReactor::default();
+
+        reactor
+            .once_labeled(0, EventName::Read, noop_waker(), None, "stdin read")
+            .unwrap();
+
+        let dump = reactor.debug_dump();
+
+        assert!(dump.contains("fd(0)"));
+        assert!(dump.contains("label(stdin read)"));
+
+        reactor.remove_once(0, EventName::Read);
+
+        assert!(!reactor.debug_dump().contains("stdin read"));
+    }
+
+    #[test]
+    fn test_max_registrations() {
+        let mut reactor = IoReactor::default();
+
+        reactor.set_max_registrations(Some(2));
+
+        reactor
+            .once(0, EventName::Read, noop_waker(), None)
+            .unwrap();
+        reactor
+            .once(0, EventName::Write, noop_waker(), None)
+            .unwrap();
+
+        // Re-register existing key does not count as new registration.
+        reactor
+            .once(0, EventName::Read, noop_waker(), None)
+            .unwrap();
+
+        let err = reactor
+            .once(1, EventName::Read, noop_waker(), None)
+            .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Other);
+
+        assert_eq!(reactor.registrations(), 2);
+
+        assert!(!reactor.debug_dump().contains("fd(1)"));
+
+        reactor.remove_once(0, EventName::Write);
+
+        reactor
+            .once(1, EventName::Read, noop_waker(), None)
+            .unwrap();
+
+        assert_eq!(reactor.registrations(), 2);
+    }
+
+    #[test]
+    fn test_read_and_timeout_in_same_poll() {
+        let mut reactor = IoReactor::new(Duration::from_millis(10)).unwrap();
+
+        let mut fds = [0; 2];
+
+        assert_eq!(
+            unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) },
+            0
+        );
+
+        reactor.on_open_fd(fds[0]).unwrap();
+
+        reactor
+            .once(
+                fds[0],
+                EventName::Read,
+                noop_waker(),
+                Some(Duration::from_millis(10)),
+            )
+            .unwrap();
+
+        assert_eq!(
+            unsafe { libc::write(fds[1], b"x".as_ptr() as *const _, 1) },
+            1
+        );
+
+        // Let the timeout expire before polling, so both fire in the same pass.
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(reactor.poll_once(Duration::ZERO).unwrap(), 1);
+
+        let event = reactor.poll_io_event(fds[0], EventName::Read).unwrap();
+
+        assert!(event.unwrap().message.is_ok());
+
+        std::thread::sleep(Duration::from_millis(50));
+
+        reactor.poll_once(Duration::ZERO).unwrap();
+
+        assert!(reactor
+            .poll_io_event(fds[0], EventName::Read)
+            .unwrap()
+            .is_none());
+
+        reactor.on_close_fd(fds[0]);
+
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+
+    #[test]
+    fn test_poll_ready_fds() {
+        let mut reactor = IoReactor::default();
+
+        let mut pairs = vec![];
+
+        for _ in 0..100 {
+            let mut fds = [0; 2];
+
+            assert_eq!(
+                unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) },
+                0
+            );
+
+            reactor.on_open_fd(fds[0]).unwrap();
+
+            reactor
+                .once(fds[0], EventName::Read, noop_waker(), None)
+                .unwrap();
+
+            assert_eq!(
+                unsafe { libc::write(fds[1], b"x".as_ptr() as *const _, 1) },
+                1
+            );
+
+            pairs.push(fds);
+        }
+
+        let mut ready = Vec::with_capacity(100);
+
+        assert_eq!(
+            reactor
+                .poll_ready_fds(&mut ready, Duration::from_secs(1))
+                .unwrap(),
+            100
+        );
+
+        let mut fds = ready.iter().map(|(fd, _)| *fd).collect::<Vec<_>>();
+
+        fds.sort();
+
+        let mut expected = pairs.iter().map(|fds| fds[0]).collect::<Vec<_>>();
+
+        expected.sort();
+
+        assert_eq!(fds, expected);
+
+        assert!(ready
+            .iter()
+            .all(|(_, readiness)| readiness.readable && !readiness.writable));
+
+        // Registrations are consumed, events are kept for the owners.
+        assert_eq!(reactor.registrations(), 0);
+
+        for fds in pairs {
+            assert!(reactor
+                .poll_io_event(fds[0], EventName::Read)
+                .unwrap()
+                .is_some());
+
+            reactor.on_close_fd(fds[0]);
+
+            unsafe {
+                libc::close(fds[0]);
+                libc::close(fds[1]);
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_supports() {
+        use std::os::fd::AsRawFd;
+
+        let reactor = IoReactor::default();
+
+        let file = std::fs::File::open(std::env::current_exe().unwrap()).unwrap();
+
+        assert!(!reactor.supports(file.as_raw_fd()));
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        assert!(reactor.supports(socket.as_raw_fd()));
+
+        assert!(!reactor.supports(-1));
+    }
+
+    #[test]
+    fn test_sub_millisecond_tick() {
+        let mut reactor = IoReactor::new(Duration::from_micros(100)).unwrap();
+
+        let id = reactor.add_timer(Duration::from_micros(300), noop_waker());
+
+        std::thread::sleep(Duration::from_millis(1));
+
+        reactor.poll_once(Duration::ZERO).unwrap();
+
+        assert!(reactor.poll_timer(id, noop_waker()).is_ready());
+
+        assert_eq!(
+            IoReactor::new(Duration::ZERO).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+
+        // Millions of idle nanosecond ticks are skipped, not ticked one by one.
+        let mut reactor = IoReactor::new(Duration::from_nanos(1)).unwrap();
+
+        let id = reactor.add_timer(Duration::from_millis(10), noop_waker());
+
+        std::thread::sleep(Duration::from_millis(20));
+
+        reactor.poll_once(Duration::ZERO).unwrap();
+
+        assert!(reactor.poll_timer(id, noop_waker()).is_ready());
+    }
+
+    #[test]
+    fn test_register_wakes_blocking_poll() {
+        use std::{
+            sync::atomic::{AtomicBool, Ordering},
+            time::Instant,
+        };
+
+        use futures::task::{waker, ArcWake};
+
+        struct FlagWaker(AtomicBool);
+
+        impl ArcWake for FlagWaker {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let reactor = IoReactor::default();
+
+        let mut fds = [0; 2];
+
+        assert_eq!(
+            unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) },
+            0
+        );
+
+        let mut registrar = reactor.clone();
+
+        registrar.on_open_fd(fds[0]).unwrap();
+
+        // Fill the send buffer, so the poll blocks on a write registration only.
+        unsafe { libc::fcntl(fds[0], libc::F_SETFL, libc::O_NONBLOCK) };
+
+        let chunk = [0u8; 4096];
+
+        while unsafe { libc::write(fds[0], chunk.as_ptr() as *const _, chunk.len()) } > 0 {}
+
+        registrar
+            .once(fds[0], EventName::Write, noop_waker(), None)
+            .unwrap();
+
+        let flag = Arc::new(FlagWaker(AtomicBool::new(false)));
+
+        let poll_thread = {
+            let mut reactor = reactor.clone();
+            let flag = flag.clone();
+
+            std::thread::spawn(move || {
+                let start = Instant::now();
+
+                while !flag.0.load(Ordering::SeqCst) {
+                    reactor.poll_once(Duration::from_secs(10)).unwrap();
+                }
+
+                start.elapsed()
+            })
+        };
+
+        // Let the poll thread block with the write registration first.
+        std::thread::sleep(Duration::from_millis(100));
+
+        registrar
+            .once(fds[0], EventName::Read, waker(flag.clone()), None)
+            .unwrap();
+
+        assert_eq!(
+            unsafe { libc::write(fds[1], b"x".as_ptr() as *const _, 1) },
+            1
+        );
+
+        let elapsed = poll_thread.join().unwrap();
+
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+
+        registrar.on_close_fd(fds[0]);
+
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+
+    #[test]
+    fn test_notify() {
+        use std::time::Instant;
+
+        let reactor = IoReactor::default();
+
+        let poll_thread = {
+            let mut reactor = reactor.clone();
+
+            std::thread::spawn(move || {
+                let start = Instant::now();
+
+                reactor.poll_once(Duration::from_secs(10)).unwrap();
+
+                start.elapsed()
+            })
+        };
+
+        std::thread::sleep(Duration::from_millis(100));
+
+        reactor.notify().unwrap();
+
+        let elapsed = poll_thread.join().unwrap();
+
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_timer_category() {
+        use std::time::Instant;
+
+        let mut reactor = IoReactor::default();
+
+        let mut io = reactor.timer_category("io", Duration::from_millis(10));
+        let mut app = reactor.timer_category("app", Duration::from_millis(500));
+
+        assert_eq!(io.name(), "io");
+        assert_eq!(app.tick_duration(), Duration::from_millis(500));
+
+        // Existing category keeps its tick.
+        assert_eq!(
+            reactor
+                .timer_category("io", Duration::from_secs(1))
+                .tick_duration(),
+            Duration::from_millis(10)
+        );
+
+        let start = Instant::now();
+
+        let io_timer = io.add_timer(Duration::from_millis(30), noop_waker());
+        let app_timer = app.add_timer(Duration::from_millis(30), noop_waker());
+
+        let mut io_fired = None;
+        let mut app_fired = None;
+
+        while io_fired.is_none() || app_fired.is_none() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timers stalled");
+
+            reactor.poll_once(Duration::from_millis(5)).unwrap();
+
+            if io_fired.is_none() && reactor.poll_timer(io_timer, noop_waker()).is_ready() {
+                io_fired = Some(start.elapsed());
+            }
+
+            if app_fired.is_none() && reactor.poll_timer(app_timer, noop_waker()).is_ready() {
+                app_fired = Some(start.elapsed());
+            }
+        }
+
+        let (io_fired, app_fired) = (io_fired.unwrap(), app_fired.unwrap());
+
+        // Same timeout, each fires on the first tick of its own wheel past the deadline.
+        assert!(io_fired >= Duration::from_millis(30), "{:?}", io_fired);
+        assert!(io_fired < Duration::from_millis(400), "{:?}", io_fired);
+        assert!(app_fired >= Duration::from_millis(500), "{:?}", app_fired);
+
+        // Cancelled category timers never fire.
+        let cancelled = io.add_timer(Duration::from_millis(10), noop_waker());
+
+        reactor.remove_timer(cancelled);
+
+        std::thread::sleep(Duration::from_millis(30));
+
+        reactor.poll_once(Duration::ZERO).unwrap();
+
+        assert!(reactor.poll_timer(cancelled, noop_waker()).is_pending());
+    }
+
+    #[test]
+    fn test_partial_read_rearm() {
+        let mut reactor = IoReactor::default();
+
+        let mut fds = [0; 2];
+
+        assert_eq!(
+            unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) },
+            0
+        );
+
+        reactor.on_open_fd(fds[0]).unwrap();
+
+        // Two messages back to back, one readiness change.
+        assert_eq!(
+            unsafe { libc::write(fds[1], b"helloworld".as_ptr() as *const _, 10) },
+            10
+        );
+
+        let mut buf = [0u8; 5];
+
+        for expected in [b"hello", b"world"] {
+            reactor
+                .once(fds[0], EventName::Read, noop_waker(), None)
+                .unwrap();
+
+            assert_eq!(reactor.poll_once(Duration::from_secs(1)).unwrap(), 1);
+
+            assert!(reactor
+                .poll_io_event(fds[0], EventName::Read)
+                .unwrap()
+                .is_some());
+
+            assert_eq!(
+                unsafe { libc::read(fds[0], buf.as_mut_ptr() as *mut _, 5) },
+                5
+            );
+
+            assert_eq!(&buf, expected);
+        }
+
+        reactor.on_close_fd(fds[0]);
+
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+
+    #[test]
+    fn test_timeout_on_time() {
+        use std::{sync::mpsc::channel, time::Instant};
+
+        use futures::task::{waker, ArcWake};
+
+        struct SendWaker(Mutex<std::sync::mpsc::Sender<Instant>>);
+
+        impl ArcWake for SendWaker {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                _ = arc_self.0.lock().unwrap().send(Instant::now());
+            }
+        }
+
+        let mut reactor = IoReactor::new(Duration::from_millis(1)).unwrap();
+
+        let mut background = reactor.clone();
+
+        std::thread::spawn(move || loop {
+            // Much longer than the timeout, no io happens.
+            if background.poll_once(Duration::from_secs(5)).is_err() {
+                break;
+            }
+        });
+
+        let mut fds = [0; 2];
+
+        assert_eq!(
+            unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) },
+            0
+        );
+
+        reactor.on_open_fd(fds[0]).unwrap();
+
+        // Let the poll thread block first.
+        std::thread::sleep(Duration::from_millis(50));
+
+        let (sender, receiver) = channel();
+
+        let start = Instant::now();
+
+        reactor
+            .once(
+                fds[0],
+                EventName::Read,
+                waker(Arc::new(SendWaker(Mutex::new(sender)))),
+                Some(Duration::from_millis(200)),
+            )
+            .unwrap();
+
+        let fired = receiver.recv_timeout(Duration::from_secs(2)).unwrap() - start;
+
+        // Never early, the upper bound only catches a timer that never fires on its own.
+        assert!(fired >= Duration::from_millis(200), "{:?}", fired);
+        assert!(fired < Duration::from_secs(1), "{:?}", fired);
+
+        assert_eq!(
+            reactor
+                .poll_io_event(fds[0], EventName::Read)
+                .unwrap()
+                .unwrap()
+                .message
+                .unwrap_err()
+                .kind(),
+            ErrorKind::TimedOut
+        );
+
+        reactor.close();
+        reactor.on_close_fd(fds[0]);
+
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+
+    #[test]
+    fn test_max_events() {
+        use std::{io::Write, os::fd::AsRawFd, os::unix::net::UnixStream};
+
+        use futures_test::task::new_count_waker;
+
+        let mut reactor = IoReactor::with_max_events(Duration::from_secs(1), 16).unwrap();
+
+        assert_eq!(reactor.backend_info().max_batch, 16);
+
+        let (counter_waker, counter) = new_count_waker();
+
+        let pairs = (0..100)
+            .map(|_| UnixStream::pair().unwrap())
+            .collect::<Vec<_>>();
+
+        for (sock, peer) in &pairs {
+            reactor.on_open_fd(sock.as_raw_fd()).unwrap();
+
+            reactor
+                .once(
+                    sock.as_raw_fd(),
+                    EventName::Read,
+                    counter_waker.clone(),
+                    None,
+                )
+                .unwrap();
+
+            (&*peer).write_all(b"x").unwrap();
+        }
+
+        let mut polls = 0;
+
+        while counter.get() < 100 {
+            let woken = reactor.poll_once(Duration::from_millis(100)).unwrap();
+
+            assert!(woken <= 16, "{}", woken);
+
+            polls += 1;
+
+            assert!(polls <= 20, "events not drained after {} polls", polls);
+        }
+
+        assert_eq!(counter.get(), 100);
+        assert_eq!(polls, 7);
+        assert_eq!(reactor.registrations(), 0);
+
+        for (sock, _) in &pairs {
+            assert!(reactor
+                .poll_io_event(sock.as_raw_fd(), EventName::Read)
+                .unwrap()
+                .is_some());
+
+            reactor.on_close_fd(sock.as_raw_fd());
+        }
+    }
+
+    #[test]
+    fn test_reentrant_poll_once() {
+        use std::sync::mpsc::channel;
+
+        use futures::task::{waker, ArcWake};
+
+        struct PollingWaker(IoReactor, Mutex<std::sync::mpsc::Sender<Result<usize>>>);
+
+        impl ArcWake for PollingWaker {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                let result = arc_self.0.clone().poll_once(Duration::ZERO);
+
+                arc_self.1.lock().unwrap().send(result).unwrap();
+            }
+        }
+
+        let mut reactor = IoReactor::new(Duration::from_millis(1)).unwrap();
+
+        let (sender, receiver) = channel();
+
+        reactor.add_timer(
+            Duration::ZERO,
+            waker(Arc::new(PollingWaker(reactor.clone(), Mutex::new(sender)))),
+        );
+
+        assert_eq!(reactor.poll_once(Duration::from_millis(10)).unwrap(), 1);
+
+        let err = receiver.try_recv().unwrap().unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Deadlock);
+
+        // The outer poll is done, polling again works.
+        assert_eq!(reactor.poll_once(Duration::ZERO).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_removed_registration_never_times_out() {
+        let mut reactor = IoReactor::new(Duration::from_millis(1)).unwrap();
+
+        reactor
+            .once(
+                0,
+                EventName::Read,
+                noop_waker(),
+                Some(Duration::from_millis(10)),
+            )
+            .unwrap();
+
+        reactor.remove_once(0, EventName::Read);
+
+        assert_eq!(
+            reactor
+                .event_loop
+                .lock()
+                .unwrap()
+                .time_wheel
+                .next_expiration(),
+            None
+        );
+
+        reactor.poll_once(Duration::from_millis(30)).unwrap();
+
+        assert!(reactor.poll_io_event(0, EventName::Read).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_time_until_next_timeout() {
+        let mut reactor = IoReactor::new(Duration::from_millis(1)).unwrap();
+
+        assert_eq!(reactor.time_until_next_timeout(), None);
+
+        reactor
+            .once(
+                0,
+                EventName::Read,
+                noop_waker(),
+                Some(Duration::from_millis(500)),
+            )
+            .unwrap();
+
+        reactor
+            .once(
+                1,
+                EventName::Read,
+                noop_waker(),
+                Some(Duration::from_millis(100)),
+            )
+            .unwrap();
+
+        let next = reactor.time_until_next_timeout().unwrap();
+
+        // Counts down from the deadline rounded up to the tick, a busy test thread only shortens it.
+        assert!(
+            next > Duration::ZERO && next <= Duration::from_millis(102),
+            "{:?}",
+            next
+        );
+
+        reactor.remove_once(1, EventName::Read);
+
+        let next = reactor.time_until_next_timeout().unwrap();
+
+        assert!(
+            next > Duration::from_millis(102) && next <= Duration::from_millis(502),
+            "{:?}",
+            next
+        );
+
+        reactor.remove_once(0, EventName::Read);
+
+        assert_eq!(reactor.time_until_next_timeout(), None);
+
+        // Cancelled timers leave the wheel too.
+        let timer = reactor.add_timer(Duration::from_millis(100), noop_waker());
+
+        assert!(reactor.time_until_next_timeout().is_some());
+
+        reactor.remove_timer(timer);
+
+        assert_eq!(reactor.time_until_next_timeout(), None);
+    }
+
+    #[test]
+    fn test_timer_longer_than_wheel() {
+        // 16 slots of 1ms, the second level covers 256ms.
+        let mut reactor = IoReactor::with_time_wheel(Duration::from_millis(1), 16, 2).unwrap();
+
+        let start = Instant::now();
+
+        let cascaded = reactor.add_timer(Duration::from_millis(100), noop_waker());
+
+        // Longer than both levels, revisited at the top level.
+        let top = reactor.add_timer(Duration::from_millis(300), noop_waker());
+
+        let mut fired = vec![];
+
+        while fired.len() < 2 {
+            reactor.poll_once(Duration::from_secs(1)).unwrap();
+
+            for id in [cascaded, top] {
+                if !fired.iter().any(|(fired, _)| *fired == id)
+                    && reactor.poll_timer(id, noop_waker()).is_ready()
+                {
+                    fired.push((id, start.elapsed()));
+                }
+            }
+        }
+
+        assert_eq!(fired[0].0, cascaded);
+        assert!(
+            fired[0].1 >= Duration::from_millis(100) && fired[0].1 < Duration::from_millis(500),
+            "{:?}",
+            fired[0].1
+        );
+
+        assert_eq!(fired[1].0, top);
+        assert!(
+            fired[1].1 >= Duration::from_millis(300) && fired[1].1 < Duration::from_millis(1500),
+            "{:?}",
+            fired[1].1
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_poll_thread_policy() {
+        // Never opened, re-arming it fails every poll until it is removed.
+        let bogus = 100_000;
+
+        let mut reactor = IoReactor::new(Duration::from_millis(1)).unwrap();
+
+        let poll_thread = reactor
+            .spawn_poll_thread_with(
+                Duration::from_millis(10),
+                PollThreadPolicy::Retry {
+                    initial_backoff: Duration::from_millis(1),
+                    max_backoff: Duration::from_millis(10),
+                },
+            )
+            .unwrap();
+
+        reactor
+            .once(bogus, EventName::Read, noop_waker(), None)
+            .unwrap();
+
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert!(!poll_thread.is_finished());
+
+        reactor.remove_once(bogus, EventName::Read);
+
+        // The thread recovered and drives timers again.
+        futures::executor::block_on(crate::io::util::sleep(
+            reactor.clone(),
+            Duration::from_millis(20),
+        ));
+
+        drop(reactor);
+
+        poll_thread.join().unwrap().unwrap();
+
+        // Default policy stops at the first error.
+        let mut reactor = IoReactor::new(Duration::from_millis(1)).unwrap();
+
+        let poll_thread = reactor
+            .spawn_poll_thread(Duration::from_millis(10))
+            .unwrap();
+
+        reactor
+            .once(bogus, EventName::Read, noop_waker(), None)
+            .unwrap();
+
+        assert!(poll_thread.join().unwrap().is_err());
+    }
+}
@@ -1,12 +1,12 @@
 use std::{
     collections::HashMap,
-    io::{Error, Result},
+    io::{Error, ErrorKind, Result},
     ptr::null_mut,
-    sync::Arc,
+    sync::{Arc, Mutex, RwLock, RwLockReadGuard},
     time::Duration,
 };
 
-use super::{Event, EventName, Key, RawFd};
+use super::{BackendInfo, Event, EventName, Key, RawFd, DEFAULT_MAX_EVENTS};
 
 use errno::{errno, set_errno};
 use libc::*;
@@ -15,39 +15,198 @@ use libc::*;
 ///
 #[derive(Clone, Debug)]
 pub struct SysPoller {
-    handle: Arc<i32>,
+    /// Epoll fd, `-1` once [`close`](SysPoller::close)d.
+    handle: Arc<RwLock<i32>>,
+    /// `eventfd` interrupting a blocking `epoll_wait`, see [`wakeup`](SysPoller::wakeup).
+    wakeup_fd: i32,
+    /// `timerfd` ending a blocking `epoll_wait` on time, which only counts whole milliseconds.
+    timer_fd: i32,
+    /// Fds registered with `EPOLLEXCLUSIVE`, which can't be modified, mapped to whether they are in the epoll set.
+    exclusive: Arc<Mutex<HashMap<RawFd, bool>>>,
+    /// Max events returned by one `epoll_wait`.
+    max_events: usize,
+    /// `epoll_wait` output buffer reused across polls, see [`with_max_events`](SysPoller::with_max_events).
+    fired_events: Arc<Mutex<Vec<epoll_event>>>,
 }
 
 impl Drop for SysPoller {
     fn drop(&mut self) {
         if Arc::strong_count(&self.handle) == 1 {
-            log::debug!("Close iocp handle({:?})", *self.handle);
-            unsafe { close(*self.handle) };
+            self.release(&mut self.handle.write().unwrap());
         }
     }
 }
 
 impl SysPoller {
     pub fn new() -> Result<Self> {
+        Self::with_max_events(DEFAULT_MAX_EVENTS)
+    }
+
+    /// Create poller returning at most `max_events` events per [`poll_once`](SysPoller::poll_once).
+    ///
+    /// Events beyond the limit stay ready and are returned by the following polls.
+    /// The internal wakeup and timer fds take slots of the limit when they fire.
+    pub fn with_max_events(max_events: usize) -> Result<Self> {
+        let max_events = max_events.max(1);
+
         let handle = unsafe { libc::epoll_create(1) };
 
         if -1 == handle {
             return Err(Error::last_os_error());
         }
 
+        let wakeup_fd = unsafe { eventfd(0, EFD_NONBLOCK | EFD_CLOEXEC) };
+
+        if -1 == wakeup_fd {
+            let err = Error::last_os_error();
+
+            unsafe { close(handle) };
+
+            return Err(err);
+        }
+
+        let timer_fd = unsafe { timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK | TFD_CLOEXEC) };
+
+        if -1 == timer_fd {
+            let err = Error::last_os_error();
+
+            unsafe {
+                close(wakeup_fd);
+                close(handle);
+            }
+
+            return Err(err);
+        }
+
+        for fd in [wakeup_fd, timer_fd] {
+            // Level triggered, stays readable until drained by `poll_once`.
+            let event = epoll_event {
+                events: EPOLLIN as u32,
+                u64: fd as u64,
+            };
+
+            if unsafe { epoll_ctl(handle, EPOLL_CTL_ADD, fd, &event as *const _ as *mut _) } == -1 {
+                let err = Error::last_os_error();
+
+                unsafe {
+                    close(timer_fd);
+                    close(wakeup_fd);
+                    close(handle);
+                }
+
+                return Err(err);
+            }
+        }
+
         Ok(Self {
-            handle: Arc::new(handle),
+            handle: Arc::new(RwLock::new(handle)),
+            wakeup_fd,
+            timer_fd,
+            exclusive: Default::default(),
+            max_events,
+            fired_events: Arc::new(Mutex::new(vec![unsafe { std::mem::zeroed() }; max_events])),
         })
     }
+
+    /// Get the epoll fd, fails with `NotConnected` once [`close`](Self::close)d.
+    ///
+    /// Calls hold the guard while using the fds, so `close` never releases them (and lets their numbers
+    /// be reused) under a call. Take it once per call, a nested read may deadlock with a waiting `close`.
+    fn handle(&self) -> Result<RwLockReadGuard<'_, i32>> {
+        let handle = self.handle.read().unwrap();
+
+        if *handle == -1 {
+            return Err(Error::new(ErrorKind::NotConnected, "epoll poller closed"));
+        }
+
+        Ok(handle)
+    }
+
+    /// Close the epoll fd and the internal fds, following calls fail with `NotConnected`.
+    ///
+    /// Polls blocking in other threads are woken until they all returned, then the fds are closed.
+    pub fn close(&self) -> Result<()> {
+        let mut handle = loop {
+            if let Ok(handle) = self.handle.try_write() {
+                break handle;
+            }
+
+            _ = self.wakeup();
+
+            std::thread::yield_now();
+        };
+
+        self.release(&mut handle);
+
+        Ok(())
+    }
+
+    fn release(&self, handle: &mut i32) {
+        if *handle == -1 {
+            return;
+        }
+
+        log::debug!("Close epoll handle({:?})", *handle);
+
+        unsafe {
+            close(*handle);
+            close(self.wakeup_fd);
+            close(self.timer_fd);
+        }
+
+        *handle = -1;
+    }
+
+    /// Interrupt a `poll_once` blocking in another thread, or make the next one return immediately.
+    pub fn wakeup(&self) -> Result<()> {
+        let _handle = self.handle()?;
+
+        let value = 1u64;
+
+        let ret = unsafe {
+            write(
+                self.wakeup_fd,
+                &value as *const u64 as *const c_void,
+                std::mem::size_of::<u64>(),
+            )
+        };
+
+        // EAGAIN means the counter is saturated, the poller is woken anyway.
+        if ret == -1 && errno().0 != EAGAIN {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+    /// Make the timer fd readable once `timeout` elapsed, replacing the previous expiration.
+    ///
+    /// Polls running in several threads share the timer, a poll whose expiration is replaced
+    /// by a later one ends with the `epoll_wait` timeout instead, at most one millisecond late.
+    fn arm_timer(&self, timeout: Duration) -> Result<()> {
+        let mut spec: itimerspec = unsafe { std::mem::zeroed() };
+
+        spec.it_value.tv_sec = timeout.as_secs().min(i64::MAX as u64) as _;
+        spec.it_value.tv_nsec = timeout.subsec_nanos() as _;
+
+        if unsafe { timerfd_settime(self.timer_fd, 0, &spec, null_mut()) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Add `fd` disarmed, `poll_once` arms it for the registered keys.
     pub fn on_open_fd(&self, fd: RawFd) -> Result<()> {
+        let handle = self.handle()?;
+
         let event = epoll_event {
-            events: (EPOLLIN | EPOLLOUT | EPOLLET) as u32,
+            events: EPOLLONESHOT as u32,
             u64: fd as u64,
         };
 
         let ret = unsafe {
             epoll_ctl(
-                *self.handle,
+                *handle,
                 EPOLL_CTL_ADD,
                 fd,
                 [event].as_ptr() as *mut epoll_event,
@@ -61,8 +220,57 @@ impl SysPoller {
         return Ok(());
     }
 
+    /// Register `fd` (e.g. a listening socket shared by several reactors) for `EPOLLEXCLUSIVE` wakeups,
+    /// so one readiness change wakes one of the epoll instances watching it instead of all.
+    ///
+    /// Exclusive fds can't be re-armed with `EPOLL_CTL_MOD`, instead they are only in the epoll set while
+    /// an operation is registered, see [`poll_once`](Self::poll_once).
+    pub fn on_open_fd_exclusive(&self, fd: RawFd) -> Result<()> {
+        let handle = self.handle()?;
+
+        // Check `fd` can be watched now, rather than failing a later poll.
+        Self::add_exclusive(*handle, fd, EPOLLIN)?;
+
+        if unsafe { epoll_ctl(*handle, EPOLL_CTL_DEL, fd, null_mut()) } == -1 {
+            return Err(Error::last_os_error());
+        }
+
+        self.exclusive.lock().unwrap().insert(fd, false);
+
+        Ok(())
+    }
+
+    fn add_exclusive(handle: i32, fd: RawFd, ops: c_int) -> Result<()> {
+        let event = epoll_event {
+            events: (ops | EPOLLEXCLUSIVE) as u32,
+            u64: fd as u64,
+        };
+
+        let ret = unsafe {
+            epoll_ctl(
+                handle,
+                EPOLL_CTL_ADD,
+                fd,
+                [event].as_ptr() as *mut epoll_event,
+            )
+        };
+
+        if ret == -1 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
     pub fn on_close_fd(&self, fd: RawFd) -> Result<()> {
-        let ret = unsafe { epoll_ctl(*self.handle, EPOLL_CTL_DEL, fd, null_mut()) };
+        let handle = self.handle()?;
+
+        // Not in the epoll set while nothing waits on it.
+        if self.exclusive.lock().unwrap().remove(&fd) == Some(false) {
+            return Ok(());
+        }
+
+        let ret = unsafe { epoll_ctl(*handle, EPOLL_CTL_DEL, fd, null_mut()) };
 
         if ret == -1 {
             return Err(Error::last_os_error());
@@ -71,7 +279,53 @@ impl SysPoller {
         return Ok(());
     }
 
+    /// Check if `fd` can be registered to epoll, regular files and directories can not.
+    pub fn supports(&self, fd: RawFd) -> bool {
+        let mut stat: stat = unsafe { std::mem::zeroed() };
+
+        if unsafe { fstat(fd, &mut stat) } == -1 {
+            return false;
+        }
+
+        match stat.st_mode & S_IFMT {
+            S_IFREG | S_IFDIR => false,
+            S_IFSOCK => {
+                let mut ty: c_int = 0;
+                let mut len = std::mem::size_of::<c_int>() as socklen_t;
+
+                unsafe {
+                    getsockopt(
+                        fd,
+                        SOL_SOCKET,
+                        SO_TYPE,
+                        &mut ty as *mut c_int as *mut c_void,
+                        &mut len,
+                    ) == 0
+                }
+            }
+            _ => true,
+        }
+    }
+
+    /// Fds are armed level triggered and one-shot on each poll, see [`poll_once`](Self::poll_once).
+    pub fn backend_info(&self) -> BackendInfo {
+        BackendInfo {
+            name: "epoll",
+            supports_files: false,
+            supports_edge_trigger: false,
+            max_batch: self.max_events,
+        }
+    }
+
+    /// Wait for events of `keys` up to `timeout`.
+    ///
+    /// Each fd of `keys` is re-armed with `EPOLL_CTL_MOD`, which checks the current readiness,
+    /// so data left unread by a partial read fires again as soon as the reader registers again.
+    /// `EPOLLONESHOT` disarms the fd once it fires, so unread data doesn't fire every poll
+    /// while no one is registered. Exclusive fds are added with `EPOLL_CTL_ADD` and deleted once they fire instead.
     pub fn poll_once(&self, keys: &[Key], timeout: Duration) -> Result<Vec<Event>> {
+        let handle = self.handle()?;
+
         let mut fds = HashMap::new();
 
         for key in keys {
@@ -89,15 +343,32 @@ impl SysPoller {
             }
         }
 
+        // `EPOLL_CTL_MOD` is refused for exclusive fds, add them while waited on.
+        {
+            let mut exclusive = self.exclusive.lock().unwrap();
+
+            for (fd, ops) in &fds {
+                if let Some(added) = exclusive.get_mut(fd) {
+                    if !*added {
+                        Self::add_exclusive(*handle, *fd, *ops)?;
+
+                        *added = true;
+                    }
+                }
+            }
+
+            fds.retain(|fd, _| !exclusive.contains_key(fd));
+        }
+
         for (fd, ops) in fds {
             let event = epoll_event {
-                events: ops as u32,
+                events: (ops | EPOLLONESHOT) as u32,
                 u64: fd as u64,
             };
 
             let ret = unsafe {
                 epoll_ctl(
-                    *self.handle,
+                    *handle,
                     EPOLL_CTL_MOD,
                     fd,
                     [event].as_ptr() as *mut epoll_event,
@@ -109,14 +380,33 @@ impl SysPoller {
             }
         }
 
-        let fired_events: Vec<epoll_event> = vec![unsafe { std::mem::zeroed() }; keys.len()];
+        // Round up, so the wait doesn't end before the precise timer does.
+        let timeout_ms = timeout.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32;
+
+        if !timeout.is_zero() {
+            self.arm_timer(timeout)?;
+        }
+
+        // Another thread polling the same poller holds the shared buffer, use a temporary one.
+        let mut shared = self.fired_events.try_lock().ok();
+
+        let mut owned = vec![];
+
+        let fired_events = match shared.as_deref_mut() {
+            Some(fired_events) => fired_events,
+            None => {
+                owned.resize(self.max_events, unsafe { std::mem::zeroed() });
+
+                &mut owned
+            }
+        };
 
         let fired = unsafe {
             epoll_wait(
-                *self.handle,
-                fired_events.as_ptr() as *mut epoll_event,
+                *handle,
+                fired_events.as_mut_ptr(),
                 fired_events.len() as i32,
-                timeout.as_millis() as i32,
+                timeout_ms,
             )
         };
 
@@ -132,17 +422,49 @@ impl SysPoller {
 
         let mut events = Vec::with_capacity(fired as usize);
 
+        let mut exclusive = self.exclusive.lock().unwrap();
+
         for i in 0..fired {
             let event = &fired_events[i as usize];
 
-            if event.events & EPOLLIN as u32 != 0 {
+            if event.u64 == self.wakeup_fd as u64 || event.u64 == self.timer_fd as u64 {
+                let mut value = 0u64;
+
+                unsafe {
+                    read(
+                        event.u64 as i32,
+                        &mut value as *mut u64 as *mut c_void,
+                        std::mem::size_of::<u64>(),
+                    )
+                };
+
+                continue;
+            }
+
+            // One-shot for exclusive fds, a level triggered fd left in the set fires every poll
+            // while its waiter is gone (e.g. a listener with pending connections not accepting).
+            let fd = event.u64 as i32;
+
+            if let Some(added @ true) = exclusive.get_mut(&fd) {
+                *added = false;
+
+                if unsafe { epoll_ctl(*handle, EPOLL_CTL_DEL, fd, null_mut()) } == -1 {
+                    log::error!("epoll remove exclusive fd({}) error", fd);
+                }
+            }
+
+            // Error or hangup (e.g. keepalive timeout) wakes both readers and writers,
+            // the following recv/send call reports the socket error.
+            let failed = event.events & (EPOLLERR | EPOLLHUP) as u32 != 0;
+
+            if failed || event.events & EPOLLIN as u32 != 0 {
                 events.push(Event {
                     key: Key(event.u64 as i32, EventName::Read),
                     message: Ok(()),
                 })
             }
 
-            if event.events & EPOLLOUT as u32 != 0 {
+            if failed || event.events & EPOLLOUT as u32 != 0 {
                 events.push(Event {
                     key: Key(event.u64 as i32, EventName::Write),
                     message: Ok(()),
@@ -150,8 +472,6 @@ impl SysPoller {
             }
         }
 
-        log::trace!("raised {:?}", events);
-
         Ok(events)
     }
 }
@@ -1,9 +1,12 @@
 use std::{
-    io::{Error, Result},
+    io::{Error, ErrorKind, Result},
     mem::size_of,
     net::SocketAddr,
     ptr::null_mut,
-    sync::{Arc, Once},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Once, RwLock, RwLockReadGuard,
+    },
     time::{Duration, SystemTime},
 };
 
@@ -14,7 +17,16 @@ use winapi::{shared::winerror::*, shared::ws2def::WSABUF, um::handleapi::*};
 use winapi::{shared::ws2def::SOCKADDR, um::minwinbase::OVERLAPPED_ENTRY};
 use winapi::{shared::ws2ipdef::SOCKADDR_IN6, um::winsock2::*};
 
-use super::{Event, Key, RawFd};
+use super::{BackendInfo, Event, Key, RawFd, DEFAULT_MAX_EVENTS};
+
+/// `NTSTATUS` of overlapped operations cancelled by `CancelIoEx`.
+const STATUS_CANCELLED: usize = 0xC0000120;
+
+/// `NTSTATUS` of datagram receives truncated to the buffer size.
+const STATUS_BUFFER_OVERFLOW: usize = 0x80000005;
+
+/// Default max completions dequeued by one `GetQueuedCompletionStatusEx` call.
+const DEFAULT_BATCH_SIZE: usize = 256;
 
 /// Event types for IOCP
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
@@ -75,16 +87,68 @@ impl ReactorOverlapped {
             }
         }
     }
+}
+
+/// Max number of idle overlapped structures kept by [`OverlappedPool`].
+const MAX_POOLED_OVERLAPPED: usize = 1024;
+
+/// Free-list of [`ReactorOverlapped`] structures reused across IOCP operations.
+#[derive(Clone, Default)]
+pub(crate) struct OverlappedPool {
+    free: Arc<Mutex<Vec<Box<ReactorOverlapped>>>>,
+    allocated: Arc<AtomicUsize>,
+}
+
+impl OverlappedPool {
+    /// Take an overlapped structure from pool and re-initialize it, allocate new one if the pool is empty.
+    pub fn acquire(&self, fd: RawFd, event_name: EventName) -> *mut ReactorOverlapped {
+        let reused = self.free.lock().unwrap().pop();
 
-    /// Create new raw overlapped point.
-    pub fn new_raw(fd: RawFd, event_name: EventName) -> *mut Self {
-        Box::into_raw(Box::new(Self::new(fd, event_name)))
+        let overlapped = match reused {
+            Some(mut overlapped) => {
+                *overlapped = ReactorOverlapped::new(fd, event_name);
+                overlapped
+            }
+            None => {
+                self.allocated.fetch_add(1, Ordering::Relaxed);
+                Box::new(ReactorOverlapped::new(fd, event_name))
+            }
+        };
+
+        Box::into_raw(overlapped)
+    }
+
+    /// Return overlapped structure created by [`acquire`](Self::acquire) to pool.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that no completion packet references `overlapped` anymore,
+    /// that is the completion was dequeued or the operation failed to start.
+    pub unsafe fn release(&self, overlapped: *mut ReactorOverlapped) {
+        self.release_box(Box::from_raw(overlapped))
+    }
+
+    fn release_box(&self, overlapped: Box<ReactorOverlapped>) {
+        let mut free = self.free.lock().unwrap();
+
+        if free.len() < MAX_POOLED_OVERLAPPED {
+            free.push(overlapped);
+        } else {
+            self.allocated.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Number of overlapped structures alive, either in flight or idle in pool.
+    pub fn allocated(&self) -> usize {
+        self.allocated.load(Ordering::Relaxed)
     }
 }
 
-impl From<*mut ReactorOverlapped> for Box<ReactorOverlapped> {
-    fn from(value: *mut ReactorOverlapped) -> Self {
-        unsafe { Box::from_raw(value) }
+impl std::fmt::Debug for OverlappedPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OverlappedPool")
+            .field("allocated", &self.allocated())
+            .finish()
     }
 }
 
@@ -92,14 +156,20 @@ impl From<*mut ReactorOverlapped> for Box<ReactorOverlapped> {
 ///
 #[derive(Clone, Debug)]
 pub struct SysPoller {
-    iocp: Arc<HANDLE>,
+    /// Completion port, null once [`close`](SysPoller::close)d.
+    iocp: Arc<RwLock<HANDLE>>,
+    pool: OverlappedPool,
+    batch_size: Arc<AtomicUsize>,
+    /// Max completions dequeued by one [`poll_once`](SysPoller::poll_once).
+    max_events: usize,
+    /// `GetQueuedCompletionStatusEx` output buffer reused across polls.
+    entries: Arc<Mutex<Vec<OVERLAPPED_ENTRY>>>,
 }
 
 impl Drop for SysPoller {
     fn drop(&mut self) {
         if Arc::strong_count(&self.iocp) == 1 {
-            log::debug!("Close iocp handle({:?})", self.io_handle());
-            unsafe { CloseHandle(*self.iocp) };
+            Self::release(&mut self.iocp.write().unwrap());
         }
     }
 }
@@ -109,6 +179,13 @@ unsafe impl Sync for SysPoller {}
 
 impl SysPoller {
     pub fn new() -> Result<Self> {
+        Self::with_max_events(DEFAULT_MAX_EVENTS)
+    }
+
+    /// Create poller dequeuing at most `max_events` completions per [`poll_once`](SysPoller::poll_once).
+    ///
+    /// Completions beyond the limit stay queued for the following polls.
+    pub fn with_max_events(max_events: usize) -> Result<Self> {
         static WSA_STARTUP: Once = Once::new();
 
         WSA_STARTUP.call_once(|| unsafe {
@@ -130,15 +207,98 @@ impl SysPoller {
         }
 
         Ok(Self {
-            iocp: Arc::new(handle),
+            iocp: Arc::new(RwLock::new(handle)),
+            pool: Default::default(),
+            batch_size: Arc::new(AtomicUsize::new(DEFAULT_BATCH_SIZE)),
+            max_events: max_events.max(1),
+            entries: Arc::new(Mutex::new(vec![
+                unsafe { std::mem::zeroed() };
+                DEFAULT_BATCH_SIZE
+            ])),
         })
     }
+
+    /// Set max completions dequeued by one `GetQueuedCompletionStatusEx` call, default 256.
+    ///
+    /// Full batches are followed by another non-blocking call, so one poll drains queued completions
+    /// up to its max events, see [`with_max_events`](SysPoller::with_max_events).
+    pub fn set_batch_size(&self, batch_size: usize) {
+        self.batch_size.store(batch_size.max(1), Ordering::Relaxed);
+    }
+    /// Get the completion port handle, null once [`close`](Self::close)d.
     pub fn io_handle(&self) -> super::RawFd {
-        *self.iocp
+        *self.iocp.read().unwrap()
+    }
+
+    /// Get the completion port, fails with `NotConnected` once [`close`](Self::close)d.
+    ///
+    /// Calls hold the guard while using the handle, so `close` never releases it (and lets its value
+    /// be reused) under a call. Take it once per call, a nested read may deadlock with a waiting `close`.
+    fn handle(&self) -> Result<RwLockReadGuard<'_, HANDLE>> {
+        let handle = self.iocp.read().unwrap();
+
+        if handle.is_null() {
+            return Err(Error::new(ErrorKind::NotConnected, "iocp poller closed"));
+        }
+
+        Ok(handle)
+    }
+
+    /// Close the completion port, following calls fail with `NotConnected`.
+    ///
+    /// Polls blocking in other threads are woken until they all returned, then the handle is closed.
+    /// Completions of operations still in flight are abandoned with it, their overlapped structures are leaked
+    /// as the kernel may still write them.
+    pub fn close(&self) -> Result<()> {
+        let mut handle = loop {
+            if let Ok(handle) = self.iocp.try_write() {
+                break handle;
+            }
+
+            _ = self.wakeup();
+
+            std::thread::yield_now();
+        };
+
+        Self::release(&mut handle);
+
+        Ok(())
+    }
+
+    fn release(handle: &mut HANDLE) {
+        if handle.is_null() {
+            return;
+        }
+
+        log::debug!("Close iocp handle({:?})", *handle);
+
+        unsafe { CloseHandle(*handle) };
+
+        *handle = null_mut();
+    }
+
+    /// Interrupt a `poll_once` blocking in another thread, or make the next one return immediately.
+    ///
+    /// Posts a completion without overlapped, which `poll_once` drops.
+    pub fn wakeup(&self) -> Result<()> {
+        let handle = self.handle()?;
+
+        if unsafe { PostQueuedCompletionStatus(*handle, 0, 0, null_mut()) } == 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Get overlapped structure pool bound to this poller.
+    pub(crate) fn overlapped_pool(&self) -> &OverlappedPool {
+        &self.pool
     }
 
     pub fn on_open_fd(&self, fd: RawFd) -> Result<()> {
-        let ret = unsafe { CreateIoCompletionPort(fd, self.io_handle(), 0, 0) };
+        let handle = self.handle()?;
+
+        let ret = unsafe { CreateIoCompletionPort(fd, *handle, 0, 0) };
 
         if ret == null_mut() {
             return Err(Error::last_os_error());
@@ -151,194 +311,273 @@ impl SysPoller {
         Ok(())
     }
 
-    pub fn poll_once(&self, keys: &[Key], timeout: Duration) -> Result<Vec<Event>> {
+    /// Check if `fd` can be associated with iocp, the handle must be opened with `FILE_FLAG_OVERLAPPED`.
+    pub fn supports(&self, fd: RawFd) -> bool {
+        fd != INVALID_HANDLE_VALUE && !fd.is_null()
+    }
+
+    /// Completion based, readiness triggers don't apply.
+    pub fn backend_info(&self) -> BackendInfo {
+        BackendInfo {
+            name: "iocp",
+            supports_files: true,
+            supports_edge_trigger: false,
+            max_batch: self.batch_size.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn poll_once(&self, _keys: &[Key], timeout: Duration) -> Result<Vec<Event>> {
+        let handle = self.handle()?;
+
         let start_time = SystemTime::now();
 
         let mut events = vec![];
 
+        let batch_size = self.batch_size.load(Ordering::Relaxed).max(1);
+
+        // Another thread polling the same poller holds the shared buffer, use a temporary one.
+        let mut shared = self.entries.try_lock().ok();
+
+        let mut owned = vec![];
+
+        let overlapped_entries = match shared.as_deref_mut() {
+            Some(entries) => entries,
+            None => &mut owned,
+        };
+
+        overlapped_entries.resize(batch_size, unsafe { std::mem::zeroed() });
+
+        let mut dequeued = 0;
+
+        let mut woken = false;
+
         loop {
             unsafe {
-                let elapsed = start_time.elapsed().unwrap();
+                let elapsed = start_time.elapsed().unwrap_or_default();
+
+                // Zero `timeout` still makes one non-blocking dequeue.
+                let mut wait_millis = timeout.saturating_sub(elapsed).as_millis() as u32;
+
+                // Keep dequeuing without waiting while batches come back full.
+                loop {
+                    let mut removed = 0u32;
+
+                    let len = batch_size.min(self.max_events - dequeued);
+
+                    let ret = GetQueuedCompletionStatusEx(
+                        *handle,
+                        overlapped_entries.as_mut_ptr() as *mut OVERLAPPED_ENTRY,
+                        len as u32,
+                        &mut removed,
+                        wait_millis,
+                        0,
+                    );
+
+                    if ret > 0 {
+                        let overlappeds = overlapped_entries[..removed as usize]
+                            .into_iter()
+                            .filter(|o| {
+                                // Posted by `wakeup`.
+                                if o.lpOverlapped.is_null() {
+                                    woken = true;
+                                }
 
-                if elapsed >= timeout {
-                    break;
-                }
+                                !o.lpOverlapped.is_null()
+                            })
+                            .map(|o| Box::from_raw((*o).lpOverlapped as *mut ReactorOverlapped))
+                            .collect::<Vec<_>>();
 
-                let real_timeout = timeout - elapsed;
-
-                let mut removed = 0u32;
-
-                let len = std::cmp::max(keys.len(), 20);
-
-                let mut overlapped_entries: Vec<OVERLAPPED_ENTRY> = vec![std::mem::zeroed(); len];
-
-                let ret = GetQueuedCompletionStatusEx(
-                    self.io_handle(),
-                    overlapped_entries.as_mut_ptr() as *mut OVERLAPPED_ENTRY,
-                    overlapped_entries.len() as u32,
-                    &mut removed,
-                    real_timeout.as_millis() as u32,
-                    0,
-                );
-
-                if ret > 0 {
-                    let overlappeds = overlapped_entries[..removed as usize]
-                        .into_iter()
-                        .map(|o| Box::from_raw((*o).lpOverlapped as *mut ReactorOverlapped))
-                        .collect::<Vec<_>>();
-
-                    for o in overlappeds {
-                        log::debug!("iocp file({:?}) raised({:?})", o.fd, o.event_name,);
-                        match o.event_name {
-                            EventName::Accept => {
-                                log::debug!(
-                                    "Internal({}) InternalHigh({})",
-                                    o.overlapped.Internal,
-                                    o.overlapped.InternalHigh
-                                );
-                                if o.overlapped.Internal != ERROR_SUCCESS as usize {
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::Accept),
-                                        message: Err(Error::from_raw_os_error(
-                                            o.overlapped.Internal as i32,
-                                        )),
-                                    })
-                                } else {
-                                    let addr = OsSocketAddr::copy_from_raw(
-                                        o.addrs[size_of::<SOCKADDR_IN6>()..].as_ptr()
-                                            as *mut SOCKADDR,
-                                        size_of::<SOCKADDR_IN6>() as i32,
-                                    );
+                        for o in overlappeds {
+                            // Operations are only cancelled when closing handle, nobody waits for them.
+                            if o.overlapped.Internal == STATUS_CANCELLED {
+                                log::trace!("fd({:?}) {:?} cancelled", o.fd, o.event_name);
 
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::Accept),
-                                        message: Ok(EventMessage::Accept(o.accept_fd, addr.into())),
-                                    })
-                                }
+                                self.pool.release_box(o);
+
+                                continue;
                             }
-                            EventName::Connect => {
-                                if o.overlapped.Internal != ERROR_SUCCESS as usize {
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::Connect),
-                                        message: Err(Error::from_raw_os_error(
-                                            o.overlapped.Internal as i32,
-                                        )),
-                                    })
-                                } else {
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::Connect),
-                                        message: Ok(EventMessage::Connect),
-                                    })
+
+                            match o.event_name {
+                                EventName::Accept => {
+                                    log::debug!(
+                                        "Internal({}) InternalHigh({})",
+                                        o.overlapped.Internal,
+                                        o.overlapped.InternalHigh
+                                    );
+                                    if o.overlapped.Internal != ERROR_SUCCESS as usize {
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::Accept),
+                                            message: Err(Error::from_raw_os_error(
+                                                o.overlapped.Internal as i32,
+                                            )),
+                                        })
+                                    } else {
+                                        let addr = OsSocketAddr::copy_from_raw(
+                                            o.addrs[size_of::<SOCKADDR_IN6>()..].as_ptr()
+                                                as *mut SOCKADDR,
+                                            size_of::<SOCKADDR_IN6>() as i32,
+                                        );
+
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::Accept),
+                                            message: Ok(EventMessage::Accept(
+                                                o.accept_fd,
+                                                addr.into(),
+                                            )),
+                                        })
+                                    }
                                 }
-                            }
-                            EventName::Read => {
-                                if o.overlapped.Internal != ERROR_SUCCESS as usize {
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::Read),
-                                        message: Err(Error::from_raw_os_error(
-                                            o.overlapped.Internal as i32,
-                                        )),
-                                    })
-                                } else {
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::Read),
-                                        message: Ok(EventMessage::Read(o.overlapped.InternalHigh)),
-                                    })
+                                EventName::Connect => {
+                                    if o.overlapped.Internal != ERROR_SUCCESS as usize {
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::Connect),
+                                            message: Err(Error::from_raw_os_error(
+                                                o.overlapped.Internal as i32,
+                                            )),
+                                        })
+                                    } else {
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::Connect),
+                                            message: Ok(EventMessage::Connect),
+                                        })
+                                    }
                                 }
-                            }
-                            EventName::RecvFrom => {
-                                if o.overlapped.Internal != ERROR_SUCCESS as usize {
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::RecvFrom),
-                                        message: Err(Error::from_raw_os_error(
-                                            o.overlapped.Internal as i32,
-                                        )),
-                                    })
-                                } else {
-                                    let addr = OsSocketAddr::copy_from_raw(
-                                        o.addrs[size_of::<SOCKADDR_IN6>()..].as_ptr()
-                                            as *mut SOCKADDR,
-                                        size_of::<SOCKADDR_IN6>() as i32,
-                                    );
-
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::RecvFrom),
-                                        message: Ok(EventMessage::RecvFrom(
-                                            o.overlapped.InternalHigh,
-                                            addr.into(),
-                                        )),
-                                    })
+                                EventName::Read => {
+                                    if o.overlapped.Internal != ERROR_SUCCESS as usize {
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::Read),
+                                            message: Err(Error::from_raw_os_error(
+                                                o.overlapped.Internal as i32,
+                                            )),
+                                        })
+                                    } else {
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::Read),
+                                            message: Ok(EventMessage::Read(
+                                                o.overlapped.InternalHigh,
+                                            )),
+                                        })
+                                    }
                                 }
-                            }
-                            EventName::Write => {
-                                if o.overlapped.Internal != ERROR_SUCCESS as usize {
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::Write),
-                                        message: Err(Error::from_raw_os_error(
-                                            o.overlapped.Internal as i32,
-                                        )),
-                                    })
-                                } else {
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::Write),
-                                        message: Ok(EventMessage::Write(o.overlapped.InternalHigh)),
-                                    })
+                                EventName::RecvFrom => {
+                                    if o.overlapped.Internal == STATUS_BUFFER_OVERFLOW {
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::RecvFrom),
+                                            message: Err(Error::from_raw_os_error(
+                                                WSAEMSGSIZE as i32,
+                                            )),
+                                        })
+                                    } else if o.overlapped.Internal != ERROR_SUCCESS as usize {
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::RecvFrom),
+                                            message: Err(Error::from_raw_os_error(
+                                                o.overlapped.Internal as i32,
+                                            )),
+                                        })
+                                    } else {
+                                        let addr = OsSocketAddr::copy_from_raw(
+                                            o.addrs[size_of::<SOCKADDR_IN6>()..].as_ptr()
+                                                as *mut SOCKADDR,
+                                            size_of::<SOCKADDR_IN6>() as i32,
+                                        );
+
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::RecvFrom),
+                                            message: Ok(EventMessage::RecvFrom(
+                                                o.overlapped.InternalHigh,
+                                                addr.into(),
+                                            )),
+                                        })
+                                    }
                                 }
-                            }
-                            EventName::SendTo => {
-                                if o.overlapped.Internal != ERROR_SUCCESS as usize {
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::SendTo),
-                                        message: Err(Error::from_raw_os_error(
-                                            o.overlapped.Internal as i32,
-                                        )),
-                                    })
-                                } else {
-                                    events.push(Event {
-                                        key: Key(o.fd, EventName::SendTo),
-                                        message: Ok(EventMessage::SendTo(
-                                            o.overlapped.InternalHigh,
-                                        )),
-                                    })
+                                EventName::Write => {
+                                    if o.overlapped.Internal != ERROR_SUCCESS as usize {
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::Write),
+                                            message: Err(Error::from_raw_os_error(
+                                                o.overlapped.Internal as i32,
+                                            )),
+                                        })
+                                    } else {
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::Write),
+                                            message: Ok(EventMessage::Write(
+                                                o.overlapped.InternalHigh,
+                                            )),
+                                        })
+                                    }
+                                }
+                                EventName::SendTo => {
+                                    if o.overlapped.Internal != ERROR_SUCCESS as usize {
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::SendTo),
+                                            message: Err(Error::from_raw_os_error(
+                                                o.overlapped.Internal as i32,
+                                            )),
+                                        })
+                                    } else {
+                                        events.push(Event {
+                                            key: Key(o.fd, EventName::SendTo),
+                                            message: Ok(EventMessage::SendTo(
+                                                o.overlapped.InternalHigh,
+                                            )),
+                                        })
+                                    }
                                 }
                             }
+
+                            // The completion is dequeued, so the overlapped can be reused.
+                            self.pool.release_box(o);
                         }
-                    }
-                } else {
-                    let e = GetLastError();
-
-                    if e == ERROR_ABANDONED_WAIT_0 {
-                        log::info!("iocp poller({:?}) closed", self.iocp);
-                        return Ok(vec![]);
-                    } else if e == WAIT_TIMEOUT {
-                        log::info!("iocp poller({:?}) timeout", self.iocp);
-                        continue;
+
+                        dequeued += removed as usize;
+
+                        if (removed as usize) < len || dequeued >= self.max_events {
+                            break;
+                        }
+
+                        wait_millis = 0;
                     } else {
-                        return Err(Error::last_os_error());
+                        let e = GetLastError();
+
+                        // `close` waits for polls to return, the port was closed by someone else.
+                        if e == ERROR_ABANDONED_WAIT_0 {
+                            log::info!("iocp poller({:?}) closed", *handle);
+
+                            return Err(Error::new(ErrorKind::NotConnected, "iocp poller closed"));
+                        } else if e == WAIT_TIMEOUT {
+                            if wait_millis > 0 {
+                                log::info!("iocp poller({:?}) timeout", *handle);
+                            }
+
+                            break;
+                        } else {
+                            return Err(Error::last_os_error());
+                        }
                     }
                 }
             }
 
-            if !events.is_empty() {
+            if !events.is_empty() || woken || dequeued >= self.max_events {
                 break;
             }
 
-            if start_time.elapsed().unwrap() > timeout {
+            if start_time.elapsed().unwrap_or_default() >= timeout {
                 break;
             }
         }
 
-        log::trace!("raised {:?}", events);
-
         Ok(events)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
-    use super::SysPoller;
+    use winapi::um::{ioapiset::PostQueuedCompletionStatus, minwinbase::OVERLAPPED};
+
+    use super::{EventName, RawFd, SysPoller};
 
     #[test]
     fn test_poll_one() {
@@ -348,4 +587,62 @@ mod tests {
 
         poller.poll_once(&[], Duration::from_secs(1)).unwrap();
     }
+
+    #[test]
+    fn test_poll_drains_all_completions() {
+        _ = pretty_env_logger::try_init();
+
+        let poller = SysPoller::new().unwrap();
+
+        poller.set_batch_size(64);
+
+        for i in 1..=500usize {
+            let overlapped = poller
+                .overlapped_pool()
+                .acquire(i as RawFd, EventName::Write);
+
+            assert_ne!(
+                unsafe {
+                    PostQueuedCompletionStatus(
+                        poller.io_handle(),
+                        0,
+                        0,
+                        overlapped as *mut OVERLAPPED,
+                    )
+                },
+                0
+            );
+        }
+
+        let events = poller.poll_once(&[], Duration::from_secs(1)).unwrap();
+
+        assert_eq!(events.len(), 500);
+    }
+
+    #[test]
+    fn test_poll_zero_timeout() {
+        _ = pretty_env_logger::try_init();
+
+        let poller = SysPoller::new().unwrap();
+
+        let start = Instant::now();
+
+        assert!(poller.poll_once(&[], Duration::ZERO).unwrap().is_empty());
+
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        let overlapped = poller
+            .overlapped_pool()
+            .acquire(1usize as RawFd, EventName::Write);
+
+        assert_ne!(
+            unsafe {
+                PostQueuedCompletionStatus(poller.io_handle(), 0, 0, overlapped as *mut OVERLAPPED)
+            },
+            0
+        );
+
+        // Queued completions are dequeued by a zero timeout poll.
+        assert_eq!(poller.poll_once(&[], Duration::ZERO).unwrap().len(), 1);
+    }
 }
@@ -1,44 +1,157 @@
 use std::{
-    io::{Error, Result},
+    io::{Error, ErrorKind, Result},
     ptr::null_mut,
-    sync::Arc,
+    sync::{Arc, Mutex, RwLock, RwLockReadGuard},
     time::Duration,
 };
 
-use super::{Event, EventName, Key, RawFd};
+use super::{BackendInfo, Event, EventName, Key, RawFd, DEFAULT_MAX_EVENTS};
 use libc::*;
 
 /// Event for iocp system.
 ///
 #[derive(Clone, Debug)]
 pub struct SysPoller {
-    handle: Arc<i32>,
+    /// Kqueue fd, `-1` once [`close`](SysPoller::close)d.
+    handle: Arc<RwLock<i32>>,
+    /// Max events returned by one `kevent` call.
+    max_events: usize,
+    /// `kevent` output buffer reused across polls, see [`with_max_events`](SysPoller::with_max_events).
+    fired_events: Arc<Mutex<Vec<kevent>>>,
 }
 
+// `udata` pointers of the buffered events are never dereferenced.
+unsafe impl Send for SysPoller {}
+unsafe impl Sync for SysPoller {}
+
 impl Drop for SysPoller {
     fn drop(&mut self) {
         if Arc::strong_count(&self.handle) == 1 {
-            log::debug!("Close iocp handle({:?})", *self.handle);
-            unsafe { close(*self.handle) };
+            Self::release(&mut self.handle.write().unwrap());
         }
     }
 }
 
+/// `EVFILT_USER` ident interrupting a blocking `kevent`, see [`SysPoller::wakeup`].
+const WAKEUP_IDENT: usize = 0;
+
 impl SysPoller {
     pub fn new() -> Result<Self> {
+        Self::with_max_events(DEFAULT_MAX_EVENTS)
+    }
+
+    /// Create poller returning at most `max_events` events per [`poll_once`](SysPoller::poll_once).
+    ///
+    /// Filters beyond the limit stay triggered and are returned by the following polls.
+    /// The internal wakeup trigger takes a slot of the limit when it fires.
+    pub fn with_max_events(max_events: usize) -> Result<Self> {
+        let max_events = max_events.max(1);
+
         let kq_handle = unsafe { libc::kqueue() };
 
+        if kq_handle < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        // `EV_CLEAR` resets the trigger once it is returned by `poll_once`.
+        let mut wakeup = kevent {
+            ident: WAKEUP_IDENT,
+            filter: EVFILT_USER,
+            flags: EV_ADD | EV_CLEAR,
+            fflags: 0,
+            data: 0,
+            udata: null_mut(),
+        };
+
+        if unsafe { kevent(kq_handle, &mut wakeup, 1, null_mut(), 0, null_mut()) } < 0 {
+            let err = Error::last_os_error();
+
+            unsafe { close(kq_handle) };
+
+            return Err(err);
+        }
+
         Ok(Self {
-            handle: Arc::new(kq_handle),
+            handle: Arc::new(RwLock::new(kq_handle)),
+            max_events,
+            fired_events: Arc::new(Mutex::new(vec![unsafe { std::mem::zeroed() }; max_events])),
         })
     }
+
+    /// Get the kqueue fd, fails with `NotConnected` once [`close`](Self::close)d.
+    ///
+    /// Calls hold the guard while using the fd, so `close` never releases it (and lets its number
+    /// be reused) under a call. Take it once per call, a nested read may deadlock with a waiting `close`.
+    fn handle(&self) -> Result<RwLockReadGuard<'_, i32>> {
+        let handle = self.handle.read().unwrap();
+
+        if *handle == -1 {
+            return Err(Error::new(ErrorKind::NotConnected, "kqueue poller closed"));
+        }
+
+        Ok(handle)
+    }
+
+    /// Close the kqueue fd, following calls fail with `NotConnected`.
+    ///
+    /// Polls blocking in other threads are woken until they all returned, then the fd is closed.
+    pub fn close(&self) -> Result<()> {
+        let mut handle = loop {
+            if let Ok(handle) = self.handle.try_write() {
+                break handle;
+            }
+
+            _ = self.wakeup();
+
+            std::thread::yield_now();
+        };
+
+        Self::release(&mut handle);
+
+        Ok(())
+    }
+
+    fn release(handle: &mut i32) {
+        if *handle == -1 {
+            return;
+        }
+
+        log::debug!("Close kqueue handle({:?})", *handle);
+
+        unsafe { close(*handle) };
+
+        *handle = -1;
+    }
+
+    /// Interrupt a `poll_once` blocking in another thread, or make the next one return immediately.
+    pub fn wakeup(&self) -> Result<()> {
+        let handle = self.handle()?;
+
+        let mut wakeup = kevent {
+            ident: WAKEUP_IDENT,
+            filter: EVFILT_USER,
+            flags: 0,
+            fflags: NOTE_TRIGGER,
+            data: 0,
+            udata: null_mut(),
+        };
+
+        if unsafe { kevent(*handle, &mut wakeup, 1, null_mut(), 0, null_mut()) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+    /// Add disabled filters for `fd`, `poll_once` arms them for the registered keys.
     pub fn on_open_fd(&self, fd: RawFd) -> Result<()> {
+        let handle = self.handle()?;
+
         log::debug!("add to kevent fd({})", fd);
         let mut evts = [
             kevent {
                 ident: fd as usize,
                 filter: EVFILT_WRITE,
-                flags: EV_ADD,
+                flags: EV_ADD | EV_DISABLE,
                 fflags: 0,
                 data: 0,
                 udata: null_mut(),
@@ -46,23 +159,14 @@ impl SysPoller {
             kevent {
                 ident: fd as usize,
                 filter: EVFILT_READ,
-                flags: EV_ADD,
+                flags: EV_ADD | EV_DISABLE,
                 fflags: 0,
                 data: 0,
                 udata: null_mut(),
             },
         ];
 
-        let ret = unsafe {
-            kevent(
-                *self.handle,
-                evts.as_mut_ptr(),
-                2,
-                null_mut(),
-                0,
-                null_mut(),
-            )
-        };
+        let ret = unsafe { kevent(*handle, evts.as_mut_ptr(), 2, null_mut(), 0, null_mut()) };
 
         if ret < 0 {
             return Err(Error::last_os_error());
@@ -71,7 +175,14 @@ impl SysPoller {
         Ok(())
     }
 
+    /// Same as [`on_open_fd`](Self::on_open_fd), kqueue has no exclusive wakeup mode.
+    pub fn on_open_fd_exclusive(&self, fd: RawFd) -> Result<()> {
+        self.on_open_fd(fd)
+    }
+
     pub fn on_close_fd(&self, fd: RawFd) -> Result<()> {
+        let handle = self.handle()?;
+
         log::debug!("remove from kevent fd({})", fd);
         let mut evts = [
             kevent {
@@ -92,16 +203,7 @@ impl SysPoller {
             },
         ];
 
-        let ret = unsafe {
-            kevent(
-                *self.handle,
-                evts.as_mut_ptr(),
-                2,
-                null_mut(),
-                0,
-                null_mut(),
-            )
-        };
+        let ret = unsafe { kevent(*handle, evts.as_mut_ptr(), 2, null_mut(), 0, null_mut()) };
 
         if ret < 0 {
             return Err(Error::last_os_error());
@@ -110,35 +212,57 @@ impl SysPoller {
         Ok(())
     }
 
+    /// Check if `fd` can be registered to kqueue, which supports any valid fd (sockets, pipes, regular files ..).
+    pub fn supports(&self, fd: RawFd) -> bool {
+        let mut stat: stat = unsafe { std::mem::zeroed() };
+
+        unsafe { fstat(fd, &mut stat) != -1 }
+    }
+
+    /// Filters are armed without `EV_CLEAR` on each poll, so registrations are level triggered.
+    pub fn backend_info(&self) -> BackendInfo {
+        BackendInfo {
+            name: "kqueue",
+            supports_files: true,
+            supports_edge_trigger: false,
+            max_batch: self.max_events,
+        }
+    }
+
     pub fn poll_once(&self, keys: &[Key], timeout: Duration) -> Result<Vec<Event>> {
-        // let mut changes = Vec::<kevent>::with_capacity(keys.len());
-
-        use libc::*;
-
-        // for key in keys {
-        //     let k_event = match key.1 {
-        //         EventName::Read => kevent {
-        //             ident: key.0 as usize,
-        //             filter: EVFILT_READ,
-        //             flags: EV_ADD | EV_ONESHOT | EV_ENABLE,
-        //             fflags: 0,
-        //             data: 0,
-        //             udata: null_mut(),
-        //         },
-        //         EventName::Write => kevent {
-        //             ident: key.0 as usize,
-        //             filter: EVFILT_WRITE,
-        //             flags: EV_ADD | EV_ONESHOT | EV_ENABLE,
-        //             fflags: 0,
-        //             data: 0,
-        //             udata: null_mut(),
-        //         },
-        //     };
-
-        //     changes.push(k_event);
-        // }
-
-        let mut fired_events = vec![unsafe { std::mem::zeroed() }; keys.len()];
+        let handle = self.handle()?;
+
+        // Arm oneshot filters for the registered keys, a fired filter is deleted by the kernel
+        // and armed again by the next poll if the key is still registered.
+        let changes = keys
+            .iter()
+            .map(|key| kevent {
+                ident: key.0 as usize,
+                filter: match key.1 {
+                    EventName::Read => EVFILT_READ,
+                    EventName::Write => EVFILT_WRITE,
+                },
+                flags: EV_ADD | EV_ENABLE | EV_ONESHOT,
+                fflags: 0,
+                data: 0,
+                udata: null_mut(),
+            })
+            .collect::<Vec<_>>();
+
+        // Changes failed to apply (e.g. closed fd) are returned as `EV_ERROR` events of their key.
+        // Another thread polling the same poller holds the shared buffer, use a temporary one.
+        let mut shared = self.fired_events.try_lock().ok();
+
+        let mut owned = vec![];
+
+        let fired_events = match shared.as_deref_mut() {
+            Some(fired_events) => fired_events,
+            None => {
+                owned.resize(self.max_events, unsafe { std::mem::zeroed() });
+
+                &mut owned
+            }
+        };
 
         let timeout = libc::timespec {
             tv_sec: timeout.as_secs() as i64,
@@ -147,9 +271,9 @@ impl SysPoller {
 
         let fired = unsafe {
             libc::kevent(
-                *self.handle,
-                null_mut(),
-                0,
+                *handle,
+                changes.as_ptr(),
+                changes.len() as i32,
                 fired_events.as_mut_ptr(),
                 fired_events.len() as i32,
                 &timeout,
@@ -204,8 +328,49 @@ impl SysPoller {
             }
         }
 
-        log::debug!("raised {:?}", ret);
-
         Ok(ret)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arm_read_filter() {
+        let poller = SysPoller::new().unwrap();
+
+        let mut fds = [0; 2];
+
+        assert_eq!(
+            unsafe { socketpair(AF_UNIX, SOCK_STREAM, 0, fds.as_mut_ptr()) },
+            0
+        );
+
+        poller.on_open_fd(fds[0]).unwrap();
+
+        assert_eq!(unsafe { write(fds[1], b"x".as_ptr() as *const _, 1) }, 1);
+
+        // Filters are disabled until a key is registered.
+        assert!(poller
+            .poll_once(&[], Duration::from_millis(10))
+            .unwrap()
+            .is_empty());
+
+        let events = poller
+            .poll_once(&[Key(fds[0], EventName::Read)], Duration::from_secs(1))
+            .unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].key, Key(fds[0], EventName::Read));
+        assert!(events[0].message.is_ok());
+
+        // The fired read filter is already deleted.
+        _ = poller.on_close_fd(fds[0]);
+
+        unsafe {
+            close(fds[0]);
+            close(fds[1]);
+        }
+    }
+}
@@ -7,7 +7,12 @@ pub mod tcp;
 pub mod udp;
 
 pub mod sys {
-    use std::{io::Result, net::SocketAddr, task::Poll, time::Duration};
+    use std::{
+        io::{IoSlice, IoSliceMut, Result},
+        net::SocketAddr,
+        task::Poll,
+        time::Duration,
+    };
 
     use crate::io::{IoReactor, RawFd};
 
@@ -24,8 +29,8 @@ pub mod sys {
         /// Bind socket to [`addr`](SocketAddr)
         fn bind(fd: RawFd, addr: SocketAddr) -> Result<()>;
 
-        /// Stream socket start listen incoming connection.
-        fn listen(fd: RawFd) -> Result<()>;
+        /// Stream socket start listen incoming connection, with accept queue length `backlog`.
+        fn listen(fd: RawFd, backlog: i32) -> Result<()>;
 
         /// Create new wrapper socket and bind to [`reactor`](IoReactor).
         ///
@@ -47,14 +52,20 @@ pub mod sys {
     /// Socket [`ReadBuffer`](crate::reactor::ReactorHandle::ReadBuffer)
     pub enum ReadBuffer<'cx> {
         Stream(&'cx mut [u8]),
+        /// Read of stream socket which leaves the data in the receive queue (`MSG_PEEK`).
+        Peek(&'cx mut [u8]),
         Datagram(&'cx mut [u8], &'cx mut Option<SocketAddr>),
 
         Accept(&'cx mut Option<RawFd>, &'cx mut Option<SocketAddr>),
+        /// Scatter read of stream socket, filled in order with one `readv`/`WSARecv` call.
+        Vectored(&'cx mut [IoSliceMut<'cx>]),
     }
 
     /// Socket [`WriteBuffer`](crate::reactor::ReactorHandle::WriteBuffer)
     pub enum WriteBuffer<'cx> {
         Stream(&'cx [u8]),
         Datagram(&'cx [u8], &'cx SocketAddr),
+        /// Gather write of stream (or connected datagram) socket, sent with one `writev`/`WSASend` call.
+        Vectored(&'cx [IoSlice<'cx>]),
     }
 }
@@ -1,7 +1,7 @@
 use std::{
     ffi::c_void,
     io::*,
-    mem::size_of,
+    mem::{size_of, ManuallyDrop},
     net::SocketAddr,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -22,24 +22,950 @@ use crate::{
 };
 
 use super::sys::{self, ReadBuffer, Socket, WriteBuffer};
+use super::tcp::KeepaliveConfig;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use super::udp::RecvInfo;
+
+/// Keepalive idle time option, apple platforms name it `TCP_KEEPALIVE`.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+const TCP_KEEPIDLE_OPT: c_int = TCP_KEEPALIVE;
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+const TCP_KEEPIDLE_OPT: c_int = TCP_KEEPIDLE;
+
+/// Ipv6 group membership options, bsd platforms name them `IPV6_JOIN_GROUP`/`IPV6_LEAVE_GROUP`.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+use libc::{IPV6_JOIN_GROUP as IPV6_ADD_MEMBERSHIP, IPV6_LEAVE_GROUP as IPV6_DROP_MEMBERSHIP};
+
+/// Max buffers per `readv`/`writev` call, `IOV_MAX` of linux and bsd (not exported by libc on linux).
+const MAX_IOV: usize = 1024;
+
+fn in_addr_v4(addr: std::net::Ipv4Addr) -> in_addr {
+    in_addr {
+        s_addr: u32::from_ne_bytes(addr.octets()),
+    }
+}
+
+fn ip_mreq_v4(multiaddr: std::net::Ipv4Addr, interface: std::net::Ipv4Addr) -> ip_mreq {
+    ip_mreq {
+        imr_multiaddr: in_addr_v4(multiaddr),
+        imr_interface: in_addr_v4(interface),
+    }
+}
+
+fn ip_mreq_v6(multiaddr: std::net::Ipv6Addr, interface: u32) -> ipv6_mreq {
+    let mut mreq: ipv6_mreq = unsafe { std::mem::zeroed() };
+
+    mreq.ipv6mr_multiaddr.s6_addr = multiaddr.octets();
+    mreq.ipv6mr_interface = interface as _;
+
+    mreq
+}
+
+/// Receive datagrams into `bufs` with one `recvmmsg` call.
+///
+/// Datagrams longer than their buffer are truncated to it and flagged.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn recv_batch(fd: RawFd, bufs: &mut [(Vec<u8>, SocketAddr, bool)]) -> Result<usize> {
+    let mut addrs: Vec<sockaddr_storage> = vec![unsafe { std::mem::zeroed() }; bufs.len()];
+
+    let mut iovs = bufs
+        .iter_mut()
+        .map(|(buf, _, _)| iovec {
+            iov_base: buf.as_mut_ptr() as *mut c_void,
+            iov_len: buf.len(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut msgs = iovs
+        .iter_mut()
+        .zip(addrs.iter_mut())
+        .map(|(iov, addr)| {
+            let mut msg: mmsghdr = unsafe { std::mem::zeroed() };
+
+            msg.msg_hdr.msg_name = addr as *mut _ as *mut c_void;
+            msg.msg_hdr.msg_namelen = size_of::<sockaddr_storage>() as socklen_t;
+            msg.msg_hdr.msg_iov = iov;
+            msg.msg_hdr.msg_iovlen = 1;
+
+            msg
+        })
+        .collect::<Vec<_>>();
+
+    let received = unsafe {
+        recvmmsg(
+            fd,
+            msgs.as_mut_ptr(),
+            msgs.len() as _,
+            0,
+            std::ptr::null_mut(),
+        )
+    };
+
+    if received < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    for (i, msg) in msgs[..received as usize].iter().enumerate() {
+        let source = unsafe {
+            OsSocketAddr::copy_from_raw(
+                msg.msg_hdr.msg_name as *mut sockaddr,
+                msg.msg_hdr.msg_namelen,
+            )
+        }
+        .into_addr()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid datagram source address"))?;
+
+        let truncated = msg.msg_hdr.msg_flags & MSG_TRUNC != 0;
+
+        if truncated {
+            log::debug!(
+                "fd({}) datagram({}) of batch({}) truncated to buffer({})",
+                fd,
+                i,
+                received,
+                msg.msg_len
+            );
+        }
+
+        bufs[i].0.truncate(msg.msg_len as usize);
+        bufs[i].1 = source;
+        bufs[i].2 = truncated;
+    }
+
+    Ok(received as usize)
+}
+
+/// Receive one datagram into `buf` with `recvmsg`, returns its length, sender
+/// and whether it was truncated to `buf`.
+fn recv_datagram(fd: RawFd, buf: &mut [u8]) -> Result<(usize, Option<SocketAddr>, bool)> {
+    let mut addr: sockaddr_storage = unsafe { std::mem::zeroed() };
+
+    let mut iov = iovec {
+        iov_base: buf.as_mut_ptr() as *mut c_void,
+        iov_len: buf.len(),
+    };
+
+    let mut msg: msghdr = unsafe { std::mem::zeroed() };
+
+    msg.msg_name = &mut addr as *mut _ as *mut c_void;
+    msg.msg_namelen = size_of::<sockaddr_storage>() as socklen_t;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    let len = unsafe { recvmsg(fd, &mut msg, 0) };
+
+    if len < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    let source = unsafe {
+        OsSocketAddr::copy_from_raw(&mut addr as *mut _ as *mut sockaddr, msg.msg_namelen)
+    }
+    .into_addr();
+
+    Ok((len as usize, source, msg.msg_flags & MSG_TRUNC != 0))
+}
+
+/// Receive datagrams into `bufs` with `recvmsg` calls, until no more datagram is queued.
+///
+/// Datagrams longer than their buffer are truncated to it and flagged.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn recv_batch(fd: RawFd, bufs: &mut [(Vec<u8>, SocketAddr, bool)]) -> Result<usize> {
+    let mut received = 0;
+
+    for (buf, remote, truncated) in bufs.iter_mut() {
+        let (len, source, trunc) = match recv_datagram(fd, buf) {
+            Ok(datagram) => datagram,
+            // Report datagrams already received, the error shows up again on the next call.
+            Err(_) if received > 0 => break,
+            Err(err) => return Err(err),
+        };
+
+        *remote = source
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid datagram source address"))?;
+
+        buf.truncate(len);
+
+        *truncated = trunc;
+
+        received += 1;
+    }
+
+    Ok(received)
+}
+
+/// Send `packets` with one `sendmmsg` call, returns the number of packets sent.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn send_batch(fd: RawFd, packets: &[(Vec<u8>, SocketAddr)]) -> Result<usize> {
+    let addrs = packets
+        .iter()
+        .map(|(_, remote)| OsSocketAddr::from(*remote))
+        .collect::<Vec<_>>();
+
+    let mut iovs = packets
+        .iter()
+        .map(|(buf, _)| iovec {
+            iov_base: buf.as_ptr() as *mut c_void,
+            iov_len: buf.len(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut msgs = iovs
+        .iter_mut()
+        .zip(addrs.iter())
+        .map(|(iov, addr)| {
+            let mut msg: mmsghdr = unsafe { std::mem::zeroed() };
+
+            msg.msg_hdr.msg_name = addr.as_ptr() as *mut c_void;
+            msg.msg_hdr.msg_namelen = addr.len();
+            msg.msg_hdr.msg_iov = iov;
+            msg.msg_hdr.msg_iovlen = 1;
+
+            msg
+        })
+        .collect::<Vec<_>>();
+
+    let sent = unsafe { sendmmsg(fd, msgs.as_mut_ptr(), msgs.len() as _, 0) };
+
+    if sent < 0 {
+        return Err(Error::last_os_error());
+    }
+
+    Ok(sent as usize)
+}
+
+/// Send `packets` with `sendto` calls, until the socket buffer is full.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn send_batch(fd: RawFd, packets: &[(Vec<u8>, SocketAddr)]) -> Result<usize> {
+    let mut sent = 0;
+
+    for (buf, remote) in packets {
+        let addr = OsSocketAddr::from(*remote);
+
+        let len = unsafe {
+            sendto(
+                fd,
+                buf.as_ptr() as *const c_void,
+                buf.len(),
+                0,
+                addr.as_ptr(),
+                addr.len(),
+            )
+        };
+
+        if len < 0 {
+            // Report packets already sent, the error shows up again on the next call.
+            if sent > 0 {
+                break;
+            }
+
+            return Err(Error::last_os_error());
+        }
+
+        sent += 1;
+    }
+
+    Ok(sent)
+}
+
+/// Keepalive options are in whole seconds, and the kernel rejects 0.
+fn keepalive_secs(duration: Duration) -> c_int {
+    duration.as_secs().clamp(1, c_int::MAX as u64) as c_int
+}
+
+/// Socket handle wrapper.
+#[derive(Debug, Clone)]
+pub struct Handle {
+    /// Socket handle bind reactor
+    pub reactor: IoReactor,
+    /// Socket handle bind os fd.
+    pub fd: Arc<i32>,
+    /// If this socket is ipv4 familiy
+    pub ip_v4: bool,
+    /// Close status
+    pub closed: Arc<AtomicBool>,
+}
+
+impl Handle {
+    pub fn to_raw_fd(&self) -> RawFd {
+        *self.fd as RawFd
+    }
+
+    /// Adopt an already opened socket `fd` (e.g. inherited from parent process) and bind it to [`reactor`](IoReactor).
+    ///
+    /// The socket is switched to nonblock and close-on-exec mode. As with [`new`](sys::Socket::new),
+    /// the `fd` is released if this method return an error.
+    pub fn adopt(fd: RawFd, reactor: IoReactor) -> Result<Self> {
+        let ip_v4 = unsafe {
+            let prepare = || -> Result<bool> {
+                super::super::noblock(fd)?;
+
+                if fcntl(fd, F_SETFD, FD_CLOEXEC) < 0 {
+                    return Err(Error::last_os_error());
+                }
+
+                Self::is_ipv4(fd)
+            };
+
+            match prepare() {
+                Ok(ip_v4) => ip_v4,
+                Err(err) => {
+                    close(fd);
+                    return Err(err);
+                }
+            }
+        };
+
+        Self::new(ip_v4, fd, reactor)
+    }
+
+    /// Duplicate this socket and bind the duplicate to `reactor`, e.g. to accept on one listener from several reactors.
+    ///
+    /// With `exclusive`, the duplicate is registered by [`IoReactor::on_open_fd_exclusive`].
+    pub fn share(&self, mut reactor: IoReactor, exclusive: bool) -> Result<Self> {
+        let fd = unsafe { fcntl(*self.fd, F_DUPFD_CLOEXEC, 0) };
+
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let registered = match exclusive {
+            true => reactor.on_open_fd_exclusive(fd),
+            false => reactor.on_open_fd(fd),
+        };
+
+        if let Err(err) = registered {
+            unsafe { close(fd) };
+
+            return Err(err);
+        }
+
+        Ok(Self {
+            reactor,
+            fd: Arc::new(fd),
+            closed: Default::default(),
+            ip_v4: self.ip_v4,
+        })
+    }
+
+    /// Deregister socket from [`reactor`](IoReactor) and release the fd without closing it.
+    ///
+    /// Returns the handle back if other clones (e.g. read/write streams) are still alive.
+    pub fn detach(self) -> std::result::Result<(IoReactor, RawFd), Self> {
+        if Arc::strong_count(&self.fd) != 1 {
+            return Err(self);
+        }
+
+        let mut this = ManuallyDrop::new(self);
+
+        let fd = *this.fd;
+
+        this.reactor.on_close_fd(fd);
+
+        // Release fields without `Drop for Handle`, which closes the fd.
+        let (reactor, _, _) = unsafe {
+            (
+                std::ptr::read(&this.reactor),
+                std::ptr::read(&this.fd),
+                std::ptr::read(&this.closed),
+            )
+        };
+
+        Ok((reactor, fd))
+    }
+
+    /// Set `FD_CLOEXEC`, clear it to let a child process started with `exec` inherit this socket.
+    pub fn set_cloexec(&self, on: bool) -> Result<()> {
+        unsafe {
+            let flags = fcntl(*self.fd, F_GETFD);
+
+            if flags < 0 {
+                return Err(Error::last_os_error());
+            }
+
+            let flags = if on {
+                flags | FD_CLOEXEC
+            } else {
+                flags & !FD_CLOEXEC
+            };
+
+            if fcntl(*self.fd, F_SETFD, flags) < 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get `FD_CLOEXEC` of this socket.
+    pub fn cloexec(&self) -> Result<bool> {
+        let flags = unsafe { fcntl(*self.fd, F_GETFD) };
+
+        if flags < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(flags & FD_CLOEXEC != 0)
+    }
+
+    /// Check if socket `fd` is bound to ipv4 family address.
+    pub fn is_ipv4(fd: RawFd) -> Result<bool> {
+        let mut addr: sockaddr_storage = unsafe { std::mem::zeroed() };
+
+        let mut len = size_of::<sockaddr_storage>() as socklen_t;
+
+        if unsafe { getsockname(fd, &mut addr as *mut _ as *mut sockaddr, &mut len) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(addr.ss_family as i32 != AF_INET6)
+    }
+
+    /// Check if socket `fd` is listening for incoming connections.
+    pub fn is_listener(fd: RawFd) -> Result<bool> {
+        let mut value: c_int = 0;
+
+        let mut len = size_of::<c_int>() as socklen_t;
+
+        if unsafe {
+            getsockopt(
+                fd,
+                SOL_SOCKET,
+                SO_ACCEPTCONN,
+                &mut value as *mut _ as *mut c_void,
+                &mut len,
+            )
+        } < 0
+        {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(value != 0)
+    }
+
+    /// Set `SO_REUSEADDR` and optionally `SO_REUSEPORT` of listening socket `fd`, must be called before bind.
+    pub(crate) fn set_listener_options(fd: RawFd, reuse_port: bool) -> Result<()> {
+        set_raw_option(fd, SOL_SOCKET, SO_REUSEADDR, 1 as c_int)?;
+
+        if reuse_port {
+            set_raw_option(fd, SOL_SOCKET, SO_REUSEPORT, 1 as c_int)?;
+        }
+
+        Ok(())
+    }
+
+    /// Set `IPV6_V6ONLY` of ipv6 socket `fd`, must be called before bind.
+    pub(crate) fn set_v6only(fd: RawFd, on: bool) -> Result<()> {
+        set_raw_option(fd, IPPROTO_IPV6, IPV6_V6ONLY, on as c_int)
+    }
+
+    /// Bind socket `fd` to network interface `device` with `SO_BINDTODEVICE`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) fn set_bind_device(fd: RawFd, device: &str) -> Result<()> {
+        if unsafe {
+            setsockopt(
+                fd,
+                SOL_SOCKET,
+                SO_BINDTODEVICE,
+                device.as_ptr() as *const c_void,
+                device.len() as socklen_t,
+            )
+        } < 0
+        {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Set socket option `name` at `level` to `value`.
+    pub(crate) fn set_option<T>(&self, level: c_int, name: c_int, value: T) -> Result<()> {
+        set_raw_option(*self.fd, level, name, value)
+    }
+
+    /// Get socket option `name` at `level`.
+    pub(crate) fn get_option<T: Default>(&self, level: c_int, name: c_int) -> Result<T> {
+        let mut value = T::default();
+
+        let mut len = size_of::<T>() as socklen_t;
+
+        if unsafe {
+            getsockopt(
+                *self.fd,
+                level,
+                name,
+                &mut value as *mut _ as *mut c_void,
+                &mut len,
+            )
+        } < 0
+        {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(value)
+    }
+
+    /// Get the local address this socket is bound to (`getsockname`).
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket_addr(getsockname, "invalid local address")
+    }
+
+    /// Get the remote address this socket is connected to (`getpeername`),
+    /// also works for sockets adopted from raw fd.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.socket_addr(getpeername, "invalid peer address")
+    }
+
+    fn socket_addr(
+        &self,
+        query: unsafe extern "C" fn(c_int, *mut sockaddr, *mut socklen_t) -> c_int,
+        invalid: &'static str,
+    ) -> Result<SocketAddr> {
+        let mut addr: sockaddr_storage = unsafe { std::mem::zeroed() };
+
+        let mut len = size_of::<sockaddr_storage>() as socklen_t;
+
+        unsafe {
+            if query(*self.fd, &mut addr as *mut _ as *mut sockaddr, &mut len) < 0 {
+                return Err(Error::last_os_error());
+            }
+
+            OsSocketAddr::copy_from_raw(&mut addr as *mut _ as *mut sockaddr, len)
+                .into_addr()
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, invalid))
+        }
+    }
+
+    /// Set the default peer of this datagram socket (`connect`), which completes without waiting.
+    ///
+    /// Datagrams from other addresses are dropped by the kernel, and icmp errors are reported on later calls.
+    pub fn connect_datagram(&self, peer: SocketAddr) -> Result<()> {
+        let addr: OsSocketAddr = peer.into();
+
+        if unsafe { connect(*self.fd, addr.as_ptr(), addr.len()) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Shut down the read, write or both halves of this connection, the fd stays open.
+    pub fn shutdown(&self, how: std::net::Shutdown) -> Result<()> {
+        let how = match how {
+            std::net::Shutdown::Read => SHUT_RD,
+            std::net::Shutdown::Write => SHUT_WR,
+            std::net::Shutdown::Both => SHUT_RDWR,
+        };
+
+        if unsafe { shutdown(*self.fd, how) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Check if the socket is writable with a zero timeout `poll`, without registering a waker.
+    ///
+    /// Errors and hangups count as writable, the following write reports them.
+    pub fn poll_write_ready_nonblocking(&self) -> Poll<Result<()>> {
+        let mut pollfd = pollfd {
+            fd: *self.fd,
+            events: POLLOUT,
+            revents: 0,
+        };
+
+        if unsafe { poll(&mut pollfd, 1, 0) } < 0 {
+            return Poll::Ready(Err(Error::last_os_error()));
+        }
+
+        if pollfd.revents & (POLLOUT | POLLERR | POLLHUP) != 0 {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Enable or disable `TCP_NODELAY` (nagle algorithm off) of this socket.
+    pub fn set_nodelay(&self, on: bool) -> Result<()> {
+        self.set_option(IPPROTO_TCP, TCP_NODELAY, on as c_int)
+    }
+
+    /// Get `TCP_NODELAY` of this socket.
+    pub fn nodelay(&self) -> Result<bool> {
+        Ok(self.get_option::<c_int>(IPPROTO_TCP, TCP_NODELAY)? != 0)
+    }
+
+    /// Set `IP_TTL` (ipv4) or `IPV6_UNICAST_HOPS` (ipv6) of this socket.
+    pub fn set_ttl(&self, ttl: u32) -> Result<()> {
+        if self.ip_v4 {
+            self.set_option(IPPROTO_IP, IP_TTL, ttl as c_int)
+        } else {
+            self.set_option(IPPROTO_IPV6, IPV6_UNICAST_HOPS, ttl as c_int)
+        }
+    }
+
+    /// Get `IP_TTL` (ipv4) or `IPV6_UNICAST_HOPS` (ipv6) of this socket.
+    pub fn ttl(&self) -> Result<u32> {
+        let ttl = if self.ip_v4 {
+            self.get_option::<c_int>(IPPROTO_IP, IP_TTL)?
+        } else {
+            self.get_option::<c_int>(IPPROTO_IPV6, IPV6_UNICAST_HOPS)?
+        };
+
+        Ok(ttl as u32)
+    }
+
+    /// Enable or disable `SO_BROADCAST`, sending to broadcast addresses fails with `EACCES` without it.
+    pub fn set_broadcast(&self, on: bool) -> Result<()> {
+        self.set_option(SOL_SOCKET, SO_BROADCAST, on as c_int)
+    }
+
+    /// Get `SO_BROADCAST` of this socket.
+    pub fn broadcast(&self) -> Result<bool> {
+        Ok(self.get_option::<c_int>(SOL_SOCKET, SO_BROADCAST)? != 0)
+    }
+
+    /// Join ipv4 multicast group `multiaddr` on the interface with address `interface`
+    /// (`0.0.0.0` lets the system choose).
+    pub fn join_multicast_v4(
+        &self,
+        multiaddr: std::net::Ipv4Addr,
+        interface: std::net::Ipv4Addr,
+    ) -> Result<()> {
+        self.set_option(
+            IPPROTO_IP,
+            IP_ADD_MEMBERSHIP,
+            ip_mreq_v4(multiaddr, interface),
+        )
+    }
+
+    /// Leave ipv4 multicast group joined by [`join_multicast_v4`](Self::join_multicast_v4).
+    pub fn leave_multicast_v4(
+        &self,
+        multiaddr: std::net::Ipv4Addr,
+        interface: std::net::Ipv4Addr,
+    ) -> Result<()> {
+        self.set_option(
+            IPPROTO_IP,
+            IP_DROP_MEMBERSHIP,
+            ip_mreq_v4(multiaddr, interface),
+        )
+    }
+
+    /// Join ipv6 multicast group `multiaddr` on the interface with index `interface` (0 lets the system choose).
+    pub fn join_multicast_v6(&self, multiaddr: std::net::Ipv6Addr, interface: u32) -> Result<()> {
+        self.set_option(
+            IPPROTO_IPV6,
+            IPV6_ADD_MEMBERSHIP,
+            ip_mreq_v6(multiaddr, interface),
+        )
+    }
+
+    /// Leave ipv6 multicast group joined by [`join_multicast_v6`](Self::join_multicast_v6).
+    pub fn leave_multicast_v6(&self, multiaddr: std::net::Ipv6Addr, interface: u32) -> Result<()> {
+        self.set_option(
+            IPPROTO_IPV6,
+            IPV6_DROP_MEMBERSHIP,
+            ip_mreq_v6(multiaddr, interface),
+        )
+    }
+
+    /// Set `IP_MULTICAST_LOOP`, whether multicast datagrams sent by this socket are looped back to local members.
+    pub fn set_multicast_loop_v4(&self, on: bool) -> Result<()> {
+        self.set_option(IPPROTO_IP, IP_MULTICAST_LOOP, on as c_int)
+    }
+
+    /// Get `IP_MULTICAST_LOOP` of this socket.
+    pub fn multicast_loop_v4(&self) -> Result<bool> {
+        Ok(self.get_option::<c_int>(IPPROTO_IP, IP_MULTICAST_LOOP)? != 0)
+    }
+
+    /// Set `IP_MULTICAST_TTL` of outgoing multicast datagrams, default 1 (stay in the local network).
+    pub fn set_multicast_ttl_v4(&self, ttl: u32) -> Result<()> {
+        self.set_option(IPPROTO_IP, IP_MULTICAST_TTL, ttl as c_int)
+    }
+
+    /// Get `IP_MULTICAST_TTL` of this socket.
+    pub fn multicast_ttl_v4(&self) -> Result<u32> {
+        Ok(self.get_option::<c_int>(IPPROTO_IP, IP_MULTICAST_TTL)? as u32)
+    }
+
+    /// Set `IP_MULTICAST_IF`, the interface (by address) outgoing multicast datagrams are sent on.
+    pub fn set_multicast_if_v4(&self, interface: std::net::Ipv4Addr) -> Result<()> {
+        self.set_option(IPPROTO_IP, IP_MULTICAST_IF, in_addr_v4(interface))
+    }
+
+    /// Set `SO_SNDBUF` of this socket, the kernel may adjust the value (linux doubles it).
+    pub fn set_send_buffer_size(&self, size: usize) -> Result<()> {
+        self.set_option(
+            SOL_SOCKET,
+            SO_SNDBUF,
+            size.min(c_int::MAX as usize) as c_int,
+        )
+    }
+
+    /// Get `SO_SNDBUF` of this socket, as reported by the kernel.
+    pub fn send_buffer_size(&self) -> Result<usize> {
+        Ok(self.get_option::<c_int>(SOL_SOCKET, SO_SNDBUF)? as usize)
+    }
+
+    /// Set `SO_RCVBUF` of this socket, the kernel may adjust the value (linux doubles it).
+    pub fn set_recv_buffer_size(&self, size: usize) -> Result<()> {
+        self.set_option(
+            SOL_SOCKET,
+            SO_RCVBUF,
+            size.min(c_int::MAX as usize) as c_int,
+        )
+    }
+
+    /// Get `SO_RCVBUF` of this socket, as reported by the kernel.
+    pub fn recv_buffer_size(&self) -> Result<usize> {
+        Ok(self.get_option::<c_int>(SOL_SOCKET, SO_RCVBUF)? as usize)
+    }
+
+    /// Set don't fragment (DF) bit of outgoing packets, oversized datagrams fail with `EMSGSIZE`
+    /// instead of being fragmented.
+    ///
+    /// Maps to `IP_MTU_DISCOVER`/`IPV6_MTU_DISCOVER` with `IP_PMTUDISC_DO` on linux.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_dont_fragment(&self, on: bool) -> Result<()> {
+        if self.ip_v4 {
+            let value = if on { IP_PMTUDISC_DO } else { IP_PMTUDISC_DONT };
+
+            self.set_option(IPPROTO_IP, IP_MTU_DISCOVER, value)
+        } else {
+            let value = if on {
+                IPV6_PMTUDISC_DO
+            } else {
+                IPV6_PMTUDISC_DONT
+            };
+
+            self.set_option(IPPROTO_IPV6, IPV6_MTU_DISCOVER, value)
+        }
+    }
+
+    /// Get don't fragment (DF) bit setting of this socket.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn dont_fragment(&self) -> Result<bool> {
+        if self.ip_v4 {
+            Ok(self.get_option::<c_int>(IPPROTO_IP, IP_MTU_DISCOVER)? == IP_PMTUDISC_DO)
+        } else {
+            Ok(self.get_option::<c_int>(IPPROTO_IPV6, IPV6_MTU_DISCOVER)? == IPV6_PMTUDISC_DO)
+        }
+    }
+
+    /// Set don't fragment (DF) bit of outgoing packets with `IP_DONTFRAG`/`IPV6_DONTFRAG`.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn set_dont_fragment(&self, on: bool) -> Result<()> {
+        if self.ip_v4 {
+            self.set_option(IPPROTO_IP, IP_DONTFRAG, on as c_int)
+        } else {
+            self.set_option(IPPROTO_IPV6, IPV6_DONTFRAG, on as c_int)
+        }
+    }
+
+    /// Get don't fragment (DF) bit setting of this socket.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn dont_fragment(&self) -> Result<bool> {
+        if self.ip_v4 {
+            Ok(self.get_option::<c_int>(IPPROTO_IP, IP_DONTFRAG)? != 0)
+        } else {
+            Ok(self.get_option::<c_int>(IPPROTO_IPV6, IPV6_DONTFRAG)? != 0)
+        }
+    }
+
+    /// Get the path mtu known by kernel (`IP_MTU`/`IPV6_MTU`), only valid for connected sockets.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn path_mtu(&self) -> Result<usize> {
+        let mtu = if self.ip_v4 {
+            self.get_option::<c_int>(IPPROTO_IP, IP_MTU)?
+        } else {
+            self.get_option::<c_int>(IPPROTO_IPV6, IPV6_MTU)?
+        };
+
+        Ok(mtu as usize)
+    }
+
+    /// Enable `IP_PKTINFO`/`IPV6_RECVPKTINFO`, datagrams received by
+    /// [`poll_recv_msg`](Self::poll_recv_msg) then report destination address and interface index.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_recv_pktinfo(&self, on: bool) -> Result<()> {
+        if self.ip_v4 {
+            self.set_option(IPPROTO_IP, IP_PKTINFO, on as c_int)
+        } else {
+            self.set_option(IPPROTO_IPV6, IPV6_RECVPKTINFO, on as c_int)
+        }
+    }
+
+    /// Enable tcp keepalive probes with `config`, or disable keepalive if `config` is [`None`].
+    pub fn set_keepalive(&self, config: Option<&KeepaliveConfig>) -> Result<()> {
+        let config = match config {
+            Some(config) => config,
+            None => return self.set_option(SOL_SOCKET, SO_KEEPALIVE, 0 as c_int),
+        };
+
+        self.set_option(IPPROTO_TCP, TCP_KEEPIDLE_OPT, keepalive_secs(config.idle))?;
+        self.set_option(IPPROTO_TCP, TCP_KEEPINTVL, keepalive_secs(config.interval))?;
+        self.set_option(IPPROTO_TCP, TCP_KEEPCNT, config.count as c_int)?;
+
+        self.set_option(SOL_SOCKET, SO_KEEPALIVE, 1 as c_int)
+    }
+
+    /// Get tcp keepalive configuration, [`None`] if keepalive is disabled.
+    pub fn keepalive(&self) -> Result<Option<KeepaliveConfig>> {
+        if self.get_option::<c_int>(SOL_SOCKET, SO_KEEPALIVE)? == 0 {
+            return Ok(None);
+        }
+
+        let idle = self.get_option::<c_int>(IPPROTO_TCP, TCP_KEEPIDLE_OPT)?;
+        let interval = self.get_option::<c_int>(IPPROTO_TCP, TCP_KEEPINTVL)?;
+        let count = self.get_option::<c_int>(IPPROTO_TCP, TCP_KEEPCNT)?;
+
+        Ok(Some(KeepaliveConfig {
+            idle: Duration::from_secs(idle as u64),
+            interval: Duration::from_secs(interval as u64),
+            count: count as u32,
+        }))
+    }
+
+    /// Set `SO_MARK` of this socket, which tags outgoing packets for policy routing (`ip rule`)
+    /// and netfilter (`iptables -m mark`).
+    ///
+    /// Requires `CAP_NET_ADMIN` (or `CAP_NET_RAW` since linux 5.17), otherwise returns [`ErrorKind::PermissionDenied`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_mark(&self, mark: u32) -> Result<()> {
+        self.set_option(SOL_SOCKET, SO_MARK, mark).map_err(|err| {
+            if err.kind() == ErrorKind::PermissionDenied {
+                Error::new(
+                    ErrorKind::PermissionDenied,
+                    format!("set SO_MARK requires CAP_NET_ADMIN, {}", err),
+                )
+            } else {
+                err
+            }
+        })
+    }
+
+    /// Get `SO_MARK` of this socket.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn mark(&self) -> Result<u32> {
+        self.get_option(SOL_SOCKET, SO_MARK)
+    }
+
+    /// Set `SO_INCOMING_CPU`, with `SO_REUSEPORT` listeners the kernel steers new connections
+    /// to the listener whose incoming cpu matches the cpu handling the rx queue.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_incoming_cpu(&self, cpu: i32) -> Result<()> {
+        self.set_option(SOL_SOCKET, SO_INCOMING_CPU, cpu as c_int)
+    }
+
+    /// Get `SO_INCOMING_CPU` of this socket.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn incoming_cpu(&self) -> Result<i32> {
+        self.get_option::<c_int>(SOL_SOCKET, SO_INCOMING_CPU)
+    }
+
+    /// Get `SO_INCOMING_NAPI_ID`, the id of the napi context (rx queue) which received the last packet,
+    /// 0 if unknown.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn incoming_napi_id(&self) -> Result<u32> {
+        self.get_option(SOL_SOCKET, SO_INCOMING_NAPI_ID)
+    }
 
-/// Socket handle wrapper.
-#[derive(Debug, Clone)]
-pub struct Handle {
-    /// Socket handle bind reactor
-    pub reactor: IoReactor,
-    /// Socket handle bind os fd.
-    pub fd: Arc<i32>,
-    /// If this socket is ipv4 familiy
-    pub ip_v4: bool,
-    /// Close status
-    pub closed: Arc<AtomicBool>,
+    /// Get bytes in the send queue not yet acknowledged by peer (`SIOCOUTQ`).
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn send_queue_len(&self) -> Result<usize> {
+        self.queue_len(TIOCOUTQ)
+    }
+
+    /// Get bytes received but not yet read (`SIOCINQ`).
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn recv_queue_len(&self) -> Result<usize> {
+        self.queue_len(FIONREAD)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn queue_len(&self, request: Ioctl) -> Result<usize> {
+        let mut len: c_int = 0;
+
+        if unsafe { ioctl(*self.fd, request, &mut len) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(len as usize)
+    }
+
+    /// Get kernel `TCP_INFO` statistics of this connection.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn tcp_info(&self) -> Result<tcp_info> {
+        let mut info: tcp_info = unsafe { std::mem::zeroed() };
+
+        let mut len = size_of::<tcp_info>() as socklen_t;
+
+        if unsafe {
+            getsockopt(
+                *self.fd,
+                IPPROTO_TCP,
+                TCP_INFO,
+                &mut info as *mut _ as *mut c_void,
+                &mut len,
+            )
+        } < 0
+        {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(info)
+    }
+
+    /// Get the original destination address of a connection redirected by netfilter
+    /// (`iptables REDIRECT`/`TPROXY`) via `SO_ORIGINAL_DST`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn original_dst(&self) -> Result<SocketAddr> {
+        let mut addr: sockaddr_storage = unsafe { std::mem::zeroed() };
+
+        let mut len = size_of::<sockaddr_storage>() as socklen_t;
+
+        let (level, name) = match self.ip_v4 {
+            true => (SOL_IP, SO_ORIGINAL_DST),
+            false => (SOL_IPV6, IP6T_SO_ORIGINAL_DST),
+        };
+
+        unsafe {
+            if getsockopt(
+                *self.fd,
+                level,
+                name,
+                &mut addr as *mut _ as *mut c_void,
+                &mut len,
+            ) < 0
+            {
+                return Err(Error::last_os_error());
+            }
+
+            OsSocketAddr::copy_from_raw(&mut addr as *mut _ as *mut sockaddr, len)
+                .into_addr()
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid original dst address"))
+        }
+    }
 }
 
-impl Handle {
-    pub fn to_raw_fd(&self) -> RawFd {
-        *self.fd as RawFd
+/// Set socket option `name` at `level` of raw `fd` to `value`.
+fn set_raw_option<T>(fd: RawFd, level: c_int, name: c_int, value: T) -> Result<()> {
+    if unsafe {
+        setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const _ as *const c_void,
+            size_of::<T>() as socklen_t,
+        )
+    } < 0
+    {
+        return Err(Error::last_os_error());
     }
+
+    Ok(())
 }
 
 impl Drop for Handle {
@@ -64,24 +990,9 @@ impl sys::Socket for Handle {
         Ok(())
     }
 
-    fn listen(fd: RawFd) -> Result<()> {
+    fn listen(fd: RawFd, backlog: i32) -> Result<()> {
         unsafe {
-            let on: c_int = 1;
-
-            let len = size_of::<c_int>() as u32;
-
-            if setsockopt(
-                fd,
-                SOL_SOCKET,
-                SO_REUSEADDR,
-                on.to_be_bytes().as_ptr() as *const libc::c_void,
-                len,
-            ) < 0
-            {
-                return Err(Error::last_os_error());
-            }
-
-            if listen(fd, SOMAXCONN as i32) < 0 {
+            if listen(fd, backlog) < 0 {
                 return Err(Error::last_os_error());
             } else {
                 Ok(())
@@ -126,7 +1037,9 @@ impl sys::Socket for Handle {
     }
 
     fn close(&mut self) {
-        log::trace!("close fd({})", *self.fd);
+        if self.reactor.trace_enabled() {
+            log::trace!("close fd({})", *self.fd);
+        }
         self.reactor.on_close_fd(*self.fd);
 
         unsafe {
@@ -155,13 +1068,19 @@ impl sys::Socket for Handle {
 
         if let Some(event) = self.reactor.poll_io_event(fd, EventName::Write)? {
             event.message?;
+        } else if self.reactor.refresh_waker(fd, EventName::Write, cx.waker()) {
+            // Woken by other futures (e.g. `join!`), connect is still in progress,
+            // calling connect again would fail with `EALREADY`.
+            return Poll::Pending;
         }
 
         let addr: OsSocketAddr = remote.into();
 
         let len = unsafe { connect(fd, addr.as_ptr(), addr.len()) };
 
-        log::trace!("socket({:?}) connect({})", fd, len);
+        if self.reactor.trace_enabled() {
+            log::trace!("socket({:?}) connect({})", fd, len);
+        }
 
         if len < 0 {
             let e = errno();
@@ -169,8 +1088,13 @@ impl sys::Socket for Handle {
             set_errno(e);
 
             if e.0 == libc::EAGAIN || e.0 == libc::EWOULDBLOCK || e.0 == libc::EINPROGRESS {
-                self.reactor
-                    .once(fd, EventName::Write, cx.waker().clone(), timeout);
+                self.reactor.once_labeled(
+                    fd,
+                    EventName::Write,
+                    cx.waker().clone(),
+                    timeout,
+                    "connect",
+                )?;
 
                 return Poll::Pending;
             } else if EISCONN == e.0 {
@@ -200,6 +1124,7 @@ impl ReactorHandle for Handle {
                 self.poll_write_datagram(cx, buff, remote, timeout)
             }
             WriteBuffer::Stream(buff) => self.poll_write_stream(cx, buff, timeout),
+            WriteBuffer::Vectored(bufs) => self.poll_write_vectored(cx, bufs, timeout),
         }
     }
 
@@ -231,12 +1156,28 @@ impl ReactorHandle for Handle {
             ReadBuffer::Datagram(buff, remote) => {
                 self.poll_read_datagram(cx, buff, remote, timeout)
             }
-            ReadBuffer::Stream(buff) => self.poll_read_stream(cx, buff, timeout),
+            ReadBuffer::Stream(buff) => self.poll_read_stream(cx, buff, 0, timeout),
+            ReadBuffer::Peek(buff) => self.poll_read_stream(cx, buff, MSG_PEEK, timeout),
+            ReadBuffer::Vectored(bufs) => self.poll_read_vectored(cx, bufs, timeout),
         }
     }
 }
 
 impl Handle {
+    /// Remove the waker of a pending accept, so a dropped accept future doesn't stay registered until the listener fires.
+    pub(crate) fn cancel_accept(&mut self) {
+        let fd = self.to_raw_fd();
+
+        self.reactor.remove_once(fd, EventName::Read);
+    }
+
+    /// Remove the waker and timeout of a pending stream read, e.g. when a read with deadline is dropped.
+    pub(crate) fn cancel_read(&mut self) {
+        let fd = self.to_raw_fd();
+
+        self.reactor.remove_once(fd, EventName::Read);
+    }
+
     fn poll_accept<'cx>(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -252,26 +1193,35 @@ impl Handle {
 
         let mut remote_buff = [0u8; size_of::<sockaddr_in6>()];
 
-        let mut len = remote_buff.len() as u32;
+        let mut addr_len = remote_buff.len() as socklen_t;
 
         unsafe {
-            let len = accept(
+            let conn = accept(
                 *self.fd,
                 remote_buff.as_mut_ptr() as *mut sockaddr,
-                &mut len as *mut u32,
+                &mut addr_len,
             );
 
-            if len != -1 {
+            if conn != -1 {
+                // Accepted socket does not inherit O_NONBLOCK from listener.
+                if let Err(err) = super::super::noblock(conn) {
+                    close(conn);
+
+                    return Poll::Ready(Err(err));
+                }
+
                 let addr = OsSocketAddr::copy_from_raw(
                     remote_buff.as_mut_ptr() as *mut sockaddr,
-                    len as socklen_t,
+                    addr_len,
                 );
 
                 *remote = addr.into_addr();
 
-                *conn_fd = Some(len);
+                *conn_fd = Some(conn);
 
-                log::trace!(target:"unix_net","fd({}) accept connection({}) from ({:?})", self.fd, len, remote);
+                if self.reactor.trace_enabled() {
+                    log::trace!(target:"unix_net","fd({}) accept connection({}) from ({:?})", self.fd, conn, remote);
+                }
 
                 return Poll::Ready(Ok(0));
             } else {
@@ -280,8 +1230,13 @@ impl Handle {
                 set_errno(e);
 
                 if e.0 == libc::EAGAIN || e.0 == libc::EWOULDBLOCK {
-                    self.reactor
-                        .once(fd, EventName::Read, cx.waker().clone(), timeout);
+                    self.reactor.once_labeled(
+                        fd,
+                        EventName::Read,
+                        cx.waker().clone(),
+                        timeout,
+                        "accept",
+                    )?;
 
                     return Poll::Pending;
                 } else {
@@ -304,54 +1259,345 @@ impl Handle {
             event.message?;
         }
 
-        let mut remote_buff = [0u8; size_of::<sockaddr_in6>()];
+        match recv_datagram(fd, buff) {
+            Ok((len, _, true)) => {
+                log::debug!("fd({}) datagram truncated to buffer({})", self.fd, len);
 
-        let mut len = remote_buff.len() as u32;
+                Poll::Ready(Err(Error::from_raw_os_error(EMSGSIZE)))
+            }
+            Ok((len, addr, false)) => {
+                *remote = addr;
 
-        let len = unsafe {
-            recvfrom(
-                *self.fd,
-                buff.as_ptr() as *mut c_void,
-                buff.len(),
-                0,
-                remote_buff.as_mut_ptr() as *mut sockaddr,
-                &mut len as *mut u32,
-            )
+                if self.reactor.trace_enabled() {
+                    log::trace!(target:"unix_net","fd({}) recvmsg({:?}) {}", self.fd, remote, len);
+                }
+
+                Poll::Ready(Ok(len))
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                self.reactor.once_labeled(
+                    fd,
+                    EventName::Read,
+                    cx.waker().clone(),
+                    timeout,
+                    "recv_from",
+                )?;
+
+                Poll::Pending
+            }
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+
+    /// Receive one datagram with `recvmsg`, along with the packet info control message if enabled
+    /// by [`set_recv_pktinfo`](Self::set_recv_pktinfo).
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) fn poll_recv_msg(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buff: &mut [u8],
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<Result<RecvInfo>> {
+        let fd = self.to_raw_fd();
+
+        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Read)? {
+            event.message?;
+        }
+
+        let mut remote_buff: sockaddr_storage = unsafe { std::mem::zeroed() };
+
+        let mut iov = iovec {
+            iov_base: buff.as_mut_ptr() as *mut c_void,
+            iov_len: buff.len(),
         };
 
+        // Room for one `in6_pktinfo` control message, u64 elements keep `cmsghdr` aligned.
+        let mut control = [0u64; 8];
+
+        let mut msg: msghdr = unsafe { std::mem::zeroed() };
+
+        msg.msg_name = &mut remote_buff as *mut _ as *mut c_void;
+        msg.msg_namelen = size_of::<sockaddr_storage>() as socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut c_void;
+        msg.msg_controllen = size_of::<[u64; 8]>() as _;
+
+        let len = unsafe { recvmsg(fd, &mut msg, 0) };
+
+        if len >= 0 && msg.msg_flags & MSG_TRUNC != 0 {
+            return Poll::Ready(Err(Error::from_raw_os_error(EMSGSIZE)));
+        }
+
         if len >= 0 {
-            let addr = unsafe {
+            let source = unsafe {
                 OsSocketAddr::copy_from_raw(
-                    remote_buff.as_mut_ptr() as *mut sockaddr,
-                    len as socklen_t,
+                    &mut remote_buff as *mut _ as *mut sockaddr,
+                    msg.msg_namelen,
                 )
+            }
+            .into_addr()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid datagram source address"))?;
+
+            let mut info = RecvInfo {
+                len: len as usize,
+                source,
+                dest_ip: None,
+                ifindex: None,
             };
 
-            *remote = addr.into_addr();
+            unsafe {
+                let mut cmsg = CMSG_FIRSTHDR(&msg);
+
+                while !cmsg.is_null() {
+                    match ((*cmsg).cmsg_level, (*cmsg).cmsg_type) {
+                        (IPPROTO_IP, IP_PKTINFO) => {
+                            let pktinfo =
+                                std::ptr::read_unaligned(CMSG_DATA(cmsg) as *const in_pktinfo);
+
+                            info.dest_ip = Some(std::net::IpAddr::V4(std::net::Ipv4Addr::from(
+                                u32::from_be(pktinfo.ipi_addr.s_addr),
+                            )));
+                            info.ifindex = Some(pktinfo.ipi_ifindex as u32);
+                        }
+                        (IPPROTO_IPV6, IPV6_PKTINFO) => {
+                            let pktinfo =
+                                std::ptr::read_unaligned(CMSG_DATA(cmsg) as *const in6_pktinfo);
+
+                            info.dest_ip = Some(std::net::IpAddr::V6(std::net::Ipv6Addr::from(
+                                pktinfo.ipi6_addr.s6_addr,
+                            )));
+                            info.ifindex = Some(pktinfo.ipi6_ifindex);
+                        }
+                        _ => {}
+                    }
+
+                    cmsg = CMSG_NXTHDR(&msg, cmsg);
+                }
+            }
 
-            log::trace!(target:"unix_net","fd({}) recvfrom({:?}) {}", self.fd, remote, len);
+            if self.reactor.trace_enabled() {
+                log::trace!(target:"unix_net","fd({}) recvmsg({:?}) {}", self.fd, info, len);
+            }
 
-            return Poll::Ready(Ok(len as usize));
+            Poll::Ready(Ok(info))
         } else {
             let e = errno();
 
             set_errno(e);
 
             if e.0 == libc::EAGAIN || e.0 == libc::EWOULDBLOCK {
-                self.reactor
-                    .once(fd, EventName::Read, cx.waker().clone(), timeout);
-
-                return Poll::Pending;
+                self.reactor.once_labeled(
+                    fd,
+                    EventName::Read,
+                    cx.waker().clone(),
+                    timeout,
+                    "recv_msg",
+                )?;
+
+                Poll::Pending
             } else {
+                Poll::Ready(Err(Error::from_raw_os_error(e.0)))
+            }
+        }
+    }
+
+    /// Receive up to `max` datagrams into `bufs`, returns the number received.
+    ///
+    /// Each buffer is filled up to its capacity and truncated to the datagram length,
+    /// the flag is set if the datagram was longer than the buffer.
+    /// Entries past the returned count are left with unspecified content.
+    /// Buffers without capacity fail with `InvalidInput`.
+    pub(crate) fn poll_recv_batch(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &mut [(Vec<u8>, SocketAddr, bool)],
+        max: usize,
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<Result<usize>> {
+        let fd = self.to_raw_fd();
+
+        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Read)? {
+            event.message?;
+        }
+
+        let count = max.min(bufs.len());
+
+        if count == 0 {
+            return Poll::Ready(Ok(0));
+        }
+
+        if bufs[..count].iter().any(|(buf, _, _)| buf.capacity() == 0) {
+            return Poll::Ready(Err(Error::new(
+                ErrorKind::InvalidInput,
+                "recv_batch buffer without capacity",
+            )));
+        }
+
+        for (buf, _, _) in &mut bufs[..count] {
+            buf.resize(buf.capacity(), 0);
+        }
+
+        match recv_batch(fd, &mut bufs[..count]) {
+            Ok(received) => {
+                if self.reactor.trace_enabled() {
+                    log::trace!(target:"unix_net","fd({}) recv_batch({}) {}", self.fd, count, received);
+                }
+
+                Poll::Ready(Ok(received))
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                self.reactor.once_labeled(
+                    fd,
+                    EventName::Read,
+                    cx.waker().clone(),
+                    timeout,
+                    "recv_batch",
+                )?;
+
+                Poll::Pending
+            }
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+
+    /// Send `packets` to their target addresses, returns the number of packets sent.
+    ///
+    /// A partial count means the socket buffer filled up, the next call waits until it is writable.
+    pub(crate) fn poll_send_batch(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        packets: &[(Vec<u8>, SocketAddr)],
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<Result<usize>> {
+        let fd = self.to_raw_fd();
+
+        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Write)? {
+            event.message?;
+        }
+
+        if packets.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        match send_batch(fd, packets) {
+            Ok(sent) => {
+                if self.reactor.trace_enabled() {
+                    log::trace!(target:"unix_net","fd({}) send_batch({}) {}", self.fd, packets.len(), sent);
+                }
+
+                Poll::Ready(Ok(sent))
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                self.reactor.once_labeled(
+                    fd,
+                    EventName::Write,
+                    cx.waker().clone(),
+                    timeout,
+                    "send_batch",
+                )?;
+
+                Poll::Pending
+            }
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+
+    /// Send each queued datagram back to its source, reusing one `msghdr` for the `recvmsg`/`sendmsg` pair.
+    ///
+    /// Only resolves with a receive error, otherwise stays pending after the queue is drained.
+    /// Datagrams longer than `buff`, or not accepted by a full send buffer, are dropped.
+    pub(crate) fn poll_reflect(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buff: &mut [u8],
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<Result<()>> {
+        // Datagrams reflected in one poll, before yielding to other tasks.
+        const BUDGET: usize = 1024;
+
+        let fd = self.to_raw_fd();
+
+        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Read)? {
+            event.message?;
+        }
+
+        let mut remote_buff: sockaddr_storage = unsafe { std::mem::zeroed() };
+
+        let mut iov = iovec {
+            iov_base: buff.as_mut_ptr() as *mut c_void,
+            iov_len: buff.len(),
+        };
+
+        let mut msg: msghdr = unsafe { std::mem::zeroed() };
+
+        msg.msg_name = &mut remote_buff as *mut _ as *mut c_void;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+
+        for _ in 0..BUDGET {
+            msg.msg_namelen = size_of::<sockaddr_storage>() as socklen_t;
+            // `iov` is only reached through `msg` from here on.
+            unsafe { (*msg.msg_iov).iov_len = buff.len() };
+
+            let len = unsafe { recvmsg(fd, &mut msg, 0) };
+
+            if len < 0 {
+                let e = errno();
+
+                set_errno(e);
+
+                if e.0 == libc::EAGAIN || e.0 == libc::EWOULDBLOCK {
+                    self.reactor.once_labeled(
+                        fd,
+                        EventName::Read,
+                        cx.waker().clone(),
+                        timeout,
+                        "reflect",
+                    )?;
+
+                    return Poll::Pending;
+                }
+
                 return Poll::Ready(Err(Error::from_raw_os_error(e.0)));
             }
+
+            if msg.msg_flags & MSG_TRUNC != 0 {
+                log::debug!(
+                    "fd({}) drop truncated datagram, buffer({})",
+                    self.fd,
+                    buff.len()
+                );
+
+                continue;
+            }
+
+            // `msg_name`/`msg_namelen` now hold the source address.
+            unsafe { (*msg.msg_iov).iov_len = len as usize };
+
+            if unsafe { sendmsg(fd, &msg, 0) } < 0 {
+                log::debug!(
+                    "fd({}) drop reflected datagram({}), {}",
+                    self.fd,
+                    len,
+                    Error::last_os_error()
+                );
+            } else if self.reactor.trace_enabled() {
+                log::trace!(target:"unix_net","fd({}) reflect {}", self.fd, len);
+            }
         }
+
+        cx.waker().wake_by_ref();
+
+        Poll::Pending
     }
 
+    /// Read stream socket with `recv` `flags`, e.g. `MSG_PEEK`.
     fn poll_read_stream<'cx>(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
         buff: &'cx mut [u8],
+        flags: c_int,
         timeout: Option<std::time::Duration>,
     ) -> std::task::Poll<Result<usize>> {
         let fd = self.to_raw_fd();
@@ -360,10 +1606,12 @@ impl Handle {
             event.message?;
         }
 
-        let len = unsafe { recv(*self.fd, buff.as_ptr() as *mut c_void, buff.len(), 0) };
+        let len = unsafe { recv(*self.fd, buff.as_ptr() as *mut c_void, buff.len(), flags) };
 
         if len >= 0 {
-            log::trace!(target:"unix_net","fd({}) recv {}", self.fd, len);
+            if self.reactor.trace_enabled() {
+                log::trace!(target:"unix_net","fd({}) recv({}) {}", self.fd, flags, len);
+            }
 
             return Poll::Ready(Ok(len as usize));
         } else {
@@ -372,8 +1620,13 @@ impl Handle {
             set_errno(e);
 
             if e.0 == libc::EAGAIN || e.0 == libc::EWOULDBLOCK {
-                self.reactor
-                    .once(fd, EventName::Read, cx.waker().clone(), timeout);
+                self.reactor.once_labeled(
+                    fd,
+                    EventName::Read,
+                    cx.waker().clone(),
+                    timeout,
+                    "read",
+                )?;
 
                 return Poll::Pending;
             } else {
@@ -382,6 +1635,54 @@ impl Handle {
         }
     }
 
+    fn poll_read_vectored<'cx>(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &'cx mut [IoSliceMut<'cx>],
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<Result<usize>> {
+        let fd = self.to_raw_fd();
+
+        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Read)? {
+            event.message?;
+        }
+
+        // `IoSliceMut` is ABI compatible with `iovec`.
+        let len = unsafe {
+            readv(
+                *self.fd,
+                bufs.as_ptr() as *const iovec,
+                bufs.len().min(MAX_IOV) as c_int,
+            )
+        };
+
+        if len >= 0 {
+            if self.reactor.trace_enabled() {
+                log::trace!(target:"unix_net","fd({}) readv({}) {}", self.fd, bufs.len(), len);
+            }
+
+            Poll::Ready(Ok(len as usize))
+        } else {
+            let e = errno();
+
+            set_errno(e);
+
+            if e.0 == libc::EAGAIN || e.0 == libc::EWOULDBLOCK {
+                self.reactor.once_labeled(
+                    fd,
+                    EventName::Read,
+                    cx.waker().clone(),
+                    timeout,
+                    "read_vectored",
+                )?;
+
+                Poll::Pending
+            } else {
+                Poll::Ready(Err(Error::from_raw_os_error(e.0)))
+            }
+        }
+    }
+
     fn poll_write_datagram<'cx>(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -409,7 +1710,9 @@ impl Handle {
         };
 
         if len >= 0 {
-            log::trace!(target:"unix_net","fd({}) sendto {}", self.fd, len);
+            if self.reactor.trace_enabled() {
+                log::trace!(target:"unix_net","fd({}) sendto {}", self.fd, len);
+            }
 
             return Poll::Ready(Ok(len as usize));
         } else {
@@ -418,8 +1721,13 @@ impl Handle {
             set_errno(e);
 
             if e.0 == libc::EAGAIN || e.0 == libc::EWOULDBLOCK {
-                self.reactor
-                    .once(fd, EventName::Write, cx.waker().clone(), timeout);
+                self.reactor.once_labeled(
+                    fd,
+                    EventName::Write,
+                    cx.waker().clone(),
+                    timeout,
+                    "send_to",
+                )?;
 
                 return Poll::Pending;
             } else {
@@ -443,7 +1751,9 @@ impl Handle {
         let len = unsafe { send(*self.fd, buff.as_ptr() as *mut c_void, buff.len(), 0) };
 
         if len >= 0 {
-            log::trace!(target:"unix_net","fd({}) send {}", self.fd, len);
+            if self.reactor.trace_enabled() {
+                log::trace!(target:"unix_net","fd({}) send {}", self.fd, len);
+            }
 
             return Poll::Ready(Ok(len as usize));
         } else {
@@ -452,8 +1762,13 @@ impl Handle {
             set_errno(e);
 
             if e.0 == libc::EAGAIN || e.0 == libc::EWOULDBLOCK {
-                self.reactor
-                    .once(fd, EventName::Write, cx.waker().clone(), timeout);
+                self.reactor.once_labeled(
+                    fd,
+                    EventName::Write,
+                    cx.waker().clone(),
+                    timeout,
+                    "write",
+                )?;
 
                 return Poll::Pending;
             } else {
@@ -461,4 +1776,52 @@ impl Handle {
             }
         }
     }
+
+    fn poll_write_vectored<'cx>(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &std::task::Context<'_>,
+        bufs: &'cx [IoSlice<'cx>],
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<Result<usize>> {
+        let fd = self.to_raw_fd();
+
+        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Write)? {
+            event.message?;
+        }
+
+        // `IoSlice` is ABI compatible with `iovec`.
+        let len = unsafe {
+            writev(
+                *self.fd,
+                bufs.as_ptr() as *const iovec,
+                bufs.len().min(MAX_IOV) as c_int,
+            )
+        };
+
+        if len >= 0 {
+            if self.reactor.trace_enabled() {
+                log::trace!(target:"unix_net","fd({}) writev({}) {}", self.fd, bufs.len(), len);
+            }
+
+            Poll::Ready(Ok(len as usize))
+        } else {
+            let e = errno();
+
+            set_errno(e);
+
+            if e.0 == libc::EAGAIN || e.0 == libc::EWOULDBLOCK {
+                self.reactor.once_labeled(
+                    fd,
+                    EventName::Write,
+                    cx.waker().clone(),
+                    timeout,
+                    "write_vectored",
+                )?;
+
+                Poll::Pending
+            } else {
+                Poll::Ready(Err(Error::from_raw_os_error(e.0)))
+            }
+        }
+    }
 }
@@ -15,18 +15,68 @@ use std::{
 use once_cell::sync::OnceCell;
 use os_socketaddr::OsSocketAddr;
 use winapi::{
-    shared::{guiddef::*, winerror::ERROR_IO_PENDING, ws2def::*},
-    um::{errhandlingapi::GetLastError, winsock2::*},
+    shared::{guiddef::*, minwindef::BOOL, winerror::ERROR_IO_PENDING, ws2def::*},
+    um::{errhandlingapi::GetLastError, ioapiset::CancelIoEx, winsock2::*},
     um::{minwinbase::OVERLAPPED, mswsock::*},
 };
 
 use crate::{
-    io::{EventMessage, EventName, IoReactor, RawFd, ReactorOverlapped},
+    io::{EventMessage, EventName, IoReactor, RawFd},
     ReactorHandle,
 };
 
 use super::sys::{self, ReadBuffer, Socket, WriteBuffer};
 
+/// `IP_TTL` of `ws2ipdef.h`.
+const IP_TTL: i32 = 4;
+
+/// `IPV6_UNICAST_HOPS` of `ws2ipdef.h`.
+const IPV6_UNICAST_HOPS: i32 = 4;
+
+/// `IP_DONTFRAGMENT` of `ws2ipdef.h`.
+const IP_DONTFRAGMENT: i32 = 14;
+
+/// `IPV6_DONTFRAG` of `ws2ipdef.h`.
+const IPV6_DONTFRAG: i32 = 14;
+
+/// `IPV6_V6ONLY` of `ws2ipdef.h`.
+const IPV6_V6ONLY: i32 = 27;
+
+/// `IP_MULTICAST_IF` of `ws2ipdef.h`.
+const IP_MULTICAST_IF: i32 = 9;
+
+/// `IP_MULTICAST_TTL` of `ws2ipdef.h`.
+const IP_MULTICAST_TTL: i32 = 10;
+
+/// `IP_MULTICAST_LOOP` of `ws2ipdef.h`.
+const IP_MULTICAST_LOOP: i32 = 11;
+
+/// `IP_ADD_MEMBERSHIP` of `ws2ipdef.h`.
+const IP_ADD_MEMBERSHIP: i32 = 12;
+
+/// `IP_DROP_MEMBERSHIP` of `ws2ipdef.h`.
+const IP_DROP_MEMBERSHIP: i32 = 13;
+
+/// `IPV6_ADD_MEMBERSHIP` of `ws2ipdef.h`.
+const IPV6_ADD_MEMBERSHIP: i32 = 12;
+
+/// `IPV6_DROP_MEMBERSHIP` of `ws2ipdef.h`.
+const IPV6_DROP_MEMBERSHIP: i32 = 13;
+
+/// `IP_MREQ` of `ws2ipdef.h`, addresses in network byte order.
+#[repr(C)]
+struct IpMreq {
+    imr_multiaddr: [u8; 4],
+    imr_interface: [u8; 4],
+}
+
+/// `IPV6_MREQ` of `ws2ipdef.h`.
+#[repr(C)]
+struct Ipv6Mreq {
+    ipv6mr_multiaddr: [u8; 16],
+    ipv6mr_interface: u32,
+}
+
 /// Socket handle wrapper.
 #[derive(Debug, Clone)]
 pub struct Handle {
@@ -44,6 +94,346 @@ impl Handle {
     pub fn to_raw_fd(&self) -> RawFd {
         *self.fd as RawFd
     }
+
+    /// Check listener options of socket `fd`, windows has no `SO_REUSEPORT`
+    /// (and `SO_REUSEADDR` allows port hijacking, so it is never set).
+    pub(crate) fn set_listener_options(_fd: RawFd, reuse_port: bool) -> Result<()> {
+        if reuse_port {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "SO_REUSEPORT is not supported on windows",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Set `IPV6_V6ONLY` of ipv6 socket `fd`, must be called before bind.
+    pub(crate) fn set_v6only(fd: RawFd, on: bool) -> Result<()> {
+        let value = on as BOOL;
+
+        if unsafe {
+            setsockopt(
+                fd as SOCKET,
+                IPPROTO_IPV6 as i32,
+                IPV6_V6ONLY,
+                &value as *const _ as *const i8,
+                size_of::<BOOL>() as i32,
+            )
+        } == SOCKET_ERROR
+        {
+            return Err(Error::from_raw_os_error(unsafe { WSAGetLastError() }));
+        }
+
+        Ok(())
+    }
+
+    /// Set socket option `name` at `level` to `value`.
+    pub(crate) fn set_option<T>(&self, level: i32, name: i32, value: T) -> Result<()> {
+        if unsafe {
+            setsockopt(
+                *self.fd,
+                level,
+                name,
+                &value as *const _ as *const i8,
+                size_of::<T>() as i32,
+            )
+        } == SOCKET_ERROR
+        {
+            return Err(Error::from_raw_os_error(unsafe { WSAGetLastError() }));
+        }
+
+        Ok(())
+    }
+
+    /// Get socket option `name` at `level`.
+    pub(crate) fn get_option<T: Default>(&self, level: i32, name: i32) -> Result<T> {
+        let mut value = T::default();
+
+        let mut len = size_of::<T>() as i32;
+
+        if unsafe {
+            getsockopt(
+                *self.fd,
+                level,
+                name,
+                &mut value as *mut _ as *mut i8,
+                &mut len,
+            )
+        } == SOCKET_ERROR
+        {
+            return Err(Error::from_raw_os_error(unsafe { WSAGetLastError() }));
+        }
+
+        Ok(value)
+    }
+
+    /// Get the local address this socket is bound to (`getsockname`).
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket_addr(getsockname, "invalid local address")
+    }
+
+    /// Get the remote address this socket is connected to (`getpeername`).
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.socket_addr(getpeername, "invalid peer address")
+    }
+
+    fn socket_addr(
+        &self,
+        query: unsafe extern "system" fn(SOCKET, *mut SOCKADDR, *mut i32) -> i32,
+        invalid: &'static str,
+    ) -> Result<SocketAddr> {
+        let mut addr: SOCKADDR_STORAGE = unsafe { std::mem::zeroed() };
+
+        let mut len = size_of::<SOCKADDR_STORAGE>() as i32;
+
+        unsafe {
+            if query(*self.fd, &mut addr as *mut _ as *mut SOCKADDR, &mut len) == SOCKET_ERROR {
+                return Err(Error::from_raw_os_error(WSAGetLastError()));
+            }
+
+            OsSocketAddr::copy_from_raw(&mut addr as *mut _ as *mut SOCKADDR, len)
+                .into_addr()
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, invalid))
+        }
+    }
+
+    /// Set the default peer of this datagram socket (`connect`), which completes without waiting.
+    ///
+    /// Datagrams from other addresses are dropped, and icmp errors are reported on later calls.
+    pub fn connect_datagram(&self, peer: SocketAddr) -> Result<()> {
+        let addr: OsSocketAddr = peer.into();
+
+        if unsafe {
+            connect(
+                *self.fd,
+                addr.as_ptr() as *const SOCKADDR,
+                addr.len() as i32,
+            )
+        } == SOCKET_ERROR
+        {
+            return Err(Error::from_raw_os_error(unsafe { WSAGetLastError() }));
+        }
+
+        Ok(())
+    }
+
+    /// Shut down the read, write or both halves of this connection, the socket stays open.
+    pub fn shutdown(&self, how: std::net::Shutdown) -> Result<()> {
+        let how = match how {
+            std::net::Shutdown::Read => SD_RECEIVE,
+            std::net::Shutdown::Write => SD_SEND,
+            std::net::Shutdown::Both => SD_BOTH,
+        };
+
+        if unsafe { shutdown(*self.fd, how) } == SOCKET_ERROR {
+            return Err(Error::from_raw_os_error(unsafe { WSAGetLastError() }));
+        }
+
+        Ok(())
+    }
+
+    /// Check if the socket is writable with a zero timeout `WSAPoll`, without registering a waker.
+    ///
+    /// Errors and hangups count as writable, the following write reports them.
+    pub fn poll_write_ready_nonblocking(&self) -> Poll<Result<()>> {
+        let mut pollfd = WSAPOLLFD {
+            fd: *self.fd,
+            events: POLLWRNORM,
+            revents: 0,
+        };
+
+        if unsafe { WSAPoll(&mut pollfd, 1, 0) } == SOCKET_ERROR {
+            return Poll::Ready(Err(Error::from_raw_os_error(unsafe { WSAGetLastError() })));
+        }
+
+        if pollfd.revents & (POLLWRNORM | POLLERR | POLLHUP) != 0 {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Enable or disable `TCP_NODELAY` (nagle algorithm off) of this socket.
+    pub fn set_nodelay(&self, on: bool) -> Result<()> {
+        self.set_option(IPPROTO_TCP as i32, TCP_NODELAY, on as BOOL)
+    }
+
+    /// Get `TCP_NODELAY` of this socket.
+    pub fn nodelay(&self) -> Result<bool> {
+        Ok(self.get_option::<BOOL>(IPPROTO_TCP as i32, TCP_NODELAY)? != 0)
+    }
+
+    /// Set `IP_TTL` (ipv4) or `IPV6_UNICAST_HOPS` (ipv6) of this socket.
+    pub fn set_ttl(&self, ttl: u32) -> Result<()> {
+        if self.ip_v4 {
+            self.set_option(IPPROTO_IP as i32, IP_TTL, ttl as i32)
+        } else {
+            self.set_option(IPPROTO_IPV6 as i32, IPV6_UNICAST_HOPS, ttl as i32)
+        }
+    }
+
+    /// Get `IP_TTL` (ipv4) or `IPV6_UNICAST_HOPS` (ipv6) of this socket.
+    pub fn ttl(&self) -> Result<u32> {
+        let ttl = if self.ip_v4 {
+            self.get_option::<i32>(IPPROTO_IP as i32, IP_TTL)?
+        } else {
+            self.get_option::<i32>(IPPROTO_IPV6 as i32, IPV6_UNICAST_HOPS)?
+        };
+
+        Ok(ttl as u32)
+    }
+
+    /// Enable or disable `SO_BROADCAST`, sending to broadcast addresses fails without it.
+    pub fn set_broadcast(&self, on: bool) -> Result<()> {
+        self.set_option(
+            winapi::shared::ws2def::SOL_SOCKET,
+            winapi::shared::ws2def::SO_BROADCAST,
+            on as BOOL,
+        )
+    }
+
+    /// Get `SO_BROADCAST` of this socket.
+    pub fn broadcast(&self) -> Result<bool> {
+        Ok(self.get_option::<BOOL>(
+            winapi::shared::ws2def::SOL_SOCKET,
+            winapi::shared::ws2def::SO_BROADCAST,
+        )? != 0)
+    }
+
+    /// Join ipv4 multicast group `multiaddr` on the interface with address `interface`
+    /// (`0.0.0.0` lets the system choose).
+    pub fn join_multicast_v4(
+        &self,
+        multiaddr: std::net::Ipv4Addr,
+        interface: std::net::Ipv4Addr,
+    ) -> Result<()> {
+        self.set_option(
+            IPPROTO_IP as i32,
+            IP_ADD_MEMBERSHIP,
+            IpMreq {
+                imr_multiaddr: multiaddr.octets(),
+                imr_interface: interface.octets(),
+            },
+        )
+    }
+
+    /// Leave ipv4 multicast group joined by [`join_multicast_v4`](Self::join_multicast_v4).
+    pub fn leave_multicast_v4(
+        &self,
+        multiaddr: std::net::Ipv4Addr,
+        interface: std::net::Ipv4Addr,
+    ) -> Result<()> {
+        self.set_option(
+            IPPROTO_IP as i32,
+            IP_DROP_MEMBERSHIP,
+            IpMreq {
+                imr_multiaddr: multiaddr.octets(),
+                imr_interface: interface.octets(),
+            },
+        )
+    }
+
+    /// Join ipv6 multicast group `multiaddr` on the interface with index `interface` (0 lets the system choose).
+    pub fn join_multicast_v6(&self, multiaddr: std::net::Ipv6Addr, interface: u32) -> Result<()> {
+        self.set_option(
+            IPPROTO_IPV6 as i32,
+            IPV6_ADD_MEMBERSHIP,
+            Ipv6Mreq {
+                ipv6mr_multiaddr: multiaddr.octets(),
+                ipv6mr_interface: interface,
+            },
+        )
+    }
+
+    /// Leave ipv6 multicast group joined by [`join_multicast_v6`](Self::join_multicast_v6).
+    pub fn leave_multicast_v6(&self, multiaddr: std::net::Ipv6Addr, interface: u32) -> Result<()> {
+        self.set_option(
+            IPPROTO_IPV6 as i32,
+            IPV6_DROP_MEMBERSHIP,
+            Ipv6Mreq {
+                ipv6mr_multiaddr: multiaddr.octets(),
+                ipv6mr_interface: interface,
+            },
+        )
+    }
+
+    /// Set `IP_MULTICAST_LOOP`, whether multicast datagrams sent by this socket are looped back to local members.
+    pub fn set_multicast_loop_v4(&self, on: bool) -> Result<()> {
+        self.set_option(IPPROTO_IP as i32, IP_MULTICAST_LOOP, on as u32)
+    }
+
+    /// Get `IP_MULTICAST_LOOP` of this socket.
+    pub fn multicast_loop_v4(&self) -> Result<bool> {
+        Ok(self.get_option::<u32>(IPPROTO_IP as i32, IP_MULTICAST_LOOP)? != 0)
+    }
+
+    /// Set `IP_MULTICAST_TTL` of outgoing multicast datagrams, default 1 (stay in the local network).
+    pub fn set_multicast_ttl_v4(&self, ttl: u32) -> Result<()> {
+        self.set_option(IPPROTO_IP as i32, IP_MULTICAST_TTL, ttl)
+    }
+
+    /// Get `IP_MULTICAST_TTL` of this socket.
+    pub fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.get_option::<u32>(IPPROTO_IP as i32, IP_MULTICAST_TTL)
+    }
+
+    /// Set `IP_MULTICAST_IF`, the interface (by address) outgoing multicast datagrams are sent on.
+    pub fn set_multicast_if_v4(&self, interface: std::net::Ipv4Addr) -> Result<()> {
+        self.set_option(IPPROTO_IP as i32, IP_MULTICAST_IF, interface.octets())
+    }
+
+    /// Set `SO_SNDBUF` of this socket.
+    pub fn set_send_buffer_size(&self, size: usize) -> Result<()> {
+        self.set_option(
+            winapi::shared::ws2def::SOL_SOCKET,
+            winapi::shared::ws2def::SO_SNDBUF,
+            size.min(i32::MAX as usize) as i32,
+        )
+    }
+
+    /// Get `SO_SNDBUF` of this socket, as reported by the system.
+    pub fn send_buffer_size(&self) -> Result<usize> {
+        Ok(self.get_option::<i32>(
+            winapi::shared::ws2def::SOL_SOCKET,
+            winapi::shared::ws2def::SO_SNDBUF,
+        )? as usize)
+    }
+
+    /// Set `SO_RCVBUF` of this socket.
+    pub fn set_recv_buffer_size(&self, size: usize) -> Result<()> {
+        self.set_option(
+            winapi::shared::ws2def::SOL_SOCKET,
+            winapi::shared::ws2def::SO_RCVBUF,
+            size.min(i32::MAX as usize) as i32,
+        )
+    }
+
+    /// Get `SO_RCVBUF` of this socket, as reported by the system.
+    pub fn recv_buffer_size(&self) -> Result<usize> {
+        Ok(self.get_option::<i32>(
+            winapi::shared::ws2def::SOL_SOCKET,
+            winapi::shared::ws2def::SO_RCVBUF,
+        )? as usize)
+    }
+
+    /// Set don't fragment (DF) bit of outgoing packets, oversized datagrams fail with `WSAEMSGSIZE`.
+    pub fn set_dont_fragment(&self, on: bool) -> Result<()> {
+        if self.ip_v4 {
+            self.set_option(IPPROTO_IP as i32, IP_DONTFRAGMENT, on as BOOL)
+        } else {
+            self.set_option(IPPROTO_IPV6 as i32, IPV6_DONTFRAG, on as BOOL)
+        }
+    }
+
+    /// Get don't fragment (DF) bit setting of this socket.
+    pub fn dont_fragment(&self) -> Result<bool> {
+        if self.ip_v4 {
+            Ok(self.get_option::<BOOL>(IPPROTO_IP as i32, IP_DONTFRAGMENT)? != 0)
+        } else {
+            Ok(self.get_option::<BOOL>(IPPROTO_IPV6 as i32, IPV6_DONTFRAG)? != 0)
+        }
+    }
 }
 
 impl Drop for Handle {
@@ -68,9 +458,9 @@ impl sys::Socket for Handle {
         Ok(())
     }
 
-    fn listen(fd: RawFd) -> Result<()> {
+    fn listen(fd: RawFd, backlog: i32) -> Result<()> {
         unsafe {
-            if listen(fd as usize, SOMAXCONN as i32) < 0 {
+            if listen(fd as usize, backlog) < 0 {
                 return Err(Error::last_os_error());
             } else {
                 Ok(())
@@ -129,10 +519,16 @@ impl sys::Socket for Handle {
     }
 
     fn close(&mut self) {
-        log::debug!("close socket({:?})", self.to_raw_fd());
+        if self.reactor.trace_enabled() {
+            log::debug!("close socket({:?})", self.to_raw_fd());
+        }
         self.reactor.on_close_fd(self.to_raw_fd());
 
         unsafe {
+            // Cancel in-flight overlapped operations, their completions are dequeued with `STATUS_CANCELLED`
+            // and dropped by poller, so a reused socket value never receives stale events.
+            CancelIoEx(self.to_raw_fd(), null_mut());
+
             closesocket(*self.fd);
         }
     }
@@ -173,17 +569,35 @@ impl sys::Socket for Handle {
                     panic!("Inner error")
                 }
             }
+        } else if self
+            .reactor
+            .refresh_waker(fd, EventName::Connect, cx.waker())
+        {
+            // `ConnectEx` is still in flight, don't start another one.
+            return Poll::Pending;
         }
 
-        let overlapped = ReactorOverlapped::new_raw(fd, EventName::Connect);
+        let overlapped = self
+            .reactor
+            .overlapped_pool()
+            .acquire(fd, EventName::Connect);
 
         #[allow(non_snake_case)]
         let ConnectEx = self.get_connect_ex()?.unwrap();
 
         let addr: OsSocketAddr = remote.into();
 
-        self.reactor
-            .once(fd, EventName::Connect, cx.waker().clone(), timeout);
+        if let Err(err) = self.reactor.once_labeled(
+            fd,
+            EventName::Connect,
+            cx.waker().clone(),
+            timeout,
+            "connect",
+        ) {
+            unsafe { self.reactor.overlapped_pool().release(overlapped) };
+
+            return Poll::Ready(Err(err));
+        }
 
         let ret = unsafe {
             ConnectEx(
@@ -197,7 +611,9 @@ impl sys::Socket for Handle {
             )
         };
 
-        log::trace!("socket({:?}) connect({})", fd, ret);
+        if self.reactor.trace_enabled() {
+            log::trace!("socket({:?}) connect({})", fd, ret);
+        }
 
         if ret > 0 {
             // obtain point ownership
@@ -208,14 +624,21 @@ impl sys::Socket for Handle {
 
         // This operation will completing Asynchronously
         if unsafe { GetLastError() } == ERROR_IO_PENDING {
-            log::trace!("socket({:?}) connect asynchronously", fd);
+            if self.reactor.trace_enabled() {
+                log::trace!("socket({:?}) connect asynchronously", fd);
+            }
 
             return Poll::Pending;
         }
 
+        let err = Error::last_os_error();
+
         self.reactor.remove_once(fd, EventName::Connect);
 
-        return Poll::Ready(Err(Error::last_os_error()));
+        // Release overlapped
+        unsafe { self.reactor.overlapped_pool().release(overlapped) };
+
+        return Poll::Ready(Err(err));
     }
 }
 
@@ -235,6 +658,7 @@ impl ReactorHandle for Handle {
                 self.poll_write_datagram(cx, buff, remote, timeout)
             }
             WriteBuffer::Stream(buff) => self.poll_write_stream(cx, buff, timeout),
+            WriteBuffer::Vectored(bufs) => self.poll_write_vectored(cx, bufs, timeout),
         }
     }
 
@@ -266,7 +690,9 @@ impl ReactorHandle for Handle {
             ReadBuffer::Datagram(buff, remote) => {
                 self.poll_read_datagram(cx, buff, remote, timeout)
             }
-            ReadBuffer::Stream(buff) => self.poll_read_stream(cx, buff, timeout),
+            ReadBuffer::Stream(buff) => self.poll_read_stream(cx, buff, 0, timeout),
+            ReadBuffer::Peek(buff) => self.poll_read_stream(cx, buff, MSG_PEEK as u32, timeout),
+            ReadBuffer::Vectored(bufs) => self.poll_read_vectored(cx, bufs, timeout),
         }
     }
 }
@@ -298,6 +724,25 @@ impl Handle {
             Ok(transmute(connectex))
         })
     }
+
+    /// Remove the waker of a pending accept, so a dropped accept future doesn't stay registered until the listener fires.
+    ///
+    /// The in-flight `AcceptEx` is not cancelled, its connection is returned by the next accept.
+    pub(crate) fn cancel_accept(&mut self) {
+        let fd = self.to_raw_fd();
+
+        self.reactor.remove_once(fd, EventName::Accept);
+    }
+
+    /// Remove the waker and timeout of a pending stream read, e.g. when a read with deadline is dropped.
+    ///
+    /// The in-flight `WSARecv` is not cancelled.
+    pub(crate) fn cancel_read(&mut self) {
+        let fd = self.to_raw_fd();
+
+        self.reactor.remove_once(fd, EventName::Read);
+    }
+
     fn poll_accept<'cx>(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -307,7 +752,9 @@ impl Handle {
     ) -> std::task::Poll<Result<usize>> {
         let fd = self.to_raw_fd();
 
-        log::debug!("poll_accept({:?})", fd);
+        if self.reactor.trace_enabled() {
+            log::debug!("poll_accept({:?})", fd);
+        }
 
         if let Some(event) = self.reactor.poll_io_event(fd, EventName::Accept)? {
             match event.message? {
@@ -315,7 +762,9 @@ impl Handle {
                     *remote = addr;
                     *conn_fd = Some(remote_fd);
 
-                    log::debug!("acceptor({:?}) accept({:?})", fd, remote_fd);
+                    if self.reactor.trace_enabled() {
+                        log::debug!("acceptor({:?}) accept({:?})", fd, remote_fd);
+                    }
 
                     return Poll::Ready(Ok(0));
                 }
@@ -325,16 +774,30 @@ impl Handle {
             }
         }
 
-        log::debug!("poll_accept({:?}) poll_io_event", fd);
+        if self.reactor.trace_enabled() {
+            log::debug!("poll_accept({:?}) poll_io_event", fd);
+        }
 
         let accept_socket = Self::tcp(self.ip_v4)?;
 
-        let overlapped = ReactorOverlapped::new_raw(fd, EventName::Accept);
+        let overlapped = self
+            .reactor
+            .overlapped_pool()
+            .acquire(fd, EventName::Accept);
 
         let mut bytes_received = 0u32;
 
-        self.reactor
-            .once(fd, EventName::Accept, cx.waker().clone(), timeout);
+        if let Err(err) =
+            self.reactor
+                .once_labeled(fd, EventName::Accept, cx.waker().clone(), timeout, "accept")
+        {
+            unsafe {
+                closesocket(accept_socket as usize);
+                self.reactor.overlapped_pool().release(overlapped);
+            }
+
+            return Poll::Ready(Err(err));
+        }
 
         unsafe {
             (*overlapped).accept_fd = accept_socket;
@@ -350,7 +813,9 @@ impl Handle {
                 overlapped as *mut OVERLAPPED,
             );
 
-            log::trace!("socket({:?}) accept({})", fd, ret);
+            if self.reactor.trace_enabled() {
+                log::trace!("socket({:?}) accept({})", fd, ret);
+            }
 
             if ret > 0 {
                 // obtain point ownership
@@ -372,7 +837,9 @@ impl Handle {
 
                 // This operation will completing Asynchronously
                 if e == ERROR_IO_PENDING as i32 {
-                    log::trace!("socket({:?}) accept asynchronously", fd);
+                    if self.reactor.trace_enabled() {
+                        log::trace!("socket({:?}) accept asynchronously", fd);
+                    }
 
                     return Poll::Pending;
                 }
@@ -382,13 +849,77 @@ impl Handle {
                 self.reactor.remove_once(fd, EventName::Accept);
 
                 // Release overlapped
-                let _: Box<ReactorOverlapped> = overlapped.into();
+                self.reactor.overlapped_pool().release(overlapped);
 
                 return Poll::Ready(Err(Error::last_os_error()));
             }
         }
     }
 
+    /// Receive one datagram into the first of `bufs`, windows has no batch receive.
+    ///
+    /// The buffer is filled up to its capacity and truncated to the datagram length.
+    /// A buffer without capacity fails with `InvalidInput`, a datagram longer than the buffer with `WSAEMSGSIZE`.
+    pub(crate) fn poll_recv_batch(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &mut [(Vec<u8>, SocketAddr, bool)],
+        max: usize,
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<Result<usize>> {
+        if max == 0 || bufs.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let (buf, remote, truncated) = &mut bufs[0];
+
+        if buf.capacity() == 0 {
+            return Poll::Ready(Err(Error::new(
+                ErrorKind::InvalidInput,
+                "recv_batch buffer without capacity",
+            )));
+        }
+
+        // Never reallocates, the buffer stays valid for the pending overlapped receive.
+        buf.resize(buf.capacity(), 0);
+
+        let mut addr = None;
+
+        match self.poll_read_datagram(cx, buf, &mut addr, timeout) {
+            Poll::Ready(Ok(len)) => {
+                buf.truncate(len);
+
+                *remote = addr.ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidData, "invalid datagram source address")
+                })?;
+
+                *truncated = false;
+
+                Poll::Ready(Ok(1))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Send the first of `packets`, windows has no batch send.
+    pub(crate) fn poll_send_batch(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        packets: &[(Vec<u8>, SocketAddr)],
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<Result<usize>> {
+        let Some((buf, remote)) = packets.first() else {
+            return Poll::Ready(Ok(0));
+        };
+
+        match self.poll_write_datagram(cx, buf, remote, timeout) {
+            Poll::Ready(Ok(_)) => Poll::Ready(Ok(1)),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
     fn poll_read_datagram<'cx>(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -411,10 +942,22 @@ impl Handle {
             }
         }
 
-        let overlapped = ReactorOverlapped::new_raw(fd, EventName::RecvFrom);
-
-        self.reactor
-            .once(fd, EventName::RecvFrom, cx.waker().clone(), timeout);
+        let overlapped = self
+            .reactor
+            .overlapped_pool()
+            .acquire(fd, EventName::RecvFrom);
+
+        if let Err(err) = self.reactor.once_labeled(
+            fd,
+            EventName::RecvFrom,
+            cx.waker().clone(),
+            timeout,
+            "recv_from",
+        ) {
+            unsafe { self.reactor.overlapped_pool().release(overlapped) };
+
+            return Poll::Ready(Err(err));
+        }
 
         unsafe {
             (*overlapped).buff[0].buf = buff.as_mut_ptr() as *mut i8;
@@ -450,17 +993,19 @@ impl Handle {
                 self.reactor.remove_once(fd, EventName::RecvFrom);
 
                 // Release overlapped
-                let _: Box<ReactorOverlapped> = overlapped.into();
+                self.reactor.overlapped_pool().release(overlapped);
 
                 return Poll::Ready(Err(Error::last_os_error()));
             }
         }
     }
 
+    /// Read stream socket with `WSARecv` `flags`, e.g. `MSG_PEEK`.
     fn poll_read_stream<'cx>(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
         buff: &'cx mut [u8],
+        flags: u32,
         timeout: Option<std::time::Duration>,
     ) -> std::task::Poll<Result<usize>> {
         let fd = self.to_raw_fd();
@@ -476,14 +1021,22 @@ impl Handle {
             }
         }
 
-        let overlapped = ReactorOverlapped::new_raw(fd, EventName::Read);
+        let overlapped = self.reactor.overlapped_pool().acquire(fd, EventName::Read);
 
-        log::trace!("socket({:?}) recv({})", fd, buff.len(),);
+        if self.reactor.trace_enabled() {
+            log::trace!("socket({:?}) recv({})", fd, buff.len(),);
+        }
 
-        let mut flag = 0u32;
+        let mut flag = flags;
+
+        if let Err(err) =
+            self.reactor
+                .once_labeled(fd, EventName::Read, cx.waker().clone(), timeout, "read")
+        {
+            unsafe { self.reactor.overlapped_pool().release(overlapped) };
 
-        self.reactor
-            .once(fd, EventName::Read, cx.waker().clone(), timeout);
+            return Poll::Ready(Err(err));
+        }
 
         unsafe {
             (*overlapped).buff[0].buf = buff.as_ptr() as *mut i8;
@@ -502,7 +1055,91 @@ impl Handle {
                 None,
             );
 
-            log::trace!("socket({:?}) recv({}) result({})", fd, buff.len(), ret);
+            if self.reactor.trace_enabled() {
+                log::trace!("socket({:?}) recv({}) result({})", fd, buff.len(), ret);
+            }
+
+            //  operation has completed immediately
+            if ret == 0 {
+                return Poll::Pending;
+            } else {
+                let e = WSAGetLastError();
+
+                if WSA_IO_PENDING == e {
+                    return Poll::Pending;
+                }
+
+                self.reactor.remove_once(fd, EventName::Read);
+
+                // Release overlapped
+                self.reactor.overlapped_pool().release(overlapped);
+
+                return Poll::Ready(Err(Error::last_os_error()));
+            }
+        }
+    }
+
+    fn poll_read_vectored<'cx>(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &'cx mut [IoSliceMut<'cx>],
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<Result<usize>> {
+        let fd = self.to_raw_fd();
+
+        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Read)? {
+            match event.message? {
+                EventMessage::Read(len) => {
+                    return Poll::Ready(Ok(len));
+                }
+                _ => {
+                    panic!("Inner error")
+                }
+            }
+        }
+
+        let overlapped = self.reactor.overlapped_pool().acquire(fd, EventName::Read);
+
+        if self.reactor.trace_enabled() {
+            log::trace!("socket({:?}) recv vectored({})", fd, bufs.len());
+        }
+
+        let mut flag = 0u32;
+
+        if let Err(err) = self.reactor.once_labeled(
+            fd,
+            EventName::Read,
+            cx.waker().clone(),
+            timeout,
+            "read_vectored",
+        ) {
+            unsafe { self.reactor.overlapped_pool().release(overlapped) };
+
+            return Poll::Ready(Err(err));
+        }
+
+        unsafe {
+            let mut bytes_received = 0u32;
+
+            // `IoSliceMut` is ABI compatible with `WSABUF`, the array is only read during this call.
+            let ret = WSARecv(
+                fd as usize,
+                bufs.as_mut_ptr() as *mut WSABUF,
+                bufs.len() as u32,
+                &mut bytes_received,
+                &mut flag,
+                overlapped as *mut OVERLAPPED,
+                None,
+            );
+
+            if self.reactor.trace_enabled() {
+                log::trace!(
+                    "socket({:?}) recv vectored({}) result({})",
+                    fd,
+                    bufs.len(),
+                    ret
+                );
+            }
 
             //  operation has completed immediately
             if ret == 0 {
@@ -517,7 +1154,7 @@ impl Handle {
                 self.reactor.remove_once(fd, EventName::Read);
 
                 // Release overlapped
-                let _: Box<ReactorOverlapped> = overlapped.into();
+                self.reactor.overlapped_pool().release(overlapped);
 
                 return Poll::Ready(Err(Error::last_os_error()));
             }
@@ -544,7 +1181,10 @@ impl Handle {
             }
         }
 
-        let overlapped = ReactorOverlapped::new_raw(fd, EventName::SendTo);
+        let overlapped = self
+            .reactor
+            .overlapped_pool()
+            .acquire(fd, EventName::SendTo);
 
         let addr = OsSocketAddr::from(remote.clone());
 
@@ -553,16 +1193,25 @@ impl Handle {
 
             (*overlapped).buff[0].len = buff.len() as u32;
 
-            let mut bytes_received = 0u32;
+            let mut bytes_sent = 0u32;
 
-            self.reactor
-                .once(fd, EventName::SendTo, cx.waker().clone(), timeout);
+            if let Err(err) = self.reactor.once_labeled(
+                fd,
+                EventName::SendTo,
+                cx.waker().clone(),
+                timeout,
+                "send_to",
+            ) {
+                self.reactor.overlapped_pool().release(overlapped);
+
+                return Poll::Ready(Err(err));
+            }
 
             let ret = WSASendTo(
                 fd as usize,
                 (*overlapped).buff.as_mut_ptr() as *mut WSABUF,
                 1,
-                &mut bytes_received,
+                &mut bytes_sent,
                 0,
                 addr.as_ptr() as *mut SOCKADDR,
                 addr.len(),
@@ -570,7 +1219,7 @@ impl Handle {
                 None,
             );
 
-            //  operation has completed immediately
+            // Completed immediately, the queued completion reports the bytes sent, see `poll_write_stream`.
             if ret == 0 {
                 return Poll::Pending;
             } else {
@@ -583,7 +1232,7 @@ impl Handle {
                 self.reactor.remove_once(fd, EventName::SendTo);
 
                 // Release overlapped
-                let _: Box<ReactorOverlapped> = overlapped.into();
+                self.reactor.overlapped_pool().release(overlapped);
 
                 return Poll::Ready(Err(Error::last_os_error()));
             }
@@ -609,33 +1258,128 @@ impl Handle {
             }
         }
 
-        let overlapped = ReactorOverlapped::new_raw(fd, EventName::Write);
+        let overlapped = self.reactor.overlapped_pool().acquire(fd, EventName::Write);
 
-        log::trace!("socket({:?}) send({})", fd, buff.len());
+        if self.reactor.trace_enabled() {
+            log::trace!("socket({:?}) send({})", fd, buff.len());
+        }
 
         unsafe {
             (*overlapped).buff[0].buf = buff.as_ptr() as *mut i8;
 
             (*overlapped).buff[0].len = buff.len() as u32;
 
-            let mut bytes_received = 0u32;
+            let mut bytes_sent = 0u32;
 
-            self.reactor
-                .once(fd, EventName::Write, cx.waker().clone(), timeout);
+            if let Err(err) = self.reactor.once_labeled(
+                fd,
+                EventName::Write,
+                cx.waker().clone(),
+                timeout,
+                "write",
+            ) {
+                self.reactor.overlapped_pool().release(overlapped);
+
+                return Poll::Ready(Err(err));
+            }
 
             let ret = WSASend(
                 fd as usize,
                 (*overlapped).buff.as_mut_ptr() as *mut WSABUF,
                 1,
-                &mut bytes_received,
+                &mut bytes_sent,
                 0,
                 overlapped as *mut OVERLAPPED,
                 None,
             );
 
-            log::trace!("socket({:?}) send({}) result({})", fd, buff.len(), ret);
+            if self.reactor.trace_enabled() {
+                log::trace!("socket({:?}) send({}) result({})", fd, buff.len(), ret);
+            }
 
-            //  operation has completed immediately
+            // Completed immediately, the completion is still queued to iocp (`FILE_SKIP_COMPLETION_PORT_ON_SUCCESS`
+            // is never set), and reports the bytes actually sent, which may be less than requested.
+            // `bytes_sent` is not used, so both cases resolve through the same event.
+            if ret == 0 {
+                return Poll::Pending;
+            } else {
+                let e = WSAGetLastError();
+
+                if WSA_IO_PENDING == e {
+                    return Poll::Pending;
+                }
+
+                self.reactor.remove_once(fd, EventName::Write);
+
+                // Release overlapped
+                self.reactor.overlapped_pool().release(overlapped);
+
+                return Poll::Ready(Err(Error::last_os_error()));
+            }
+        }
+    }
+
+    fn poll_write_vectored<'cx>(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &std::task::Context<'_>,
+        bufs: &'cx [IoSlice<'cx>],
+        timeout: Option<std::time::Duration>,
+    ) -> std::task::Poll<Result<usize>> {
+        let fd = self.to_raw_fd();
+
+        if let Some(event) = self.reactor.poll_io_event(fd, EventName::Write)? {
+            match event.message? {
+                EventMessage::Write(len) => {
+                    return Poll::Ready(Ok(len));
+                }
+                _ => {
+                    panic!("Inner error")
+                }
+            }
+        }
+
+        let overlapped = self.reactor.overlapped_pool().acquire(fd, EventName::Write);
+
+        if self.reactor.trace_enabled() {
+            log::trace!("socket({:?}) send vectored({})", fd, bufs.len());
+        }
+
+        unsafe {
+            let mut bytes_sent = 0u32;
+
+            if let Err(err) = self.reactor.once_labeled(
+                fd,
+                EventName::Write,
+                cx.waker().clone(),
+                timeout,
+                "write_vectored",
+            ) {
+                self.reactor.overlapped_pool().release(overlapped);
+
+                return Poll::Ready(Err(err));
+            }
+
+            // `IoSlice` is ABI compatible with `WSABUF`, the array is only read during this call.
+            let ret = WSASend(
+                fd as usize,
+                bufs.as_ptr() as *mut WSABUF,
+                bufs.len() as u32,
+                &mut bytes_sent,
+                0,
+                overlapped as *mut OVERLAPPED,
+                None,
+            );
+
+            if self.reactor.trace_enabled() {
+                log::trace!(
+                    "socket({:?}) send vectored({}) result({})",
+                    fd,
+                    bufs.len(),
+                    ret
+                );
+            }
+
+            // Completed immediately, the queued completion reports the bytes sent, see `poll_write_stream`.
             if ret == 0 {
                 return Poll::Pending;
             } else {
@@ -648,7 +1392,7 @@ impl Handle {
                 self.reactor.remove_once(fd, EventName::Write);
 
                 // Release overlapped
-                let _: Box<ReactorOverlapped> = overlapped.into();
+                self.reactor.overlapped_pool().release(overlapped);
 
                 return Poll::Ready(Err(Error::last_os_error()));
             }
@@ -1,23 +1,97 @@
 use std::fmt::Debug;
-use std::io::Error;
+use std::io::{Error, ErrorKind};
 use std::pin::Pin;
-use std::{io::Result, net::SocketAddr, task::Poll, time::Duration};
+use std::{
+    io::{IoSlice, IoSliceMut, Result},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+    time::{Duration, Instant},
+};
 
 use futures::{AsyncRead, AsyncWrite, Future, Stream};
 
-use crate::io::IoReactor;
+use crate::io::{dns::Resolver, IoReactor};
 use crate::ReactorHandle;
 
 use super::sys::{self, Socket};
 use super::Handle;
 
+/// `TCP_CLOSE_WAIT` of linux `tcp_states.h`, not exported by libc.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const TCP_CLOSE_WAIT: u8 = 8;
+
+/// Options of listening socket, see [`TcpAcceptor::listen_with`].
+#[derive(Debug, Clone)]
+pub struct TcpListenerOptions {
+    /// Set `SO_REUSEPORT` before bind, so multiple listeners (e.g. one per thread) can bind the same address
+    /// and the kernel balances incoming connections between them. Not supported on windows.
+    pub reuse_port: bool,
+    /// Max length of the accept queue, default `SOMAXCONN`.
+    pub backlog: i32,
+    /// Set `IPV6_V6ONLY` before bind, [`None`] leaves the os default.
+    /// With `Some(false)`, a listener bound to `[::]` also accepts ipv4 connections as v4-mapped addresses.
+    /// Ignored for ipv4 listen addresses.
+    pub v6only: Option<bool>,
+}
+
+impl Default for TcpListenerOptions {
+    fn default() -> Self {
+        #[cfg(target_family = "unix")]
+        let backlog = libc::SOMAXCONN;
+
+        #[cfg(target_family = "windows")]
+        let backlog = winapi::um::winsock2::SOMAXCONN as i32;
+
+        Self {
+            reuse_port: false,
+            backlog,
+            v6only: None,
+        }
+    }
+}
+
+/// Options of client socket, see [`TcpStream::connect_with`].
+#[derive(Debug, Clone, Default)]
+pub struct TcpConnectOptions {
+    /// Local address bound before connect, [`None`] binds the unspecified address with a random port.
+    pub bind_addr: Option<SocketAddr>,
+    /// Bind the socket to the network interface named `device` (e.g. `eth1`) with `SO_BINDTODEVICE`,
+    /// so the connection egresses that interface whatever the routing table says.
+    /// Linux only, kernels before 5.7 require `CAP_NET_RAW`.
+    pub device: Option<String>,
+}
+
+/// Tcp keepalive probe parameters, see [`TcpStream::set_keepalive`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+    /// Idle time before the first probe (`TCP_KEEPIDLE`, `TCP_KEEPALIVE` on macos).
+    pub idle: Duration,
+    /// Interval between probes (`TCP_KEEPINTVL`).
+    pub interval: Duration,
+    /// Unanswered probes before the connection is dropped (`TCP_KEEPCNT`).
+    pub count: u32,
+}
+
+/// Shorten the slice lifetime of `bufs` to its borrow, [`sys::ReadBuffer::Vectored`] takes one lifetime
+/// and `&mut` is invariant over the slices.
+fn unify<'a>(bufs: &'a mut [IoSliceMut<'_>]) -> &'a mut [IoSliceMut<'a>] {
+    // Safety: the slices only outlive the borrow, and the reactor doesn't keep them after the call.
+    unsafe {
+        std::slice::from_raw_parts_mut(bufs.as_mut_ptr().cast::<IoSliceMut<'a>>(), bufs.len())
+    }
+}
+
 /// Tcp connection socket facade.
-pub struct TcpStream(Handle);
+///
+/// Connections accepted under a [`ConnectionLimiter`] hold its permit until this connection
+/// and all its read/write streams are dropped.
+pub struct TcpStream(Handle, Option<Arc<ConnectionPermit>>);
 
 /// Convert tcp connection from [`Handle`]
 impl From<Handle> for TcpStream {
     fn from(value: Handle) -> Self {
-        Self(value)
+        Self(value, None)
     }
 }
 
@@ -29,7 +103,25 @@ impl TcpStream {
         bind_addr: Option<SocketAddr>,
         timeout: Option<Duration>,
     ) -> TcpConnect {
-        match Self::client(reactor, remote, bind_addr) {
+        Self::connect_with(
+            reactor,
+            remote,
+            TcpConnectOptions {
+                bind_addr,
+                ..Default::default()
+            },
+            timeout,
+        )
+    }
+
+    /// Same as [`connect`](TcpStream::connect), with the client socket set up by `options`.
+    pub fn connect_with(
+        reactor: IoReactor,
+        remote: SocketAddr,
+        options: TcpConnectOptions,
+        timeout: Option<Duration>,
+    ) -> TcpConnect {
+        match Self::client(reactor, remote, options) {
             Ok(handle) => TcpConnect {
                 error: None,
                 handle: Some(handle),
@@ -45,17 +137,52 @@ impl TcpStream {
         }
     }
 
-    fn client(
-        poller: IoReactor,
-        remote: SocketAddr,
-        bind_addr: Option<SocketAddr>,
-    ) -> Result<Handle> {
+    /// Resolve `host` with [`resolver`](Resolver) and connect to resolved addresses in order,
+    /// returns the first established connection or the last connect error.
+    pub async fn connect_host(
+        reactor: IoReactor,
+        resolver: &Resolver,
+        host: &str,
+        port: u16,
+        timeout: Option<Duration>,
+    ) -> Result<Self> {
+        let mut last_err = None;
+
+        for remote in resolver.resolve(host, port).await? {
+            match Self::connect(reactor.clone(), remote, None, timeout).await {
+                Ok(conn) => return Ok(conn),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no address resolved for host({})", host),
+            )
+        }))
+    }
+
+    fn client(poller: IoReactor, remote: SocketAddr, options: TcpConnectOptions) -> Result<Handle> {
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        if let Some(device) = &options.device {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("bind to device({}) is linux only", device),
+            ));
+        }
+
         let socket = match remote {
             SocketAddr::V4(_) => Handle::tcp(true),
             SocketAddr::V6(_) => Handle::tcp(false),
         }?;
 
-        if let Some(addr) = bind_addr {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(device) = &options.device {
+            Handle::set_bind_device(socket, device)?;
+        }
+
+        if let Some(addr) = options.bind_addr {
             Handle::bind(socket, addr)?;
         } else {
             let bind_addr = if remote.is_ipv4() {
@@ -70,11 +197,268 @@ impl TcpStream {
         Handle::new(remote.is_ipv4(), socket, poller)
     }
 
+    /// Get the local address of this connection.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    /// Get the remote address of this connection with `getpeername`,
+    /// works for connections adopted with [`from_parts`](TcpStream::from_parts) too.
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.0.peer_addr()
+    }
+
+    /// Shut down the read, write or both halves of this connection.
+    ///
+    /// e.g. shutting down [`Write`](std::net::Shutdown::Write) sends EOF to peer while this side keeps reading.
+    /// Unlike dropping the connection, the fd stays open until all handles are dropped.
+    pub fn shutdown(&self, how: std::net::Shutdown) -> Result<()> {
+        self.0.shutdown(how)
+    }
+
+    /// Read into `buf` without removing the data from the receive queue (`MSG_PEEK`),
+    /// the next read returns the same bytes. Useful to detect the protocol before handing the stream over.
+    pub async fn peek(&mut self, buf: &mut [u8]) -> Result<usize> {
+        futures::future::poll_fn(|cx| {
+            Pin::new(&mut self.0).poll_read(cx, sys::ReadBuffer::Peek(buf), None)
+        })
+        .await
+    }
+
+    /// Read into `buf`, returns `Ok(None)` once the peer closed its write side (EOF), `Ok(Some(len))` otherwise.
+    ///
+    /// Unlike the `Ok(0)` of [`AsyncRead`], EOF can't be confused with an empty `buf`, which reads `Ok(Some(0))`.
+    pub async fn read_or_eof(&mut self, buf: &mut [u8]) -> Result<Option<usize>> {
+        if buf.is_empty() {
+            return Ok(Some(0));
+        }
+
+        match futures::AsyncReadExt::read(self, buf).await? {
+            0 => Ok(None),
+            len => Ok(Some(len)),
+        }
+    }
+
+    /// Read exactly `buf.len()` bytes, failing with [`ErrorKind::TimedOut`] if they don't all arrive within `timeout`.
+    ///
+    /// The deadline spans the whole call, not each underlying read, so a framed reader can give e.g. the header
+    /// and the body distinct timeouts. EOF before `buf` is filled fails with [`ErrorKind::UnexpectedEof`].
+    /// Dropping the returned future removes its pending registration from the reactor.
+    pub fn read_exact_timeout<'a>(
+        &'a mut self,
+        buf: &'a mut [u8],
+        timeout: Duration,
+    ) -> ReadExactTimeout<'a> {
+        ReadExactTimeout {
+            stream: self,
+            buf,
+            filled: 0,
+            deadline: Instant::now() + timeout,
+        }
+    }
+
+    /// Check if a write would likely proceed without blocking, e.g. to decide whether to keep batching.
+    ///
+    /// Returns [`Pending`](Poll::Pending) while the send buffer is full, without registering a waker.
+    /// The caller can then write as usual, which waits for writability.
+    pub fn poll_write_ready_nonblocking(&self) -> Poll<Result<()>> {
+        self.0.poll_write_ready_nonblocking()
+    }
+
+    /// Enable or disable `TCP_NODELAY` of this connection, small writes are sent immediately
+    /// instead of being coalesced by nagle algorithm.
+    pub fn set_nodelay(&self, on: bool) -> Result<()> {
+        self.0.set_nodelay(on)
+    }
+
+    /// Get `TCP_NODELAY` of this connection.
+    pub fn nodelay(&self) -> Result<bool> {
+        self.0.nodelay()
+    }
+
+    /// Set time-to-live of outgoing packets (hop limit for ipv6 connections).
+    pub fn set_ttl(&self, ttl: u32) -> Result<()> {
+        self.0.set_ttl(ttl)
+    }
+
+    /// Get time-to-live of outgoing packets (hop limit for ipv6 connections).
+    pub fn ttl(&self) -> Result<u32> {
+        self.0.ttl()
+    }
+
+    /// Set send buffer size (`SO_SNDBUF`) of this connection.
+    ///
+    /// The kernel may round or double the requested value (linux doubles it to leave room for bookkeeping),
+    /// use [`send_buffer_size`](Self::send_buffer_size) to get the actual value.
+    pub fn set_send_buffer_size(&self, size: usize) -> Result<()> {
+        self.0.set_send_buffer_size(size)
+    }
+
+    /// Get send buffer size (`SO_SNDBUF`) of this connection reported by the kernel.
+    pub fn send_buffer_size(&self) -> Result<usize> {
+        self.0.send_buffer_size()
+    }
+
+    /// Set receive buffer size (`SO_RCVBUF`) of this connection, the kernel may adjust the value
+    /// as [`set_send_buffer_size`](Self::set_send_buffer_size) does.
+    pub fn set_recv_buffer_size(&self, size: usize) -> Result<()> {
+        self.0.set_recv_buffer_size(size)
+    }
+
+    /// Get receive buffer size (`SO_RCVBUF`) of this connection reported by the kernel.
+    pub fn recv_buffer_size(&self) -> Result<usize> {
+        self.0.recv_buffer_size()
+    }
+
+    /// Enable keepalive probes with `config`, or disable keepalive if `config` is [`None`].
+    ///
+    /// Durations are rounded down to whole seconds, with a minimum of 1 second.
+    #[cfg(target_family = "unix")]
+    pub fn set_keepalive(&self, config: Option<KeepaliveConfig>) -> Result<()> {
+        self.0.set_keepalive(config.as_ref())
+    }
+
+    /// Get keepalive configuration of this connection, [`None`] if keepalive is disabled.
+    #[cfg(target_family = "unix")]
+    pub fn keepalive(&self) -> Result<Option<KeepaliveConfig>> {
+        self.0.keepalive()
+    }
+
+    /// Get the original destination address of a connection redirected by netfilter,
+    /// see [`Handle::original_dst`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn original_dst(&self) -> Result<SocketAddr> {
+        self.0.original_dst()
+    }
+
+    /// Get kernel `TCP_INFO` statistics of this connection.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn tcp_info(&self) -> Result<libc::tcp_info> {
+        self.0.tcp_info()
+    }
+
+    /// Check if peer has sent FIN and the connection is waiting for local close (`CLOSE_WAIT`),
+    /// application loops can use this to find leaked half-closed connections.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn is_peer_closed(&self) -> Result<bool> {
+        Ok(self.tcp_info()?.tcpi_state == TCP_CLOSE_WAIT)
+    }
+
+    /// Get the napi id of the rx queue which received this connection, see [`Handle::incoming_napi_id`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn incoming_napi_id(&self) -> Result<u32> {
+        self.0.incoming_napi_id()
+    }
+
+    /// Get bytes written but not yet acknowledged by peer, see [`Handle::send_queue_len`].
+    ///
+    /// Proxies can pace reads from the source by how much the destination still buffers.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn send_queue_len(&self) -> Result<usize> {
+        self.0.send_queue_len()
+    }
+
+    /// Get bytes received but not yet read, see [`Handle::recv_queue_len`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn recv_queue_len(&self) -> Result<usize> {
+        self.0.recv_queue_len()
+    }
+
+    /// Set `FD_CLOEXEC` of this connection, see [`Handle::set_cloexec`].
+    #[cfg(target_family = "unix")]
+    pub fn set_cloexec(&self, on: bool) -> Result<()> {
+        self.0.set_cloexec(on)
+    }
+
+    /// Get `FD_CLOEXEC` of this connection.
+    #[cfg(target_family = "unix")]
+    pub fn cloexec(&self) -> Result<bool> {
+        self.0.cloexec()
+    }
+
+    /// Set `SO_MARK` of this connection, see [`Handle::set_mark`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_mark(&self, mark: u32) -> Result<()> {
+        self.0.set_mark(mark)
+    }
+
+    /// Get `SO_MARK` of this connection.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn mark(&self) -> Result<u32> {
+        self.0.mark()
+    }
+
+    /// Split connection into its reactor, raw fd and unread buffered bytes, e.g. to upgrade it to tls (STARTTLS).
+    ///
+    /// The fd is deregistered from reactor but not closed, rebuild the connection with
+    /// [`from_parts`](TcpStream::from_parts). All read/write streams of this connection must be dropped first.
+    /// The [`ConnectionLimiter`] permit, if any, is released.
+    /// [`TcpStream`] reads from socket directly, so the buffered bytes are always empty.
+    #[cfg(target_family = "unix")]
+    pub fn into_parts(self) -> Result<(IoReactor, crate::io::RawFd, Vec<u8>)> {
+        match self.0.detach() {
+            Ok((reactor, fd)) => Ok((reactor, fd, vec![])),
+            Err(_) => Err(Error::other(
+                "tcp connection is still shared by read/write streams",
+            )),
+        }
+    }
+
+    /// Rebuild connection from raw fd returned by [`into_parts`](TcpStream::into_parts).
+    #[cfg(target_family = "unix")]
+    pub fn from_parts(reactor: IoReactor, fd: crate::io::RawFd) -> Result<Self> {
+        Self::from_raw_fd(reactor, fd)
+    }
+
+    /// Release the raw fd of this connection without closing it, e.g. to pass it to another process.
+    ///
+    /// The fd is deregistered from reactor and the caller owns it from now on.
+    /// All read/write streams of this connection must be dropped first.
+    #[cfg(target_family = "unix")]
+    pub fn into_raw_fd(self) -> Result<crate::io::RawFd> {
+        let (_, fd, _) = self.into_parts()?;
+
+        Ok(fd)
+    }
+
+    /// Adopt an already connected stream socket `fd` opened elsewhere (e.g. passed by a parent process).
+    ///
+    /// The fd is switched to nonblock mode and registered to `reactor`, the connection owns it from now on
+    /// and closes it when dropped. The fd is closed if this method returns an error.
+    #[cfg(target_family = "unix")]
+    pub fn from_raw_fd(reactor: IoReactor, fd: crate::io::RawFd) -> Result<Self> {
+        Ok(Self(Handle::adopt(fd, reactor)?, None))
+    }
+
+    /// Convert connection into blocking [`std::net::TcpStream`], e.g. to hand it off to a blocking library.
+    ///
+    /// The fd is deregistered from reactor and switched back to blocking mode.
+    /// All read/write streams of this connection must be dropped first.
+    #[cfg(target_family = "unix")]
+    pub fn into_std(self) -> Result<std::net::TcpStream> {
+        use std::os::fd::FromRawFd;
+
+        let (_, fd, _) = self.into_parts()?;
+
+        let stream = unsafe { std::net::TcpStream::from_raw_fd(fd) };
+
+        stream.set_nonblocking(false)?;
+
+        Ok(stream)
+    }
+
+    /// Split this connection into owned read and write halves without operator timeout,
+    /// the connection is closed when both halves are dropped.
+    pub fn split(self) -> (TcpStreamReader, TcpStreamWriter) {
+        (self.to_read_stream(None), self.to_write_stream(None))
+    }
+
     /// Convert tcp connection to read stream
     pub fn to_read_stream<T: Into<Option<Duration>>>(&self, timeout: T) -> TcpStreamReader {
         TcpStreamReader {
             handle: self.0.clone(),
             timeout: timeout.into(),
+            _permit: self.1.clone(),
         }
     }
 
@@ -83,7 +467,118 @@ impl TcpStream {
         TcpStreamWriter {
             handle: self.0.clone(),
             timeout: timeout.into(),
+            _permit: self.1.clone(),
+        }
+    }
+}
+
+/// Read from tcp connection without operator timeout.
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, sys::ReadBuffer::Stream(buf), None)
+    }
+
+    fn poll_read_vectored(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, sys::ReadBuffer::Vectored(unify(bufs)), None)
+    }
+}
+
+/// Write to tcp connection without operator timeout.
+impl AsyncWrite for TcpStream {
+    fn poll_close(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<()>> {
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, sys::WriteBuffer::Stream(buf), None)
+    }
+
+    fn poll_write_vectored(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, sys::WriteBuffer::Vectored(bufs), None)
+    }
+}
+
+/// Future returned by [`TcpStream::read_exact_timeout`].
+pub struct ReadExactTimeout<'a> {
+    stream: &'a mut TcpStream,
+    buf: &'a mut [u8],
+    filled: usize,
+    deadline: Instant,
+}
+
+impl Future for ReadExactTimeout<'_> {
+    type Output = Result<()>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = &mut *self;
+
+        while this.filled < this.buf.len() {
+            let remaining = this.deadline.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                return Poll::Ready(Err(Error::new(
+                    ErrorKind::TimedOut,
+                    "tcp read_exact timeout",
+                )));
+            }
+
+            match Pin::new(&mut this.stream.0).poll_read(
+                cx,
+                sys::ReadBuffer::Stream(&mut this.buf[this.filled..]),
+                Some(remaining),
+            ) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(Error::new(
+                        ErrorKind::UnexpectedEof,
+                        format!(
+                            "tcp closed after {} of {} bytes",
+                            this.filled,
+                            this.buf.len()
+                        ),
+                    )))
+                }
+                Poll::Ready(Ok(len)) => this.filled += len,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            }
         }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for ReadExactTimeout<'_> {
+    fn drop(&mut self) {
+        self.stream.0.cancel_read();
     }
 }
 
@@ -117,7 +612,7 @@ impl Future for TcpConnect {
                 self.handle = Some(handle);
                 return Poll::Pending;
             }
-            Poll::Ready(Ok(_)) => return Poll::Ready(Ok(TcpStream(handle))),
+            Poll::Ready(Ok(_)) => return Poll::Ready(Ok(TcpStream(handle, None))),
             Poll::Ready(Err(err)) => {
                 self.handle = Some(handle);
 
@@ -131,6 +626,7 @@ impl Future for TcpConnect {
 pub struct TcpStreamReader {
     handle: Handle,
     timeout: Option<Duration>,
+    _permit: Option<Arc<ConnectionPermit>>,
 }
 
 impl AsyncRead for TcpStreamReader {
@@ -143,12 +639,23 @@ impl AsyncRead for TcpStreamReader {
 
         Pin::new(&mut self.handle).poll_read(cx, sys::ReadBuffer::Stream(buf), timeout)
     }
+
+    fn poll_read_vectored(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<Result<usize>> {
+        let timeout = self.timeout;
+
+        Pin::new(&mut self.handle).poll_read(cx, sys::ReadBuffer::Vectored(unify(bufs)), timeout)
+    }
 }
 
 /// TcpStream write stream
 pub struct TcpStreamWriter {
     handle: Handle,
     timeout: Option<Duration>,
+    _permit: Option<Arc<ConnectionPermit>>,
 }
 
 impl AsyncWrite for TcpStreamWriter {
@@ -175,40 +682,504 @@ impl AsyncWrite for TcpStreamWriter {
 
         Pin::new(&mut self.handle).poll_write(cx, sys::WriteBuffer::Stream(buf), timeout)
     }
+
+    fn poll_write_vectored(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize>> {
+        let timeout = self.timeout;
+
+        Pin::new(&mut self.handle).poll_write(cx, sys::WriteBuffer::Vectored(bufs), timeout)
+    }
+}
+
+/// Socket options applied to each connection accepted by [`TcpAcceptor`], [`None`] keeps system default.
+#[derive(Debug, Clone, Default)]
+pub struct AcceptDefaults {
+    /// `TCP_NODELAY`
+    pub nodelay: Option<bool>,
+    /// Keepalive probes, see [`TcpStream::set_keepalive`].
+    #[cfg(target_family = "unix")]
+    pub keepalive: Option<KeepaliveConfig>,
+    /// `SO_RCVBUF`
+    pub recv_buf: Option<usize>,
+    /// `SO_SNDBUF`
+    pub send_buf: Option<usize>,
+}
+
+impl AcceptDefaults {
+    fn apply(&self, stream: &TcpStream) -> Result<()> {
+        if let Some(nodelay) = self.nodelay {
+            stream.set_nodelay(nodelay)?;
+        }
+
+        #[cfg(target_family = "unix")]
+        if let Some(keepalive) = &self.keepalive {
+            stream.set_keepalive(Some(keepalive.clone()))?;
+        }
+
+        if let Some(size) = self.recv_buf {
+            stream.set_recv_buffer_size(size)?;
+        }
+
+        if let Some(size) = self.send_buf {
+            stream.set_send_buffer_size(size)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct LimiterState {
+    available: usize,
+    waiters: Vec<Waker>,
+}
+
+/// Connection budget shared by [`TcpAcceptor`]s, see [`TcpAcceptor::set_connection_limiter`].
+///
+/// Each accepted connection holds one permit until it is closed. While no permit is available,
+/// listeners stop accepting and new connections wait in the kernel accept queue.
+#[derive(Debug, Clone)]
+pub struct ConnectionLimiter(Arc<Mutex<LimiterState>>);
+
+impl ConnectionLimiter {
+    /// Create limiter allowing up to `permits` open connections across all listeners sharing it.
+    pub fn new(permits: usize) -> Self {
+        Self(Arc::new(Mutex::new(LimiterState {
+            available: permits,
+            waiters: vec![],
+        })))
+    }
+
+    /// Number of permits not held by any connection or listener.
+    pub fn available(&self) -> usize {
+        self.0.lock().unwrap().available
+    }
+
+    fn poll_acquire(&self, cx: &mut std::task::Context<'_>) -> Poll<ConnectionPermit> {
+        let mut state = self.0.lock().unwrap();
+
+        if state.available > 0 {
+            state.available -= 1;
+
+            return Poll::Ready(ConnectionPermit(self.clone()));
+        }
+
+        if !state
+            .waiters
+            .iter()
+            .any(|waker| waker.will_wake(cx.waker()))
+        {
+            state.waiters.push(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Permit of [`ConnectionLimiter`], given back when dropped.
+#[derive(Debug)]
+struct ConnectionPermit(ConnectionLimiter);
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let waiters = {
+            let mut state = self.0 .0.lock().unwrap();
+
+            state.available += 1;
+
+            std::mem::take(&mut state.waiters)
+        };
+
+        for waker in waiters {
+            waker.wake();
+        }
+    }
 }
 
-pub struct TcpAcceptor(Handle, Option<IoReactor>);
+pub struct TcpAcceptor(
+    Handle,
+    Option<IoReactor>,
+    AcceptDefaults,
+    Option<ConnectionLimiter>,
+    Option<ConnectionPermit>,
+);
+
+/// Convert tcp listener from [`Handle`]
+impl From<Handle> for TcpAcceptor {
+    fn from(value: Handle) -> Self {
+        Self(value, None, Default::default(), None, None)
+    }
+}
 
 impl TcpAcceptor {
     /// Create new tcp listener with [`listen_addr`](SocketAddr)
     ///
     /// If `connection_reactor` is not [`None`],
     /// the incoming connections will bind to that [`reactor`](IoReactor) instance
+    ///
+    /// The accept queue length is `SOMAXCONN`, use [`listen_with`](TcpAcceptor::listen_with) to bound it.
     pub fn new(
         reactor: IoReactor,
         listen_addr: SocketAddr,
         connection_reactor: Option<IoReactor>,
+    ) -> Result<Self> {
+        Self::listen_with(reactor, listen_addr, connection_reactor, Default::default())
+    }
+
+    /// Create new tcp listener with [`listen_addr`](SocketAddr) and listener `options`,
+    /// see [`new`](TcpAcceptor::new).
+    pub fn listen_with(
+        reactor: IoReactor,
+        listen_addr: SocketAddr,
+        connection_reactor: Option<IoReactor>,
+        options: TcpListenerOptions,
     ) -> Result<Self> {
         let handle = Handle::tcp(listen_addr.is_ipv4())?;
 
+        Handle::set_listener_options(handle, options.reuse_port)?;
+
+        if let (Some(on), false) = (options.v6only, listen_addr.is_ipv4()) {
+            Handle::set_v6only(handle, on)?;
+        }
+
         Handle::bind(handle, listen_addr)?;
 
-        Handle::listen(handle)?;
+        Handle::listen(handle, options.backlog)?;
 
         Ok(Self(
             Handle::new(listen_addr.is_ipv4(), handle, reactor)?,
             connection_reactor,
+            Default::default(),
+            None,
+            None,
         ))
     }
-}
 
-impl Stream for TcpAcceptor {
-    type Item = Result<(TcpStream, SocketAddr)>;
+    /// Get the local address of this listener, e.g. the port chosen by system when bound to port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.0.local_addr()
+    }
 
-    fn poll_next(
+    /// Accept on this listener from `reactor` too, e.g. one acceptor per reactor thread.
+    ///
+    /// The returned acceptor owns a duplicate of the listening socket, with the same connection reactor,
+    /// accept defaults and connection limiter. With `exclusive`, linux wakes only one of the exclusive
+    /// acceptors per incoming connection (`EPOLLEXCLUSIVE`) instead of all of them, register every
+    /// shared acceptor that way and don't poll the original one. Other systems ignore `exclusive`.
+    #[cfg(target_family = "unix")]
+    pub fn share(&self, reactor: IoReactor, exclusive: bool) -> Result<Self> {
+        Ok(Self(
+            self.0.share(reactor, exclusive)?,
+            self.1.clone(),
+            self.2.clone(),
+            self.3.clone(),
+            None,
+        ))
+    }
+
+    /// Set socket options applied to each accepted connection before it is yielded.
+    pub fn set_accept_defaults(&mut self, defaults: AcceptDefaults) {
+        self.2 = defaults;
+    }
+
+    /// Take a permit of `limiter` for each accepted connection, see [`ConnectionLimiter`].
+    ///
+    /// The listener acquires the permit before accepting and keeps it while waiting for a connection.
+    pub fn set_connection_limiter(&mut self, limiter: ConnectionLimiter) {
+        self.4 = None;
+        self.3 = Some(limiter);
+    }
+
+    /// Accept connections and run handshake `f` on each, until one succeeds, returns the handshake result.
+    ///
+    /// Connections failing the handshake are logged and closed, listener errors are returned.
+    /// Handshakes run one at a time, so `f` should bound its io with timeouts.
+    pub async fn accept_with<F, Fut, T>(&mut self, mut f: F) -> Result<T>
+    where
+        F: FnMut(TcpStream, SocketAddr) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        loop {
+            let (stream, remote) = match futures::TryStreamExt::try_next(self).await? {
+                Some(conn) => conn,
+                None => return Err(Error::new(ErrorKind::BrokenPipe, "tcp listener closed")),
+            };
+
+            match f(stream, remote).await {
+                Ok(value) => return Ok(value),
+                Err(err) => log::debug!(
+                    "handshake with {} failed, connection closed: {}",
+                    remote,
+                    err
+                ),
+            }
+        }
+    }
+
+    /// Get the raw fd of listening socket.
+    pub fn to_raw_fd(&self) -> crate::io::RawFd {
+        self.0.to_raw_fd()
+    }
+
+    /// Release the raw fd of listening socket without closing it, the caller owns it from now on.
+    ///
+    /// Unlike [`send_listener`](crate::io::handoff::send_listener), which passes a duplicate,
+    /// this listener stops accepting.
+    #[cfg(target_family = "unix")]
+    pub fn into_raw_fd(self) -> Result<crate::io::RawFd> {
+        match self.0.detach() {
+            Ok((_, fd)) => Ok(fd),
+            Err(_) => Err(Error::other("tcp listener handle is still shared")),
+        }
+    }
+
+    /// Set `FD_CLOEXEC` of this listener, e.g. to pass it to a child process, see [`Handle::set_cloexec`].
+    #[cfg(target_family = "unix")]
+    pub fn set_cloexec(&self, on: bool) -> Result<()> {
+        self.0.set_cloexec(on)
+    }
+
+    /// Get `FD_CLOEXEC` of this listener.
+    #[cfg(target_family = "unix")]
+    pub fn cloexec(&self) -> Result<bool> {
+        self.0.cloexec()
+    }
+
+    /// Set `SO_INCOMING_CPU` of this listener, see [`Handle::set_incoming_cpu`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_incoming_cpu(&self, cpu: i32) -> Result<()> {
+        self.0.set_incoming_cpu(cpu)
+    }
+
+    /// Get `SO_INCOMING_CPU` of this listener.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn incoming_cpu(&self) -> Result<i32> {
+        self.0.incoming_cpu()
+    }
+
+    /// Accept one incoming connection.
+    ///
+    /// Dropping the returned future before it completes deregisters its waker from the reactor,
+    /// e.g. to stop a parked accept on shutdown without closing the listener.
+    pub fn accept(&mut self) -> Accept<'_> {
+        Accept(self)
+    }
+
+    /// Accept one incoming connection and [`split`](TcpStream::split) it into read and write halves.
+    pub async fn accept_split(&mut self) -> Result<(TcpStreamReader, TcpStreamWriter, SocketAddr)> {
+        let (stream, remote) = self.accept().await?;
+
+        let (reader, writer) = stream.split();
+
+        Ok((reader, writer, remote))
+    }
+
+    /// Accept one incoming connection, the returned future owns the listener and gives it back with the connection.
+    pub fn accept_owned(self) -> AcceptOwned {
+        AcceptOwned(Some(self))
+    }
+
+    /// Get a stream of incoming connections borrowing this listener, accept errors are yielded as items.
+    ///
+    /// Same as polling the listener itself as [`Stream`], but leaves the listener usable
+    /// (e.g. to read [`local_addr`](Self::local_addr)) once the stream is dropped.
+    pub fn incoming(&mut self) -> Incoming<'_> {
+        Incoming(self)
+    }
+}
+
+/// Stream returned by [`TcpAcceptor::incoming`].
+pub struct Incoming<'a>(&'a mut TcpAcceptor);
+
+impl Stream for Incoming<'_> {
+    type Item = Result<(TcpStream, SocketAddr)>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut *self.0).poll_next(cx)
+    }
+}
+
+impl Drop for Incoming<'_> {
+    fn drop(&mut self) {
+        self.0 .0.cancel_accept();
+    }
+}
+
+/// Future returned by [`TcpAcceptor::accept`].
+pub struct Accept<'a>(&'a mut TcpAcceptor);
+
+impl Future for Accept<'_> {
+    type Output = Result<(TcpStream, SocketAddr)>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        match Pin::new(&mut *self.0).poll_next(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Some(result)) => Poll::Ready(result),
+            Poll::Ready(None) => Poll::Ready(Err(Error::new(
+                ErrorKind::NotConnected,
+                "tcp acceptor closed",
+            ))),
+        }
+    }
+}
+
+impl Drop for Accept<'_> {
+    fn drop(&mut self) {
+        self.0 .0.cancel_accept();
+    }
+}
+
+/// Future returned by [`TcpAcceptor::accept_owned`].
+pub struct AcceptOwned(Option<TcpAcceptor>);
+
+impl Future for AcceptOwned {
+    type Output = Result<(TcpAcceptor, TcpStream, SocketAddr)>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        let acceptor = self
+            .0
+            .as_mut()
+            .expect("AcceptOwned polled after completion");
+
+        match Pin::new(acceptor).poll_next(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Some(Ok((conn, remote)))) => {
+                Poll::Ready(Ok((self.0.take().unwrap(), conn, remote)))
+            }
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Err(err)),
+            Poll::Ready(None) => Poll::Ready(Err(Error::new(
+                std::io::ErrorKind::NotConnected,
+                "tcp acceptor closed",
+            ))),
+        }
+    }
+}
+
+impl Drop for AcceptOwned {
+    fn drop(&mut self) {
+        if let Some(acceptor) = &mut self.0 {
+            acceptor.0.cancel_accept();
+        }
+    }
+}
+
+/// The first file description passed by systemd socket activation.
+#[cfg(target_family = "unix")]
+const SD_LISTEN_FDS_START: crate::io::RawFd = 3;
+
+#[cfg(target_family = "unix")]
+impl TcpAcceptor {
+    /// Adopt listening sockets passed by systemd [`socket activation`](https://www.freedesktop.org/software/systemd/man/sd_listen_fds.html).
+    ///
+    /// Reads `LISTEN_PID`/`LISTEN_FDS` environment variables and returns an empty list if
+    /// this process is not socket activated. The inherited sockets are already listening,
+    /// so `listen` is not called again.
+    ///
+    /// Like `sd_listen_fds`, `unset_environment` removes the variables so child processes
+    /// don't adopt the sockets too. Modifying the environment is only safe while no other
+    /// thread reads it, so only set it early in `main`.
+    pub fn from_activation(reactor: IoReactor, unset_environment: bool) -> Result<Vec<Self>> {
+        let listen_pid = std::env::var("LISTEN_PID").ok();
+        let listen_fds = std::env::var("LISTEN_FDS").ok();
+
+        if unset_environment {
+            std::env::remove_var("LISTEN_PID");
+            std::env::remove_var("LISTEN_FDS");
+            std::env::remove_var("LISTEN_FDNAMES");
+        }
+
+        Self::from_activation_fds(
+            reactor,
+            SD_LISTEN_FDS_START,
+            listen_pid.as_deref(),
+            listen_fds.as_deref(),
+        )
+    }
+
+    fn from_activation_fds(
+        reactor: IoReactor,
+        start: crate::io::RawFd,
+        listen_pid: Option<&str>,
+        listen_fds: Option<&str>,
+    ) -> Result<Vec<Self>> {
+        let (listen_pid, listen_fds) = match (listen_pid, listen_fds) {
+            (Some(pid), Some(fds)) => (pid, fds),
+            _ => return Ok(vec![]),
+        };
+
+        let invalid = |name: &str| {
+            Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid socket activation env {}", name),
+            )
+        };
+
+        if listen_pid
+            .parse::<u32>()
+            .map_err(|_| invalid("LISTEN_PID"))?
+            != std::process::id()
+        {
+            return Ok(vec![]);
+        }
+
+        let count = listen_fds
+            .parse::<u32>()
+            .map_err(|_| invalid("LISTEN_FDS"))?;
+
+        let end = crate::io::RawFd::try_from(count)
+            .ok()
+            .and_then(|count| start.checked_add(count))
+            .ok_or_else(|| invalid("LISTEN_FDS"))?;
+
+        let mut acceptors = vec![];
+
+        for fd in start..end {
+            log::trace!("adopt socket activation fd({})", fd);
+
+            match Handle::adopt(fd, reactor.clone()) {
+                Ok(handle) => acceptors.push(Self::from(handle)),
+                Err(err) => {
+                    // `adopt` released `fd` and dropping `acceptors` closes the adopted ones,
+                    // the remaining fds are owned by this process too.
+                    for fd in fd + 1..end {
+                        unsafe { libc::close(fd) };
+                    }
+
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(acceptors)
+    }
+}
+
+impl Stream for TcpAcceptor {
+    type Item = Result<(TcpStream, SocketAddr)>;
+
+    fn poll_next(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<Option<Self::Item>> {
+        if let (Some(limiter), None) = (&self.3, &self.4) {
+            match limiter.poll_acquire(cx) {
+                Poll::Ready(permit) => self.4 = Some(permit),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
         let mut handle = None;
         let mut remote = None;
 
@@ -229,8 +1200,15 @@ impl Stream for TcpAcceptor {
                         self.0.reactor.clone()
                     };
 
+                    let stream = TcpStream(
+                        Handle::new(self.0.ip_v4, handle, reactor)?,
+                        self.4.take().map(Arc::new),
+                    );
+
+                    self.2.apply(&stream)?;
+
                     return Poll::Ready(Some(Ok((
-                        TcpStream::from(Handle::new(self.0.ip_v4, handle, reactor)?),
+                        stream,
                         remote
                             .expect("Underlay accept returns success, but not set remote address"),
                     ))));
@@ -251,7 +1229,8 @@ mod tests {
     use std::thread::spawn;
 
     use futures::{
-        executor::ThreadPool, task::SpawnExt, AsyncReadExt, AsyncWriteExt, FutureExt, TryStreamExt,
+        executor::ThreadPool, task::SpawnExt, AsyncReadExt, AsyncWriteExt, FutureExt, StreamExt,
+        TryStreamExt,
     };
     use futures_test::task::noop_context;
 
@@ -259,6 +1238,33 @@ mod tests {
 
     use super::*;
 
+    /// Connect a client to a server accepted on an ephemeral port, returns `(reactor, client, server)`.
+    ///
+    /// The reactor is driven by [`IoReactor::spawn_poll_thread`], which exits once all clones are dropped.
+    async fn connected_pair() -> (IoReactor, TcpStream, TcpStream) {
+        connected_pair_with(IoReactor::default()).await
+    }
+
+    /// Same as [`connected_pair`] on `reactor`, e.g. one with a finer tick for timeouts.
+    async fn connected_pair_with(reactor: IoReactor) -> (IoReactor, TcpStream, TcpStream) {
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.accept());
+
+        let (server, _) = accept.unwrap();
+
+        (reactor, client.unwrap(), server)
+    }
+
     #[futures_test::test]
     async fn test_acceptor() {
         _ = pretty_env_logger::try_init();
@@ -408,4 +1414,1928 @@ mod tests {
 
         assert_eq!(&buff, b"hello world");
     }
+
+    #[cfg(target_family = "unix")]
+    #[futures_test::test]
+    async fn test_socket_activation() {
+        use std::os::fd::IntoRawFd;
+
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        // Simulate systemd, which passes an already listening socket.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let listen_addr = listener.local_addr().unwrap();
+
+        let fd = listener.into_raw_fd();
+
+        let pid = std::process::id().to_string();
+
+        let mut acceptors =
+            TcpAcceptor::from_activation_fds(reactor.clone(), fd, Some(&pid), Some("1")).unwrap();
+
+        assert_eq!(acceptors.len(), 1);
+
+        // Activation for another process.
+        assert!(
+            TcpAcceptor::from_activation_fds(reactor.clone(), fd, Some("1"), Some("1"))
+                .unwrap()
+                .is_empty()
+        );
+
+        for listen_fds in ["-1", "abc", &u32::MAX.to_string(), &i32::MAX.to_string()] {
+            assert_eq!(
+                TcpAcceptor::from_activation_fds(reactor.clone(), fd, Some(&pid), Some(listen_fds))
+                    .err()
+                    .unwrap()
+                    .kind(),
+                std::io::ErrorKind::InvalidInput
+            );
+        }
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let mut acceptor = acceptors.pop().unwrap();
+
+        let (connection, accept) = futures::join!(connect, acceptor.try_next());
+
+        connection.unwrap();
+
+        assert!(accept.unwrap().is_some());
+
+        // Not socket activated.
+        assert!(TcpAcceptor::from_activation(reactor, false)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_socket_activation_error_closes_fds() {
+        use std::os::fd::AsRawFd;
+
+        let reactor = IoReactor::default();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let not_socket = std::fs::File::open("/dev/null").unwrap();
+
+        // Consecutive fds like systemd passes them, the middle one fails to adopt.
+        let start = 900;
+
+        for (fd, source) in [
+            listener.as_raw_fd(),
+            not_socket.as_raw_fd(),
+            listener.as_raw_fd(),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            assert_eq!(
+                unsafe { libc::dup2(source, start + fd as i32) },
+                start + fd as i32
+            );
+        }
+
+        let pid = std::process::id().to_string();
+
+        assert!(
+            TcpAcceptor::from_activation_fds(reactor.clone(), start, Some(&pid), Some("3"))
+                .is_err()
+        );
+
+        for fd in start..start + 3 {
+            assert_eq!(
+                unsafe { libc::fcntl(fd, libc::F_GETFD) },
+                -1,
+                "fd({}) leaked",
+                fd
+            );
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[futures_test::test]
+    async fn test_original_dst() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (connection, accept) = futures::join!(connect, acceptor.try_next());
+
+        let (conn, _) = accept.unwrap().unwrap();
+
+        // Without a redirect rule, the lookup either fails (no conntrack entry)
+        // or reports the real destination.
+        match conn.original_dst() {
+            Ok(addr) => assert_eq!(addr, listen_addr),
+            Err(err) => log::debug!("original_dst: {}", err),
+        }
+
+        drop(connection.unwrap());
+    }
+
+    #[cfg(target_family = "windows")]
+    #[futures_test::test]
+    async fn test_overlapped_reuse() {
+        _ = pretty_env_logger::try_init();
+
+        let (reactor, client, server) = connected_pair().await;
+
+        let mut write_stream = client.to_write_stream(None);
+
+        let mut read_stream = server.to_read_stream(None);
+
+        let write = async {
+            for _ in 0..10000 {
+                write_stream.write_all(b"hello world").await.unwrap();
+            }
+        };
+
+        let read = async {
+            let mut buff = vec![0u8; 11 * 10000];
+
+            read_stream.read_exact(&mut buff).await.unwrap();
+        };
+
+        futures::join!(write, read);
+
+        // Overlapped structures are recycled instead of allocated per operation.
+        assert!(reactor.overlapped_pool().allocated() < 16);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[futures_test::test]
+    async fn test_export_fds() {
+        use std::sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        };
+
+        _ = pretty_env_logger::try_init();
+
+        let client_reactor = IoReactor::default();
+
+        client_reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let old_reactor = IoReactor::default();
+
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let old_background = {
+            let mut background = old_reactor.clone();
+            let stop = stop.clone();
+
+            spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    background.poll_once(Duration::from_millis(100)).unwrap();
+                }
+            })
+        };
+
+        let mut acceptor =
+            TcpAcceptor::new(old_reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let connect = TcpStream::connect(client_reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+        let client = client.unwrap();
+
+        let (server, _) = accept.unwrap().unwrap();
+
+        let exported = old_reactor.export_fds().unwrap();
+
+        assert_eq!(exported.len(), 2);
+
+        // Shutdown predecessor.
+        drop(acceptor);
+        drop(server);
+
+        stop.store(true, Ordering::SeqCst);
+        old_background.join().unwrap();
+        drop(old_reactor);
+
+        // Successor adopts exported fds.
+        let new_reactor = IoReactor::default();
+
+        new_reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor = None;
+        let mut server = None;
+
+        for fd in exported {
+            let is_listener = Handle::is_listener(fd).unwrap();
+
+            let handle = Handle::adopt(fd, new_reactor.clone()).unwrap();
+
+            if is_listener {
+                acceptor = Some(TcpAcceptor::from(handle));
+            } else {
+                server = Some(TcpStream::from(handle));
+            }
+        }
+
+        let mut acceptor = acceptor.unwrap();
+        let server = server.unwrap();
+
+        // In-flight connection keeps working.
+        client
+            .to_write_stream(None)
+            .write_all(b"hello world")
+            .await
+            .unwrap();
+
+        let mut buff = [0u8; 11];
+
+        server
+            .to_read_stream(None)
+            .read_exact(&mut buff)
+            .await
+            .unwrap();
+
+        assert_eq!(&buff, b"hello world");
+
+        // Listener keeps accepting.
+        let connect = TcpStream::connect(client_reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+        client.unwrap();
+
+        assert!(accept.unwrap().is_some());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[futures_test::test]
+    async fn test_into_parts() {
+        _ = pretty_env_logger::try_init();
+
+        let (_, mut client, mut server) = connected_pair().await;
+
+        // Plaintext exchange.
+        client.write_all(b"STARTTLS").await.unwrap();
+
+        let mut buff = [0u8; 8];
+
+        server.read_exact(&mut buff).await.unwrap();
+
+        assert_eq!(&buff, b"STARTTLS");
+
+        // Shared connection can't be split.
+        let reader = client.to_read_stream(None);
+
+        let client = match client.0.detach() {
+            Err(handle) => TcpStream(handle, None),
+            Ok(_) => panic!("detach shared handle"),
+        };
+
+        drop(reader);
+
+        let (client_reactor, fd, buffered) = client.into_parts().unwrap();
+
+        assert!(buffered.is_empty());
+
+        // Continue on the same fd with new wrapper.
+        let mut client = TcpStream::from_parts(client_reactor, fd).unwrap();
+
+        assert_eq!(client.0.to_raw_fd(), fd);
+
+        client.write_all(b"upgraded").await.unwrap();
+
+        server.read_exact(&mut buff).await.unwrap();
+
+        assert_eq!(&buff, b"upgraded");
+
+        server.write_all(b"accepted").await.unwrap();
+
+        client.read_exact(&mut buff).await.unwrap();
+
+        assert_eq!(&buff, b"accepted");
+    }
+
+    #[futures_test::test]
+    async fn test_connect_host() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let resolver = Resolver::default();
+
+        let connect = TcpStream::connect_host(
+            reactor.clone(),
+            &resolver,
+            "localhost",
+            listen_addr.port(),
+            None,
+        );
+
+        let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+        client.unwrap();
+
+        assert!(accept.unwrap().is_some());
+
+        assert_eq!(resolver.lookups(), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[futures_test::test]
+    #[ignore = "requires root and iptables to drop packets of the vanished peer"]
+    async fn test_keepalive_timeout_wakes_reader() {
+        use std::process::Command;
+
+        /// Drop packets sent to `port` until dropped.
+        struct Firewall(String);
+
+        impl Firewall {
+            fn iptables(&self, op: &str) -> bool {
+                Command::new("iptables")
+                    .args([op, "INPUT", "-p", "tcp", "--dport", &self.0, "-j", "DROP"])
+                    .status()
+                    .map(|status| status.success())
+                    .unwrap_or(false)
+            }
+        }
+
+        impl Drop for Firewall {
+            fn drop(&mut self) {
+                self.iptables("-D");
+            }
+        }
+
+        _ = pretty_env_logger::try_init();
+
+        let (_, mut client, _server) = connected_pair().await;
+
+        client
+            .set_keepalive(Some(KeepaliveConfig {
+                idle: Duration::from_secs(1),
+                interval: Duration::from_secs(1),
+                count: 2,
+            }))
+            .unwrap();
+
+        let firewall = Firewall(client.peer_addr().unwrap().port().to_string());
+
+        assert!(firewall.iptables("-I"), "iptables -I failed");
+
+        let start = std::time::Instant::now();
+
+        let mut buff = [0u8; 1];
+
+        let err = client.read(&mut buff).await.unwrap_err();
+
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+        // keepalive idle(1s) + count(2) * interval(1s), with some slack.
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[futures_test::test]
+    async fn test_accept_owned() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        for _ in 0..3 {
+            let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+            let (client, accept) = futures::join!(connect, acceptor.accept_owned());
+
+            let mut client = client.unwrap();
+
+            let (next, mut conn, _) = accept.unwrap();
+
+            client.write_all(b"hello").await.unwrap();
+
+            let mut buff = [0u8; 5];
+
+            conn.read_exact(&mut buff).await.unwrap();
+
+            assert_eq!(&buff, b"hello");
+
+            acceptor = next;
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    #[futures_test::test]
+    async fn test_into_std() {
+        use std::io::{Read, Write};
+
+        _ = pretty_env_logger::try_init();
+
+        let (reactor, client, mut server) = connected_pair().await;
+
+        let registered = |reactor: &IoReactor| {
+            let fds = reactor.export_fds().unwrap();
+
+            for fd in &fds {
+                unsafe { libc::close(*fd) };
+            }
+
+            fds.len()
+        };
+
+        assert_eq!(registered(&reactor), 2);
+
+        let mut client = client.into_std().unwrap();
+
+        // Reactor no longer references the converted fd.
+        assert_eq!(registered(&reactor), 1);
+
+        server.write_all(b"hello").await.unwrap();
+
+        let mut buff = [0u8; 5];
+
+        // Blocking read.
+        client.read_exact(&mut buff).unwrap();
+
+        assert_eq!(&buff, b"hello");
+
+        client.write_all(b"world").unwrap();
+
+        server.read_exact(&mut buff).await.unwrap();
+
+        assert_eq!(&buff, b"world");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[futures_test::test]
+    async fn test_is_peer_closed() {
+        _ = pretty_env_logger::try_init();
+
+        let (_, client, server) = connected_pair().await;
+
+        assert!(!server.is_peer_closed().unwrap());
+
+        drop(client);
+
+        // Wait for FIN, without reading the EOF.
+        let mut closed = false;
+
+        for _ in 0..100 {
+            if server.is_peer_closed().unwrap() {
+                closed = true;
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(closed);
+    }
+
+    #[futures_test::test]
+    async fn test_nodelay() {
+        _ = pretty_env_logger::try_init();
+
+        let (_, client, server) = connected_pair().await;
+
+        for stream in [&client, &server] {
+            stream.set_nodelay(true).unwrap();
+
+            assert!(stream.nodelay().unwrap());
+
+            stream.set_nodelay(false).unwrap();
+
+            assert!(!stream.nodelay().unwrap());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[futures_test::test]
+    async fn test_incoming_cpu() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        acceptor.set_incoming_cpu(0).unwrap();
+
+        assert_eq!(acceptor.incoming_cpu().unwrap(), 0);
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+        client.unwrap();
+
+        let (server, _) = accept.unwrap().unwrap();
+
+        // Loopback has no napi context.
+        server.incoming_napi_id().unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[futures_test::test]
+    async fn test_keepalive() {
+        _ = pretty_env_logger::try_init();
+
+        let (_, client, _) = connected_pair().await;
+
+        assert_eq!(client.keepalive().unwrap(), None);
+
+        let config = KeepaliveConfig {
+            idle: Duration::from_secs(30),
+            interval: Duration::from_secs(5),
+            count: 4,
+        };
+
+        client.set_keepalive(Some(config.clone())).unwrap();
+
+        assert_eq!(client.keepalive().unwrap(), Some(config));
+
+        client.set_keepalive(None).unwrap();
+
+        assert_eq!(client.keepalive().unwrap(), None);
+    }
+
+    #[cfg(target_family = "windows")]
+    #[futures_test::test]
+    async fn test_close_cancels_pending_read() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        for _ in 0..10 {
+            let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+            let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+            let client = client.unwrap();
+
+            let (server, _) = accept.unwrap().unwrap();
+
+            let mut read_stream = server.to_read_stream(None);
+
+            let mut buff = [0u8; 16];
+
+            // Start an overlapped read which never completes.
+            assert!(futures::poll!(read_stream.read(&mut buff)).is_pending());
+
+            drop(read_stream);
+            drop(server);
+            drop(client);
+        }
+
+        // Let poller dequeue the cancelled completions.
+        std::thread::sleep(Duration::from_millis(500));
+
+        // Cancelled overlapped structures are returned to pool.
+        assert!(reactor.overlapped_pool().allocated() < 16);
+    }
+
+    #[futures_test::test]
+    async fn test_buffer_size() {
+        _ = pretty_env_logger::try_init();
+
+        let (_, client, _) = connected_pair().await;
+
+        client.set_send_buffer_size(256 * 1024).unwrap();
+        client.set_recv_buffer_size(256 * 1024).unwrap();
+
+        assert!(client.send_buffer_size().unwrap() >= 256 * 1024);
+        assert!(client.recv_buffer_size().unwrap() >= 256 * 1024);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[futures_test::test]
+    async fn test_connect_timeout() {
+        use std::os::fd::AsRawFd;
+
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::new(Duration::from_millis(100)).unwrap();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        // Non-routable addresses (e.g. 10.255.255.1) are answered by transparent proxies in some environments,
+        // use a loopback listener with full accept queue instead, linux drops further SYNs silently.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let listen_addr = listener.local_addr().unwrap();
+
+        assert_eq!(unsafe { libc::listen(listener.as_raw_fd(), 0) }, 0);
+
+        // Backlog 0 holds exactly one pending connection.
+        let _queued = std::net::TcpStream::connect(listen_addr).unwrap();
+
+        let start = std::time::Instant::now();
+
+        let err = TcpStream::connect(reactor, listen_addr, None, Some(Duration::from_secs(1)))
+            .await
+            .err()
+            .expect("connect to full accept queue succeeded");
+
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[futures_test::test]
+    async fn test_local_addr() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        assert_ne!(listen_addr.port(), 0);
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+        let client = client.unwrap();
+
+        let (server, remote) = accept.unwrap().unwrap();
+
+        assert_eq!(client.local_addr().unwrap(), remote);
+
+        assert_eq!(server.local_addr().unwrap(), listen_addr);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[futures_test::test]
+    async fn test_peer_addr() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+        let client = client.unwrap();
+
+        let (server, remote) = accept.unwrap().unwrap();
+
+        assert_eq!(client.peer_addr().unwrap(), listen_addr);
+
+        assert_eq!(server.peer_addr().unwrap(), remote);
+
+        // Adopted fd has no cached remote address.
+        let (reactor, fd, _) = client.into_parts().unwrap();
+
+        let adopted = TcpStream::from_parts(reactor, fd).unwrap();
+
+        assert_eq!(adopted.peer_addr().unwrap(), listen_addr);
+    }
+
+    #[futures_test::test]
+    async fn test_accept_defaults() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        acceptor.set_accept_defaults(AcceptDefaults {
+            nodelay: Some(true),
+            ..Default::default()
+        });
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+        let client = client.unwrap();
+
+        let (server, _) = accept.unwrap().unwrap();
+
+        assert!(server.nodelay().unwrap());
+
+        assert!(!client.nodelay().unwrap());
+    }
+
+    #[futures_test::test]
+    async fn test_shutdown_write() {
+        _ = pretty_env_logger::try_init();
+
+        let (_reactor, client, server) = connected_pair().await;
+
+        client
+            .to_write_stream(None)
+            .write_all(b"request")
+            .await
+            .unwrap();
+
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+
+        let mut request = vec![];
+
+        server
+            .to_read_stream(None)
+            .read_to_end(&mut request)
+            .await
+            .unwrap();
+
+        assert_eq!(request, b"request");
+
+        server
+            .to_write_stream(None)
+            .write_all(b"response")
+            .await
+            .unwrap();
+
+        drop(server);
+
+        let mut response = vec![];
+
+        client
+            .to_read_stream(None)
+            .read_to_end(&mut response)
+            .await
+            .unwrap();
+
+        assert_eq!(response, b"response");
+    }
+
+    /// Poll `fut` on current thread, polling `reactor` until idle whenever it is pending.
+    fn drive<F: Future + Unpin>(reactor: &mut IoReactor, mut fut: F) -> F::Output {
+        loop {
+            if let Poll::Ready(output) = fut.poll_unpin(&mut noop_context()) {
+                return output;
+            }
+
+            assert!(reactor.poll_until_idle(1000).unwrap() < 1000);
+        }
+    }
+
+    #[test]
+    fn test_poll_until_idle() {
+        _ = pretty_env_logger::try_init();
+
+        let mut reactor = IoReactor::default();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = drive(
+            &mut reactor,
+            Box::pin(async { futures::join!(connect, acceptor.try_next()) }),
+        );
+
+        let client = client.unwrap();
+
+        let (server, _) = accept.unwrap().unwrap();
+
+        let mut client_write = client.to_write_stream(None);
+        let mut server_read = server.to_read_stream(None);
+
+        let mut buff = [0u8; 5];
+
+        let (write, read) = drive(
+            &mut reactor,
+            Box::pin(async {
+                futures::join!(
+                    client_write.write_all(b"hello"),
+                    server_read.read_exact(&mut buff)
+                )
+            }),
+        );
+
+        write.unwrap();
+        read.unwrap();
+
+        assert_eq!(&buff, b"hello");
+
+        assert_eq!(reactor.poll_until_idle(1000).unwrap(), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_connect_spurious_polls() {
+        use std::os::fd::AsRawFd;
+
+        _ = pretty_env_logger::try_init();
+
+        let mut reactor = IoReactor::new(Duration::from_millis(100)).unwrap();
+
+        // Full accept queue keeps the connect in progress until SYN is retransmitted.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let listen_addr = listener.local_addr().unwrap();
+
+        assert_eq!(unsafe { libc::listen(listener.as_raw_fd(), 0) }, 0);
+
+        let _queued = std::net::TcpStream::connect(listen_addr).unwrap();
+
+        let mut connect = TcpStream::connect(
+            reactor.clone(),
+            listen_addr,
+            None,
+            Some(Duration::from_secs(10)),
+        );
+
+        for _ in 0..10 {
+            assert!(connect.poll_unpin(&mut noop_context()).is_pending());
+
+            reactor.poll_once(Duration::from_millis(10)).unwrap();
+        }
+
+        // Make room in the accept queue.
+        listener.accept().unwrap();
+
+        let client = drive(&mut reactor, connect).unwrap();
+
+        assert_eq!(client.peer_addr().unwrap(), listen_addr);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_reuse_port() {
+        let reactor = IoReactor::default();
+
+        let options = TcpListenerOptions {
+            reuse_port: true,
+            ..Default::default()
+        };
+
+        let first = TcpAcceptor::listen_with(
+            reactor.clone(),
+            "127.0.0.1:0".parse().unwrap(),
+            None,
+            options.clone(),
+        )
+        .unwrap();
+
+        let listen_addr = first.local_addr().unwrap();
+
+        let second = TcpAcceptor::listen_with(reactor.clone(), listen_addr, None, options).unwrap();
+
+        assert_eq!(second.local_addr().unwrap(), listen_addr);
+
+        // Without `SO_REUSEPORT` the address is still taken.
+        let err = TcpAcceptor::new(reactor, listen_addr, None).err().unwrap();
+
+        assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+    }
+
+    #[futures_test::test]
+    async fn test_listen_backlog() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor = TcpAcceptor::listen_with(
+            reactor.clone(),
+            "127.0.0.1:0".parse().unwrap(),
+            None,
+            TcpListenerOptions {
+                backlog: 4,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+        client.unwrap();
+
+        assert!(accept.unwrap().is_some());
+    }
+
+    #[futures_test::test]
+    async fn test_dual_stack_listener() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor = TcpAcceptor::listen_with(
+            reactor.clone(),
+            "[::]:0".parse().unwrap(),
+            None,
+            TcpListenerOptions {
+                v6only: Some(false),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let port = acceptor.local_addr().unwrap().port();
+
+        let connect = TcpStream::connect(
+            reactor.clone(),
+            format!("127.0.0.1:{}", port).parse().unwrap(),
+            None,
+            None,
+        );
+
+        let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+        client.unwrap();
+
+        // Only the ipv6 socket is bound to this port, the ipv4 connection is accepted as v4-mapped.
+        assert!(accept.unwrap().is_some());
+    }
+
+    #[futures_test::test]
+    async fn test_write_vectored() {
+        _ = pretty_env_logger::try_init();
+
+        let (_, mut client, mut server) = connected_pair().await;
+
+        let bufs = [
+            IoSlice::new(b"hello "),
+            IoSlice::new(b"vectored "),
+            IoSlice::new(b"world"),
+        ];
+
+        let len = client.write_vectored(&bufs).await.unwrap();
+
+        // Loopback send buffer takes all three slices in one call.
+        assert_eq!(len, 20);
+
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+
+        let mut buf = vec![];
+
+        server.read_to_end(&mut buf).await.unwrap();
+
+        assert_eq!(buf, b"hello vectored world");
+    }
+
+    #[futures_test::test]
+    async fn test_read_vectored() {
+        use crate::io::util::sleep;
+
+        _ = pretty_env_logger::try_init();
+
+        let (reactor, mut client, mut server) =
+            connected_pair_with(IoReactor::new(Duration::from_millis(10)).unwrap()).await;
+
+        let (mut header, mut body) = ([0u8; 4], [0u8; 16]);
+
+        // Nothing sent yet, the first readv would block and registers readable interest.
+        let mut bufs = [IoSliceMut::new(&mut header), IoSliceMut::new(&mut body)];
+
+        let (read, write) = futures::join!(server.read_vectored(&mut bufs), async {
+            sleep(reactor.clone(), Duration::from_millis(100)).await;
+
+            client.write_all(b"HEADhello world").await
+        });
+
+        write.unwrap();
+
+        assert_eq!(read.unwrap(), 15);
+
+        assert_eq!(&header, b"HEAD");
+
+        assert_eq!(&body[..11], b"hello world");
+    }
+
+    #[futures_test::test]
+    async fn test_read_or_eof() {
+        _ = pretty_env_logger::try_init();
+
+        let (_, mut client, mut server) = connected_pair().await;
+
+        client.write_all(&[1u8; 10000]).await.unwrap();
+
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+
+        assert_eq!(server.read_or_eof(&mut []).await.unwrap(), Some(0));
+
+        let mut buf = [0u8; 1024];
+
+        let mut total = 0;
+
+        while let Some(len) = server.read_or_eof(&mut buf).await.unwrap() {
+            total += len;
+        }
+
+        assert_eq!(total, 10000);
+
+        assert_eq!(server.read_or_eof(&mut buf).await.unwrap(), None);
+    }
+
+    #[futures_test::test]
+    async fn test_peek() {
+        use crate::io::util::sleep;
+
+        _ = pretty_env_logger::try_init();
+
+        let (reactor, mut client, mut server) =
+            connected_pair_with(IoReactor::new(Duration::from_millis(10)).unwrap()).await;
+
+        let mut peeked = [0u8; 5];
+
+        // Nothing sent yet, peek registers readable interest.
+        let (peek, write) = futures::join!(server.peek(&mut peeked), async {
+            sleep(reactor.clone(), Duration::from_millis(100)).await;
+
+            client.write_all(b"\x16\x03\x01hello").await
+        });
+
+        write.unwrap();
+
+        assert_eq!(peek.unwrap(), 5);
+
+        let mut buf = [0u8; 8];
+
+        server.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(&buf[..5], &peeked);
+
+        assert_eq!(&buf, b"\x16\x03\x01hello");
+    }
+
+    #[futures_test::test]
+    async fn test_accept_with() {
+        _ = pretty_env_logger::try_init();
+
+        const MAGIC: u8 = 0x5a;
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let clients = spawn(move || {
+            let mut rejected = std::net::TcpStream::connect(listen_addr).unwrap();
+
+            std::io::Write::write_all(&mut rejected, &[0x00]).unwrap();
+
+            let mut accepted = std::net::TcpStream::connect(listen_addr).unwrap();
+
+            std::io::Write::write_all(&mut accepted, &[MAGIC]).unwrap();
+
+            (rejected, accepted)
+        });
+
+        let (stream, remote) = acceptor
+            .accept_with(|mut stream, remote| async move {
+                let mut magic = [0u8; 1];
+
+                stream.read_exact(&mut magic).await?;
+
+                if magic[0] != MAGIC {
+                    return Err(Error::new(ErrorKind::InvalidData, "bad magic"));
+                }
+
+                stream.write_all(b"welcome").await?;
+
+                Ok((stream, remote))
+            })
+            .await
+            .unwrap();
+
+        let (mut rejected, mut accepted) = clients.join().unwrap();
+
+        assert_eq!(remote, accepted.local_addr().unwrap());
+
+        let mut banner = [0u8; 7];
+
+        std::io::Read::read_exact(&mut accepted, &mut banner).unwrap();
+
+        assert_eq!(&banner, b"welcome");
+
+        // The rejected connection is closed after its handshake failed.
+        let mut buf = [0u8; 1];
+
+        assert_eq!(std::io::Read::read(&mut rejected, &mut buf).unwrap(), 0);
+
+        stream.shutdown(std::net::Shutdown::Both).unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[futures_test::test]
+    async fn test_from_raw_fd() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut fds = [0; 2];
+
+        assert_eq!(
+            unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) },
+            0
+        );
+
+        let mut stream = TcpStream::from_raw_fd(reactor.clone(), fds[0]).unwrap();
+
+        let mut peer = unsafe {
+            <std::os::unix::net::UnixStream as std::os::fd::FromRawFd>::from_raw_fd(fds[1])
+        };
+
+        stream.write_all(b"ping").await.unwrap();
+
+        let mut buf = [0u8; 4];
+
+        std::io::Read::read_exact(&mut peer, &mut buf).unwrap();
+
+        assert_eq!(&buf, b"ping");
+
+        std::io::Write::write_all(&mut peer, b"pong").unwrap();
+
+        stream.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(&buf, b"pong");
+
+        // Dropping the stream closes the adopted fd, the peer reads EOF.
+        drop(stream);
+
+        assert_eq!(std::io::Read::read(&mut peer, &mut buf).unwrap(), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[futures_test::test]
+    async fn test_queue_len() {
+        _ = pretty_env_logger::try_init();
+
+        let (_, mut client, mut server) = connected_pair().await;
+
+        // Fill both the peer receive buffer and the local send buffer.
+        let chunk = [0u8; 64 * 1024];
+
+        let mut written = 0;
+
+        while let Some(len) = client.write(&chunk).now_or_never() {
+            written += len.unwrap();
+        }
+
+        assert!(client.send_queue_len().unwrap() > 0);
+
+        assert!(server.recv_queue_len().unwrap() > 0);
+
+        let mut buf = vec![0u8; written];
+
+        server.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(server.recv_queue_len().unwrap(), 0);
+
+        for _ in 0..100 {
+            if client.send_queue_len().unwrap() == 0 {
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(client.send_queue_len().unwrap(), 0);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[futures_test::test]
+    async fn test_into_raw_fd() {
+        use std::os::fd::FromRawFd;
+
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.try_next());
+
+        let mut client = client.unwrap();
+
+        let (server, _) = accept.unwrap().unwrap();
+
+        let mut server = unsafe { std::net::TcpStream::from_raw_fd(server.into_raw_fd().unwrap()) };
+
+        server.set_nonblocking(false).unwrap();
+
+        std::io::Write::write_all(&mut server, b"hello").unwrap();
+
+        let mut buf = [0u8; 5];
+
+        client.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(&buf, b"hello");
+
+        // Dropping the std stream closes the fd, a double close would abort debug builds.
+        drop(server);
+
+        assert_eq!(client.read(&mut buf).await.unwrap(), 0);
+
+        // Shared by a read stream, the fd can't be released.
+        let _reader = client.to_read_stream(None);
+
+        assert!(client.into_raw_fd().is_err());
+
+        let listener =
+            unsafe { std::net::TcpListener::from_raw_fd(acceptor.into_raw_fd().unwrap()) };
+
+        listener.set_nonblocking(false).unwrap();
+
+        let connect = spawn(move || std::net::TcpStream::connect(listen_addr).unwrap());
+
+        let (_, remote) = listener.accept().unwrap();
+
+        assert_eq!(remote, connect.join().unwrap().local_addr().unwrap());
+    }
+
+    #[futures_test::test]
+    async fn test_incoming() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let clients = spawn(move || {
+            (0..3)
+                .map(|_| std::net::TcpStream::connect(listen_addr).unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        let accepted = acceptor
+            .incoming()
+            .take(3)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        let mut peers = accepted
+            .iter()
+            .map(|(_, remote)| *remote)
+            .collect::<Vec<_>>();
+
+        let mut clients = clients
+            .join()
+            .unwrap()
+            .iter()
+            .map(|client| client.local_addr().unwrap())
+            .collect::<Vec<_>>();
+
+        peers.sort();
+        clients.sort();
+
+        assert_eq!(peers, clients);
+
+        assert_eq!(acceptor.local_addr().unwrap(), listen_addr);
+    }
+
+    #[futures_test::test]
+    async fn test_cancel_accept() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let mut accept = acceptor.accept();
+
+        assert!(accept.poll_unpin(&mut noop_context()).is_pending());
+
+        assert_eq!(reactor.registrations(), 1);
+
+        drop(accept);
+
+        assert_eq!(reactor.registrations(), 0);
+
+        // The listener stays usable.
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let client = spawn(move || std::net::TcpStream::connect(listen_addr).unwrap());
+
+        let (_, remote) = acceptor.accept().await.unwrap();
+
+        assert_eq!(remote, client.join().unwrap().local_addr().unwrap());
+    }
+
+    #[futures_test::test]
+    async fn test_accept_peer_addr_v6() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "[::1]:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let client = spawn(move || std::net::TcpStream::connect(listen_addr).unwrap());
+
+        let (_, remote) = acceptor.accept().await.unwrap();
+
+        assert_eq!(remote, client.join().unwrap().local_addr().unwrap());
+    }
+
+    #[futures_test::test]
+    async fn test_accept_split() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.accept_split());
+
+        let mut client = client.unwrap();
+
+        let (mut reader, mut writer, remote) = accept.unwrap();
+
+        assert_eq!(remote, client.local_addr().unwrap());
+
+        client.write_all(b"ping").await.unwrap();
+
+        let mut request = [0u8; 4];
+
+        // Read the request on one half while responding on the other.
+        let (read, write) =
+            futures::join!(reader.read_exact(&mut request), writer.write_all(b"pong"));
+
+        read.unwrap();
+        write.unwrap();
+
+        assert_eq!(&request, b"ping");
+
+        let mut response = [0u8; 4];
+
+        client.read_exact(&mut response).await.unwrap();
+
+        assert_eq!(&response, b"pong");
+    }
+
+    #[cfg(target_family = "windows")]
+    #[futures_test::test]
+    async fn test_write_all_partial_sends() {
+        _ = pretty_env_logger::try_init();
+
+        let (_, mut client, mut server) = connected_pair().await;
+
+        // Small buffers on both sides, so sends complete with less than requested.
+        client.set_send_buffer_size(4096).unwrap();
+        server.set_recv_buffer_size(4096).unwrap();
+
+        let data = (0..8 * 1024 * 1024)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        let mut received = vec![0u8; data.len()];
+
+        let (write, read) = futures::join!(
+            async {
+                client.write_all(&data).await?;
+                client.shutdown(std::net::Shutdown::Write)
+            },
+            server.read_exact(&mut received)
+        );
+
+        write.unwrap();
+        read.unwrap();
+
+        assert!(received == data);
+    }
+
+    #[futures_test::test]
+    async fn test_connection_limiter() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::io::util::sleep;
+
+        _ = pretty_env_logger::try_init();
+
+        let pool = ThreadPool::new().unwrap();
+
+        let reactor = IoReactor::new(Duration::from_millis(10)).unwrap();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(10))
+            .unwrap();
+
+        let limiter = ConnectionLimiter::new(4);
+
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_active = Arc::new(AtomicUsize::new(0));
+        let accepted = Arc::new(AtomicUsize::new(0));
+
+        let mut listen_addrs = vec![];
+
+        for _ in 0..2 {
+            let mut acceptor =
+                TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+            acceptor.set_connection_limiter(limiter.clone());
+
+            listen_addrs.push(acceptor.local_addr().unwrap());
+
+            let spawner = pool.clone();
+            let reactor = reactor.clone();
+            let active = active.clone();
+            let max_active = max_active.clone();
+            let accepted = accepted.clone();
+
+            _ = pool.spawn(async move {
+                while let Some((conn, _)) = acceptor.try_next().await.unwrap_or(None) {
+                    let count = active.fetch_add(1, Ordering::SeqCst) + 1;
+
+                    max_active.fetch_max(count, Ordering::SeqCst);
+
+                    accepted.fetch_add(1, Ordering::SeqCst);
+
+                    let reactor = reactor.clone();
+                    let active = active.clone();
+
+                    _ = spawner.spawn(async move {
+                        sleep(reactor, Duration::from_millis(50)).await;
+
+                        // Leave before the permit is given back by dropping the connection.
+                        active.fetch_sub(1, Ordering::SeqCst);
+
+                        drop(conn);
+                    });
+                }
+            });
+        }
+
+        let mut clients = vec![];
+
+        for i in 0..10 {
+            clients.push(
+                TcpStream::connect(reactor.clone(), listen_addrs[i % 2], None, None)
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let start = std::time::Instant::now();
+
+        while accepted.load(Ordering::SeqCst) < 10 {
+            assert!(start.elapsed() < Duration::from_secs(10), "accept stalled");
+
+            sleep(reactor.clone(), Duration::from_millis(10)).await;
+        }
+
+        assert!(max_active.load(Ordering::SeqCst) <= 4);
+    }
+
+    #[futures_test::test]
+    async fn test_reactor_close() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        let poll_thread = spawn(move || loop {
+            if let Err(err) = background.poll_once(Duration::from_millis(100)) {
+                return err;
+            }
+        });
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.accept());
+
+        let _client = client.unwrap();
+
+        let (server, _) = accept.unwrap();
+
+        let mut buff = [0u8; 16];
+
+        let mut read = server.to_read_stream(None);
+
+        let closer = reactor.clone();
+
+        spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+
+            closer.close();
+        });
+
+        // Nothing is written, the read is only resolved by `close`.
+        let err = read.read(&mut buff).await.unwrap_err();
+
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+
+        assert_eq!(
+            poll_thread.join().unwrap().kind(),
+            std::io::ErrorKind::NotConnected
+        );
+
+        assert_eq!(
+            read.read(&mut buff).await.unwrap_err().kind(),
+            std::io::ErrorKind::NotConnected
+        );
+
+        // The poller handle is closed as well.
+        assert_eq!(
+            reactor.notify().unwrap_err().kind(),
+            std::io::ErrorKind::NotConnected
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_share_exclusive() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        _ = pretty_env_logger::try_init();
+
+        let listener =
+            TcpAcceptor::new(IoReactor::default(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = listener.local_addr().unwrap();
+
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let accepted = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..4 {
+            let reactor = IoReactor::default();
+
+            let mut acceptor = listener.share(reactor.clone(), true).unwrap();
+
+            let mut background = reactor.clone();
+            let wakes = wakes.clone();
+
+            spawn(move || loop {
+                let woken = background.poll_once(Duration::from_millis(100)).unwrap();
+
+                wakes.fetch_add(woken, Ordering::SeqCst);
+            });
+
+            let accepted = accepted.clone();
+
+            spawn(move || {
+                futures::executor::block_on(async {
+                    while acceptor.accept().await.is_ok() {
+                        accepted.fetch_add(1, Ordering::SeqCst);
+                    }
+                })
+            });
+        }
+
+        // Let all acceptors register.
+        std::thread::sleep(Duration::from_millis(100));
+
+        let start = std::time::Instant::now();
+
+        for i in 1..=20 {
+            let _client = std::net::TcpStream::connect(listen_addr).unwrap();
+
+            while accepted.load(Ordering::SeqCst) < i {
+                assert!(start.elapsed() < Duration::from_secs(10), "accept stalled");
+
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        std::thread::sleep(Duration::from_millis(100));
+
+        // About one wake per connection, without exclusive wakeups the idle acceptors wake too (over 40 wakes).
+        let wakes = wakes.load(Ordering::SeqCst);
+
+        assert!(wakes <= 30, "{} wakes for 20 connections", wakes);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_share_exclusive_not_accepting() {
+        use futures::FutureExt;
+
+        _ = pretty_env_logger::try_init();
+
+        let listener =
+            TcpAcceptor::new(IoReactor::default(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = listener.local_addr().unwrap();
+
+        let mut reactor = IoReactor::default();
+
+        let mut acceptor = listener.share(reactor.clone(), true).unwrap();
+
+        acceptor.set_connection_limiter(ConnectionLimiter::new(1));
+
+        let _first = std::net::TcpStream::connect(listen_addr).unwrap();
+
+        let start = std::time::Instant::now();
+
+        // Takes the only permit.
+        let _conn = loop {
+            if let Some(accepted) = acceptor.try_next().now_or_never() {
+                break accepted.unwrap().unwrap();
+            }
+
+            assert!(start.elapsed() < Duration::from_secs(10), "accept stalled");
+
+            reactor.poll_once(Duration::from_millis(10)).unwrap();
+        };
+
+        // Queued in the backlog while the acceptor waits for a permit.
+        let _second = std::net::TcpStream::connect(listen_addr).unwrap();
+
+        assert!(acceptor.try_next().now_or_never().is_none());
+
+        let start = std::time::Instant::now();
+
+        assert_eq!(reactor.poll_once(Duration::from_millis(100)).unwrap(), 0);
+
+        // A listener left in the epoll set would fire at once on every poll.
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_cloexec() {
+        let reactor = IoReactor::default();
+
+        let acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let fd = acceptor.to_raw_fd();
+
+        let inherited = || {
+            std::process::Command::new("sh")
+                .arg("-c")
+                .arg(format!("test -e /dev/fd/{}", fd))
+                .status()
+                .unwrap()
+                .success()
+        };
+
+        acceptor.set_cloexec(false).unwrap();
+
+        assert!(!acceptor.cloexec().unwrap());
+        assert!(inherited());
+
+        acceptor.set_cloexec(true).unwrap();
+
+        assert!(acceptor.cloexec().unwrap());
+        assert!(!inherited());
+    }
+
+    #[futures_test::test]
+    async fn test_read_exact_timeout() {
+        _ = pretty_env_logger::try_init();
+
+        // Timeouts are hundreds of ms, the default 1s tick is too coarse.
+        let (reactor, mut client, mut server) =
+            connected_pair_with(IoReactor::new(Duration::from_millis(10)).unwrap()).await;
+
+        client.write_all(b"head").await.unwrap();
+
+        let mut header = [0u8; 4];
+
+        server
+            .read_exact_timeout(&mut header, Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        assert_eq!(&header, b"head");
+
+        let mut body = [0u8; 8];
+
+        let write_body = async {
+            client.write_all(b"body").await.unwrap();
+
+            crate::io::util::sleep(reactor.clone(), Duration::from_millis(300)).await;
+
+            client.write_all(b"tail").await.unwrap();
+        };
+
+        let (read, _) = futures::join!(
+            server.read_exact_timeout(&mut body, Duration::from_millis(500)),
+            write_body
+        );
+
+        read.unwrap();
+
+        assert_eq!(&body, b"bodytail");
+
+        // Dropping a pending read removes its waker and timeout.
+        assert!(server
+            .read_exact_timeout(&mut body, Duration::from_millis(100))
+            .now_or_never()
+            .is_none());
+
+        assert_eq!(reactor.registrations(), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[futures_test::test]
+    async fn test_connect_bind_device() {
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let options = TcpConnectOptions {
+            device: Some("lo".to_owned()),
+            ..Default::default()
+        };
+
+        let connect = TcpStream::connect_with(reactor.clone(), listen_addr, options, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.accept());
+
+        let client = client.unwrap();
+
+        let (_, remote) = accept.unwrap();
+
+        assert_eq!(client.local_addr().unwrap(), remote);
+
+        let options = TcpConnectOptions {
+            device: Some("no-such-nic".to_owned()),
+            ..Default::default()
+        };
+
+        let err = match TcpStream::connect_with(reactor, listen_addr, options, None).await {
+            Ok(_) => panic!("connect through unknown device"),
+            Err(err) => err,
+        };
+
+        assert_eq!(err.raw_os_error(), Some(libc::ENODEV));
+    }
+
+    // Overlapped sends on windows don't stop at a full send buffer.
+    #[cfg(target_family = "unix")]
+    #[futures_test::test]
+    async fn test_poll_write_ready_nonblocking() {
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.accept());
+
+        let mut client = client.unwrap();
+
+        let _server = accept.unwrap();
+
+        assert!(client.poll_write_ready_nonblocking().is_ready());
+
+        // Server never reads, write until both socket buffers are full.
+        let buf = vec![0u8; 64 * 1024];
+
+        while let Poll::Ready(len) = Pin::new(&mut client).poll_write(&mut noop_context(), &buf) {
+            len.unwrap();
+        }
+
+        assert!(client.poll_write_ready_nonblocking().is_pending());
+
+        // The probe doesn't register anything, only the pending write does.
+        assert_eq!(reactor.registrations(), 1);
+    }
 }
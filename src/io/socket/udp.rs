@@ -1,5 +1,11 @@
+use std::collections::VecDeque;
 use std::pin::Pin;
-use std::{io::Result, net::SocketAddr, task::Poll, time::Duration};
+use std::{
+    io::Result,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    task::Poll,
+    time::Duration,
+};
 
 use futures::{Sink, Stream};
 
@@ -11,6 +17,27 @@ use crate::ReactorHandle;
 /// Udp socket facade.
 pub struct UdpSocket(Handle);
 
+/// Options of udp socket, see [`UdpSocket::bind_with`].
+#[derive(Debug, Clone, Default)]
+pub struct UdpSocketOptions {
+    /// Set `IPV6_V6ONLY` before bind, [`None`] leaves the os default.
+    /// Ignored for ipv4 bind addresses.
+    pub v6only: Option<bool>,
+}
+
+/// Datagram metadata returned by [`UdpSocket::recv_from_full`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecvInfo {
+    /// Datagram length copied to the receive buffer.
+    pub len: usize,
+    /// Sender address.
+    pub source: SocketAddr,
+    /// Destination ip of the datagram header, [`None`] if packet info is disabled.
+    pub dest_ip: Option<IpAddr>,
+    /// Index of the interface the datagram arrived on, [`None`] if packet info is disabled.
+    pub ifindex: Option<u32>,
+}
+
 /// Convert udp socket from [`Handle`]
 impl From<Handle> for UdpSocket {
     fn from(value: Handle) -> Self {
@@ -21,14 +48,296 @@ impl From<Handle> for UdpSocket {
 impl UdpSocket {
     /// Create new udp socket with [`listen_addr`](SocketAddr)
     pub fn new(reactor: IoReactor, listen_addr: SocketAddr) -> Result<Self> {
+        Self::bind_with(reactor, listen_addr, Default::default())
+    }
+
+    /// Create new udp socket with [`listen_addr`](SocketAddr) and socket `options`.
+    pub fn bind_with(
+        reactor: IoReactor,
+        listen_addr: SocketAddr,
+        options: UdpSocketOptions,
+    ) -> Result<Self> {
         let fd = Handle::udp(listen_addr.is_ipv4())?;
 
+        if let (Some(on), false) = (options.v6only, listen_addr.is_ipv4()) {
+            Handle::set_v6only(fd, on)?;
+        }
+
         Handle::bind(fd, listen_addr)?;
 
-        Ok(Self(Handle::new(listen_addr.is_ipv4(), fd, reactor)?))
+        let handle = Handle::new(listen_addr.is_ipv4(), fd, reactor)?;
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        handle.set_recv_pktinfo(true)?;
+
+        Ok(Self(handle))
+    }
+
+    /// Adopt an already opened udp socket `fd` (e.g. passed by a parent process), bound or not.
+    ///
+    /// The fd is switched to nonblock mode and registered to `reactor`, the socket owns it from now on
+    /// and closes it when dropped. The fd is closed if this method returns an error.
+    #[cfg(target_family = "unix")]
+    pub fn from_raw_fd(reactor: IoReactor, fd: crate::io::RawFd) -> Result<Self> {
+        Ok(Self(Handle::adopt(fd, reactor)?))
+    }
+
+    /// Release the raw fd of this socket without closing it, the caller owns it from now on.
+    ///
+    /// The fd is deregistered from reactor, all read/write streams of this socket must be dropped first.
+    #[cfg(target_family = "unix")]
+    pub fn into_raw_fd(self) -> Result<crate::io::RawFd> {
+        match self.0.detach() {
+            Ok((_, fd)) => Ok(fd),
+            Err(_) => Err(std::io::Error::other(
+                "udp socket is still shared by read/write streams",
+            )),
+        }
+    }
+
+    /// Receive one datagram into `buf`, along with the destination ip and the interface index it arrived on.
+    ///
+    /// Sockets created by [`new`](Self::new) enable packet info, sockets converted from [`Handle`]
+    /// need [`Handle::set_recv_pktinfo`], otherwise `dest_ip` and `ifindex` are [`None`].
+    /// Datagrams longer than `buf` are dropped with an `EMSGSIZE` error.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub async fn recv_from_full(&mut self, buf: &mut [u8]) -> Result<RecvInfo> {
+        futures::future::poll_fn(|cx| Pin::new(&mut self.0).poll_recv_msg(cx, buf, None)).await
+    }
+
+    /// Connect this socket to `peer`, so [`send`](Self::send)/[`recv`](Self::recv) can be used without address.
+    ///
+    /// Only datagrams from `peer` are received afterwards, and icmp errors (e.g. port unreachable)
+    /// are reported by the following calls. The addressed streams keep working.
+    pub fn connect(&self, peer: SocketAddr) -> Result<()> {
+        self.0.connect_datagram(peer)
+    }
+
+    /// Send one datagram to the connected peer, see [`connect`](Self::connect).
+    pub async fn send(&mut self, buf: &[u8]) -> Result<usize> {
+        futures::future::poll_fn(|cx| {
+            Pin::new(&mut self.0).poll_write(cx, WriteBuffer::Stream(buf), None)
+        })
+        .await
+    }
+
+    /// Receive one datagram from the connected peer, see [`connect`](Self::connect).
+    ///
+    /// Datagrams longer than `buf` are dropped with an `EMSGSIZE` error.
+    pub async fn recv(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut remote = None;
+
+        futures::future::poll_fn(|cx| {
+            Pin::new(&mut self.0).poll_read(cx, ReadBuffer::Datagram(buf, &mut remote), None)
+        })
+        .await
+    }
+
+    /// Receive up to `max` queued datagrams into `bufs` with as few system calls as possible
+    /// (one `recvmmsg` on linux), waits until at least one is available.
+    ///
+    /// Each buffer is filled up to its capacity and truncated to the datagram length, along with the sender address,
+    /// so allocate them with [`Vec::with_capacity`]; buffers without capacity fail with `InvalidInput`.
+    /// The flag of an entry is set if its datagram was longer than the buffer, which then holds the leading part.
+    /// Returns the number of entries filled, entries past it are left with unspecified content.
+    /// Windows receives one datagram per call, and fails one longer than the buffer with `WSAEMSGSIZE`.
+    pub async fn recv_batch(
+        &mut self,
+        bufs: &mut [(Vec<u8>, SocketAddr, bool)],
+        max: usize,
+    ) -> Result<usize> {
+        futures::future::poll_fn(|cx| {
+            Pin::new(&mut self.0).poll_recv_batch(cx, &mut *bufs, max, None)
+        })
+        .await
+    }
+
+    /// Send `packets` to their target addresses with as few system calls as possible
+    /// (one `sendmmsg` on linux), waits until the socket is writable.
+    ///
+    /// Returns the number of packets sent, which is less than `packets.len()` when the socket buffer fills up,
+    /// resend the remaining ones with another call.
+    /// Windows sends one packet per call.
+    pub async fn send_batch(&mut self, packets: &[(Vec<u8>, SocketAddr)]) -> Result<usize> {
+        futures::future::poll_fn(|cx| Pin::new(&mut self.0).poll_send_batch(cx, packets, None))
+            .await
+    }
+
+    /// Send every received datagram back to its source, for echo servers and load generator reflectors.
+    ///
+    /// Unix drains the queue with one reused `msghdr` and no allocation per datagram.
+    /// Datagrams longer than 64KiB, or not accepted by a full send buffer, are dropped.
+    /// Runs until a receive error occurs, drop the future to stop.
+    pub async fn reflect_loop(&mut self) -> Result<()> {
+        let mut buff = vec![0u8; 65536];
+
+        #[cfg(target_family = "unix")]
+        {
+            futures::future::poll_fn(|cx| Pin::new(&mut self.0).poll_reflect(cx, &mut buff, None))
+                .await
+        }
+
+        #[cfg(target_family = "windows")]
+        loop {
+            let mut remote = None;
+
+            let len = futures::future::poll_fn(|cx| {
+                Pin::new(&mut self.0).poll_read(
+                    cx,
+                    ReadBuffer::Datagram(&mut buff, &mut remote),
+                    None,
+                )
+            })
+            .await?;
+
+            let Some(remote) = remote else {
+                continue;
+            };
+
+            let sent = futures::future::poll_fn(|cx| {
+                Pin::new(&mut self.0).poll_write(
+                    cx,
+                    WriteBuffer::Datagram(&buff[..len], &remote),
+                    None,
+                )
+            })
+            .await;
+
+            if let Err(err) = sent {
+                log::debug!("drop reflected datagram({}) to {}, {}", len, remote, err);
+            }
+        }
+    }
+
+    /// Get the local address of this socket.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    /// Set time-to-live of outgoing packets (hop limit for ipv6 sockets).
+    pub fn set_ttl(&self, ttl: u32) -> Result<()> {
+        self.0.set_ttl(ttl)
+    }
+
+    /// Get time-to-live of outgoing packets (hop limit for ipv6 sockets).
+    pub fn ttl(&self) -> Result<u32> {
+        self.0.ttl()
+    }
+
+    /// Enable or disable sending to broadcast addresses (`SO_BROADCAST`), default off.
+    pub fn set_broadcast(&self, on: bool) -> Result<()> {
+        self.0.set_broadcast(on)
+    }
+
+    /// Get whether sending to broadcast addresses is enabled.
+    pub fn broadcast(&self) -> Result<bool> {
+        self.0.broadcast()
+    }
+
+    /// Join ipv4 multicast group `multiaddr` on the interface with address `interface`
+    /// (`0.0.0.0` lets the system choose).
+    pub fn join_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> Result<()> {
+        self.0.join_multicast_v4(multiaddr, interface)
+    }
+
+    /// Leave ipv4 multicast group joined by [`join_multicast_v4`](Self::join_multicast_v4).
+    pub fn leave_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> Result<()> {
+        self.0.leave_multicast_v4(multiaddr, interface)
+    }
+
+    /// Join ipv6 multicast group `multiaddr` on the interface with index `interface` (0 lets the system choose).
+    pub fn join_multicast_v6(&self, multiaddr: Ipv6Addr, interface: u32) -> Result<()> {
+        self.0.join_multicast_v6(multiaddr, interface)
+    }
+
+    /// Leave ipv6 multicast group joined by [`join_multicast_v6`](Self::join_multicast_v6).
+    pub fn leave_multicast_v6(&self, multiaddr: Ipv6Addr, interface: u32) -> Result<()> {
+        self.0.leave_multicast_v6(multiaddr, interface)
+    }
+
+    /// Set whether multicast datagrams sent by this socket are looped back to local members, default on.
+    pub fn set_multicast_loop_v4(&self, on: bool) -> Result<()> {
+        self.0.set_multicast_loop_v4(on)
+    }
+
+    /// Get whether multicast datagrams sent by this socket are looped back to local members.
+    pub fn multicast_loop_v4(&self) -> Result<bool> {
+        self.0.multicast_loop_v4()
+    }
+
+    /// Set time-to-live of outgoing multicast datagrams, default 1 (stay in the local network).
+    pub fn set_multicast_ttl_v4(&self, ttl: u32) -> Result<()> {
+        self.0.set_multicast_ttl_v4(ttl)
+    }
+
+    /// Get time-to-live of outgoing multicast datagrams.
+    pub fn multicast_ttl_v4(&self) -> Result<u32> {
+        self.0.multicast_ttl_v4()
+    }
+
+    /// Set the interface (by address) outgoing multicast datagrams are sent on.
+    pub fn set_multicast_if_v4(&self, interface: Ipv4Addr) -> Result<()> {
+        self.0.set_multicast_if_v4(interface)
+    }
+
+    /// Set send buffer size (`SO_SNDBUF`) of this socket.
+    ///
+    /// The kernel may round or double the requested value (linux doubles it to leave room for bookkeeping),
+    /// use [`send_buffer_size`](Self::send_buffer_size) to get the actual value.
+    pub fn set_send_buffer_size(&self, size: usize) -> Result<()> {
+        self.0.set_send_buffer_size(size)
+    }
+
+    /// Get send buffer size (`SO_SNDBUF`) of this socket reported by the kernel.
+    pub fn send_buffer_size(&self) -> Result<usize> {
+        self.0.send_buffer_size()
+    }
+
+    /// Set receive buffer size (`SO_RCVBUF`) of this socket, the kernel may adjust the value
+    /// as [`set_send_buffer_size`](Self::set_send_buffer_size) does.
+    pub fn set_recv_buffer_size(&self, size: usize) -> Result<()> {
+        self.0.set_recv_buffer_size(size)
+    }
+
+    /// Get receive buffer size (`SO_RCVBUF`) of this socket reported by the kernel.
+    pub fn recv_buffer_size(&self) -> Result<usize> {
+        self.0.recv_buffer_size()
+    }
+
+    /// Set don't fragment (DF) bit of outgoing datagrams, see [`Handle::set_dont_fragment`].
+    ///
+    /// Used with path mtu discovery, datagrams larger than the path mtu fail with `EMSGSIZE`.
+    pub fn set_dont_fragment(&self, on: bool) -> Result<()> {
+        self.0.set_dont_fragment(on)
+    }
+
+    /// Get don't fragment (DF) bit setting of this socket.
+    pub fn dont_fragment(&self) -> Result<bool> {
+        self.0.dont_fragment()
+    }
+
+    /// Get the path mtu known by kernel, see [`Handle::path_mtu`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn path_mtu(&self) -> Result<usize> {
+        self.0.path_mtu()
+    }
+
+    /// Set `SO_MARK` of this socket, see [`Handle::set_mark`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_mark(&self, mark: u32) -> Result<()> {
+        self.0.set_mark(mark)
+    }
+
+    /// Get `SO_MARK` of this socket.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn mark(&self) -> Result<u32> {
+        self.0.mark()
     }
 
     /// Convert udp socket to read stream
+    ///
+    /// Datagrams longer than `buff_size` are yielded as `EMSGSIZE` (`WSAEMSGSIZE` on windows) errors
+    /// instead of being silently truncated, the stream keeps receiving afterwards.
     pub fn to_read_stream<T: Into<Option<Duration>>>(
         &self,
         buff_size: usize,
@@ -37,16 +346,28 @@ impl UdpSocket {
         UdpSocketReader {
             handle: self.0.clone(),
             timeout: timeout.into(),
-            buff_size,
+            buff: vec![0u8; buff_size],
         }
     }
 
     /// Convert udp socket to write stream.
     pub fn to_write_stream<T: Into<Option<Duration>>>(&self, timeout: T) -> UdpSocketWriter {
+        self.to_buffered_write_stream(1, timeout)
+    }
+
+    /// Convert udp socket to write stream, which queues up to `capacity` datagrams before sending.
+    ///
+    /// Queued datagrams are sent when the queue is full or on [`flush`](futures::SinkExt::flush).
+    pub fn to_buffered_write_stream<T: Into<Option<Duration>>>(
+        &self,
+        capacity: usize,
+        timeout: T,
+    ) -> UdpSocketWriter {
         UdpSocketWriter {
             handle: self.0.clone(),
             timeout: timeout.into(),
-            buff: None,
+            capacity: capacity.max(1),
+            queue: VecDeque::new(),
         }
     }
 }
@@ -54,32 +375,31 @@ impl UdpSocket {
 pub struct UdpSocketReader {
     handle: Handle,
     timeout: Option<Duration>,
-    buff_size: usize,
+    /// Receive buffer reused across polls, pending polls don't allocate.
+    buff: Vec<u8>,
 }
 
 impl Stream for UdpSocketReader {
     type Item = Result<(Vec<u8>, SocketAddr)>;
 
     fn poll_next(
-        mut self: std::pin::Pin<&mut Self>,
+        self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        let mut buff = vec![0u8; self.buff_size];
+        let this = self.get_mut();
 
         let mut remote = None;
 
-        let timeout = self.timeout.clone();
-
-        let read = Pin::new(&mut self.handle).poll_read(
+        let read = Pin::new(&mut this.handle).poll_read(
             cx,
-            ReadBuffer::Datagram(&mut buff, &mut remote),
-            timeout,
+            ReadBuffer::Datagram(&mut this.buff, &mut remote),
+            this.timeout,
         );
 
         match read {
             Poll::Pending => Poll::Pending,
             Poll::Ready(Ok(len)) => Poll::Ready(Some(Ok((
-                buff[0..len].to_vec(),
+                this.buff[0..len].to_vec(),
                 remote.expect("Underlay implement recvfrom success but not set remote address"),
             )))),
             Poll::Ready(Err(err)) => Poll::Ready(Some(Err(err))),
@@ -90,7 +410,8 @@ impl Stream for UdpSocketReader {
 pub struct UdpSocketWriter {
     handle: Handle,
     timeout: Option<Duration>,
-    buff: Option<(Vec<u8>, SocketAddr)>,
+    capacity: usize,
+    queue: VecDeque<(Vec<u8>, SocketAddr)>,
 }
 
 impl Sink<(Vec<u8>, SocketAddr)> for UdpSocketWriter {
@@ -100,7 +421,7 @@ impl Sink<(Vec<u8>, SocketAddr)> for UdpSocketWriter {
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<std::result::Result<(), Self::Error>> {
-        if self.buff.is_some() {
+        if self.queue.len() >= self.capacity {
             return self.poll_flush(cx);
         }
 
@@ -111,58 +432,65 @@ impl Sink<(Vec<u8>, SocketAddr)> for UdpSocketWriter {
         mut self: std::pin::Pin<&mut Self>,
         item: (Vec<u8>, SocketAddr),
     ) -> std::result::Result<(), Self::Error> {
-        self.buff = Some(item);
+        self.queue.push_back(item);
 
         Ok(())
     }
 
+    /// Send queued datagrams in order, resolves after the queue is empty.
     fn poll_flush(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<std::result::Result<(), Self::Error>> {
-        let send_buff = self.buff.take();
-
         let timeout = self.timeout.clone();
 
-        if let Some((buff, remote)) = send_buff {
-            let write = Pin::new(&mut self.handle).poll_write(
+        let this = &mut *self;
+
+        while let Some((buff, remote)) = this.queue.front() {
+            let write = Pin::new(&mut this.handle).poll_write(
                 cx,
-                WriteBuffer::Datagram(&buff, &remote),
+                WriteBuffer::Datagram(buff, remote),
                 timeout,
             );
 
             match write {
-                Poll::Ready(result) => match result {
-                    Ok(_) => Poll::Ready(Ok(())),
-                    Err(err) => Poll::Ready(Err(err)),
-                },
-                Poll::Pending => {
-                    self.buff = Some((buff, remote));
-                    Poll::Pending
+                // Keep datagram at queue front, retry after writable.
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(result) => {
+                    this.queue.pop_front();
+
+                    result?;
                 }
             }
-        } else {
-            Poll::Ready(Ok(()))
         }
+
+        Poll::Ready(Ok(()))
     }
     fn poll_close(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<std::result::Result<(), Self::Error>> {
+        futures::ready!(self.as_mut().poll_flush(cx))?;
+
         Pin::new(&mut self.handle).poll_close(cx)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{task::Poll, time::Duration};
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        task::Poll,
+        thread::spawn,
+        time::Duration,
+    };
 
-    use futures::{FutureExt, SinkExt, TryStreamExt};
+    use futures::{FutureExt, SinkExt, StreamExt, TryStreamExt};
     use futures_test::task::noop_context;
 
     use crate::{io::IoReactor, Reactor};
 
-    use super::UdpSocket;
+    use super::{UdpSocket, UdpSocketOptions};
 
     #[futures_test::test]
     async fn test_udp() {
@@ -250,4 +578,793 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_reader_pending_polls() {
+        _ = pretty_env_logger::try_init();
+
+        let mut reactor = IoReactor::default();
+
+        let socket = UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let local_addr = socket.local_addr().unwrap();
+
+        let mut reader = socket.to_read_stream(1024, None);
+
+        for _ in 0..100 {
+            assert!(reader.poll_next_unpin(&mut noop_context()).is_pending());
+        }
+
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        // The shorter second datagram must not see leftovers of the first one in the reused buffer.
+        for data in [&b"hello world"[..], &b"hi"[..]] {
+            sender.send_to(data, local_addr).unwrap();
+
+            loop {
+                match reader.poll_next_unpin(&mut noop_context()) {
+                    Poll::Pending => {
+                        reactor.poll_once(Duration::from_secs(1)).unwrap();
+                    }
+                    Poll::Ready(result) => {
+                        let (buff, _) = result.unwrap().unwrap();
+
+                        assert_eq!(buff, data);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[futures_test::test]
+    async fn test_buffered_flush() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let server_addr = "127.0.0.1:1907".parse().unwrap();
+
+        let client_addr = "127.0.0.1:1908".parse().unwrap();
+
+        let server = UdpSocket::new(reactor.clone(), server_addr).unwrap();
+
+        let client = UdpSocket::new(reactor.clone(), client_addr).unwrap();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let mut client_write_stream = client.to_buffered_write_stream(64, None);
+        let mut server_read_stream = server.to_read_stream(1024, None);
+
+        for i in 0..50u8 {
+            client_write_stream
+                .feed((vec![i; 16], server_addr))
+                .await
+                .unwrap();
+        }
+
+        client_write_stream.flush().await.unwrap();
+
+        for i in 0..50u8 {
+            let (buff, remote) = server_read_stream.try_next().await.unwrap().unwrap();
+
+            assert_eq!(buff, vec![i; 16]);
+
+            assert_eq!(remote, client_addr);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[futures_test::test]
+    async fn test_error_event_wakes_reader() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        // Nothing listens on this port, the icmp port unreachable raises EPOLLERR only.
+        let remote: std::net::SocketAddr = "127.0.0.1:1911".parse().unwrap();
+
+        let socket = UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let addr: os_socketaddr::OsSocketAddr = remote.into();
+
+        assert_eq!(
+            unsafe { libc::connect(socket.0.to_raw_fd(), addr.as_ptr(), addr.len()) },
+            0
+        );
+
+        let mut read_stream = socket.to_read_stream(1024, None);
+        let mut write_stream = socket.to_write_stream(None);
+
+        let (read, write) = futures::join!(read_stream.try_next(), async {
+            // Let reader register first.
+            std::thread::sleep(Duration::from_millis(100));
+
+            write_stream.send((b"ping".to_vec(), remote)).await
+        });
+
+        write.unwrap();
+
+        assert_eq!(
+            read.unwrap_err().kind(),
+            std::io::ErrorKind::ConnectionRefused
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn test_mark() {
+        let socket = UdpSocket::new(IoReactor::default(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        socket.set_mark(0x1234).unwrap();
+
+        assert_eq!(socket.mark().unwrap(), 0x1234);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mark_permission_denied() {
+        #[repr(C)]
+        struct CapHeader {
+            version: u32,
+            pid: i32,
+        }
+
+        #[repr(C)]
+        #[derive(Default, Clone, Copy)]
+        struct CapData {
+            effective: u32,
+            permitted: u32,
+            inheritable: u32,
+        }
+
+        const CAP_NET_ADMIN: u32 = 12;
+        const CAP_NET_RAW: u32 = 13;
+
+        let socket = UdpSocket::new(IoReactor::default(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        // Capabilities are per thread, drop them in a dedicated thread only.
+        let result = spawn(move || unsafe {
+            let mut header = CapHeader {
+                version: 0x20080522,
+                pid: 0,
+            };
+
+            let mut data = [CapData::default(); 2];
+
+            assert_eq!(
+                libc::syscall(libc::SYS_capget, &mut header, data.as_mut_ptr()),
+                0
+            );
+
+            data[0].effective &= !((1 << CAP_NET_ADMIN) | (1 << CAP_NET_RAW));
+
+            assert_eq!(
+                libc::syscall(libc::SYS_capset, &mut header, data.as_mut_ptr()),
+                0
+            );
+
+            socket.set_mark(1)
+        })
+        .join()
+        .unwrap();
+
+        let err = result.unwrap_err();
+
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+
+        assert!(err.to_string().contains("CAP_NET_ADMIN"));
+    }
+
+    #[test]
+    fn test_buffer_size() {
+        let reactor = IoReactor::default();
+
+        let socket = UdpSocket::new(reactor, "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        socket.set_send_buffer_size(256 * 1024).unwrap();
+        socket.set_recv_buffer_size(256 * 1024).unwrap();
+
+        assert!(socket.send_buffer_size().unwrap() >= 256 * 1024);
+        assert!(socket.recv_buffer_size().unwrap() >= 256 * 1024);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[futures_test::test]
+    async fn test_dont_fragment() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        reactor
+            .spawn_poll_thread(Duration::from_millis(100))
+            .unwrap();
+
+        let mut socket = UdpSocket::new(reactor.clone(), "[::1]:0".parse().unwrap()).unwrap();
+        let mut peer = UdpSocket::new(reactor.clone(), "[::1]:0".parse().unwrap()).unwrap();
+
+        socket.connect(peer.local_addr().unwrap()).unwrap();
+
+        socket.set_dont_fragment(true).unwrap();
+
+        assert!(socket.dont_fragment().unwrap());
+
+        // Larger than the loopback mtu (65536) with the ipv6 and udp headers, yet a valid ipv6 datagram.
+        let datagram = vec![0u8; 65500];
+
+        let err = socket.send(&datagram).await.unwrap_err();
+
+        assert_eq!(err.raw_os_error(), Some(libc::EMSGSIZE));
+
+        // Fragmented instead.
+        socket.set_dont_fragment(false).unwrap();
+
+        assert!(!socket.dont_fragment().unwrap());
+
+        assert_eq!(socket.send(&datagram).await.unwrap(), datagram.len());
+
+        let mut buf = vec![0u8; 65536];
+
+        assert_eq!(peer.recv(&mut buf).await.unwrap(), datagram.len());
+    }
+
+    #[test]
+    fn test_ttl() {
+        let reactor = IoReactor::default();
+
+        let socket = UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        socket.set_ttl(5).unwrap();
+
+        assert_eq!(socket.ttl().unwrap(), 5);
+
+        let socket = UdpSocket::new(reactor, "[::1]:0".parse().unwrap()).unwrap();
+
+        socket.set_ttl(7).unwrap();
+
+        assert_eq!(socket.ttl().unwrap(), 7);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[futures_test::test]
+    async fn test_recv_from_full() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let mut server = UdpSocket::new(reactor.clone(), "0.0.0.0:0".parse().unwrap()).unwrap();
+
+        let server_addr: std::net::SocketAddr =
+            format!("127.0.0.1:{}", server.local_addr().unwrap().port())
+                .parse()
+                .unwrap();
+
+        let client = UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        client
+            .to_write_stream(None)
+            .send((b"hello".to_vec(), server_addr))
+            .await
+            .unwrap();
+
+        let mut buf = [0u8; 1024];
+
+        let info = server.recv_from_full(&mut buf).await.unwrap();
+
+        assert_eq!(&buf[..info.len], b"hello");
+
+        assert_eq!(info.source, client.local_addr().unwrap());
+
+        assert_eq!(info.dest_ip, Some(server_addr.ip()));
+
+        let lo = unsafe { libc::if_nametoindex(c"lo".as_ptr()) };
+
+        assert_ne!(lo, 0);
+
+        assert_eq!(info.ifindex, Some(lo));
+    }
+
+    #[futures_test::test]
+    async fn test_connected() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let mut a = UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+        let mut b = UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+        let stray = UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        a.connect(b.local_addr().unwrap()).unwrap();
+        b.connect(a.local_addr().unwrap()).unwrap();
+
+        let mut buf = [0u8; 1024];
+
+        assert_eq!(a.send(b"ping").await.unwrap(), 4);
+
+        let len = b.recv(&mut buf).await.unwrap();
+
+        assert_eq!(&buf[..len], b"ping");
+
+        // Datagrams not from the connected peer are filtered out.
+        stray
+            .to_write_stream(None)
+            .send((b"stray".to_vec(), a.local_addr().unwrap()))
+            .await
+            .unwrap();
+
+        b.send(b"pong").await.unwrap();
+
+        let len = a.recv(&mut buf).await.unwrap();
+
+        assert_eq!(&buf[..len], b"pong");
+    }
+
+    #[futures_test::test]
+    async fn test_broadcast() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let socket = UdpSocket::new(reactor.clone(), "0.0.0.0:0".parse().unwrap()).unwrap();
+
+        assert!(!socket.broadcast().unwrap());
+
+        socket.set_broadcast(true).unwrap();
+
+        assert!(socket.broadcast().unwrap());
+
+        socket
+            .to_write_stream(None)
+            .send((b"hello".to_vec(), "255.255.255.255:9".parse().unwrap()))
+            .await
+            .unwrap();
+    }
+
+    #[futures_test::test]
+    async fn test_multicast() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let socket = UdpSocket::new(reactor.clone(), "0.0.0.0:0".parse().unwrap()).unwrap();
+
+        let group = Ipv4Addr::new(239, 255, 42, 1);
+
+        socket
+            .join_multicast_v4(group, Ipv4Addr::LOCALHOST)
+            .unwrap();
+
+        // Send on loopback, the default route may not carry multicast.
+        socket.set_multicast_if_v4(Ipv4Addr::LOCALHOST).unwrap();
+
+        socket.set_multicast_loop_v4(true).unwrap();
+        assert!(socket.multicast_loop_v4().unwrap());
+
+        socket.set_multicast_ttl_v4(2).unwrap();
+        assert_eq!(socket.multicast_ttl_v4().unwrap(), 2);
+
+        let group_addr = SocketAddr::new(group.into(), socket.local_addr().unwrap().port());
+
+        socket
+            .to_write_stream(None)
+            .send((b"hello group".to_vec(), group_addr))
+            .await
+            .unwrap();
+
+        let (buff, _) = socket
+            .to_read_stream(1024, None)
+            .try_next()
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(buff, b"hello group");
+
+        socket
+            .leave_multicast_v4(group, Ipv4Addr::LOCALHOST)
+            .unwrap();
+
+        // Not a member any more.
+        assert!(socket
+            .leave_multicast_v4(group, Ipv4Addr::LOCALHOST)
+            .is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[futures_test::test]
+    async fn test_recv_batch() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let mut socket = UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        for i in 0..5u8 {
+            sender
+                .send_to(&vec![i; i as usize + 1], socket.local_addr().unwrap())
+                .unwrap();
+        }
+
+        let mut bufs = (0..8)
+            .map(|_| {
+                (
+                    Vec::with_capacity(1024),
+                    "0.0.0.0:0".parse().unwrap(),
+                    false,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let received = socket.recv_batch(&mut bufs, 8).await.unwrap();
+
+        assert_eq!(received, 5);
+
+        for (i, (buf, source, truncated)) in bufs[..received].iter().enumerate() {
+            assert_eq!(buf, &vec![i as u8; i + 1]);
+            assert_eq!(*source, sender.local_addr().unwrap());
+            assert!(!truncated);
+        }
+
+        // `max` bounds the batch.
+        sender
+            .send_to(b"one", socket.local_addr().unwrap())
+            .unwrap();
+        sender
+            .send_to(b"two", socket.local_addr().unwrap())
+            .unwrap();
+
+        assert_eq!(socket.recv_batch(&mut bufs, 1).await.unwrap(), 1);
+        assert_eq!(bufs[0].0, b"one");
+
+        assert_eq!(socket.recv_batch(&mut bufs, 8).await.unwrap(), 1);
+        assert_eq!(bufs[0].0, b"two");
+
+        // Zero capacity buffers would drop the datagrams.
+        let mut empty = vec![(vec![], "0.0.0.0:0".parse().unwrap(), false)];
+
+        assert_eq!(
+            socket.recv_batch(&mut empty, 1).await.unwrap_err().kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[cfg(unix)]
+    #[futures_test::test]
+    async fn test_truncated_datagram() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let mut socket = UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        sender
+            .send_to(&[1u8; 2048], socket.local_addr().unwrap())
+            .unwrap();
+        sender
+            .send_to(&[2u8; 512], socket.local_addr().unwrap())
+            .unwrap();
+
+        let mut reader = socket.to_read_stream(512, None);
+
+        let err = reader.next().await.unwrap().unwrap_err();
+
+        assert_eq!(err.raw_os_error(), Some(libc::EMSGSIZE));
+
+        // Fitting datagrams are still received.
+        let (buf, _) = reader.next().await.unwrap().unwrap();
+
+        assert_eq!(buf, [2u8; 512]);
+
+        // Batch receive flags the truncated datagram and keeps the rest of the batch.
+        let mut bufs = (0..3)
+            .map(|_| (Vec::with_capacity(512), "0.0.0.0:0".parse().unwrap(), false))
+            .collect::<Vec<_>>();
+
+        sender
+            .send_to(&[3u8; 512], socket.local_addr().unwrap())
+            .unwrap();
+        sender
+            .send_to(&[4u8; 2048], socket.local_addr().unwrap())
+            .unwrap();
+        sender
+            .send_to(&[5u8; 256], socket.local_addr().unwrap())
+            .unwrap();
+
+        assert_eq!(socket.recv_batch(&mut bufs, 3).await.unwrap(), 3);
+
+        assert_eq!(bufs[0].0, [3u8; 512]);
+        assert!(!bufs[0].2);
+        assert_eq!(bufs[1].0, [4u8; 512]);
+        assert!(bufs[1].2);
+        assert_eq!(bufs[2].0, [5u8; 256]);
+        assert!(!bufs[2].2);
+
+        // Connected receive.
+        socket.connect(sender.local_addr().unwrap()).unwrap();
+
+        sender
+            .send_to(&[5u8; 2048], socket.local_addr().unwrap())
+            .unwrap();
+
+        let mut buf = [0u8; 512];
+
+        let err = socket.recv(&mut buf).await.unwrap_err();
+
+        assert_eq!(err.raw_os_error(), Some(libc::EMSGSIZE));
+
+        sender
+            .send_to(&[6u8; 512], socket.local_addr().unwrap())
+            .unwrap();
+
+        assert_eq!(socket.recv(&mut buf).await.unwrap(), 512);
+
+        assert_eq!(buf, [6u8; 512]);
+    }
+
+    #[futures_test::test]
+    async fn test_send_batch() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let mut socket = UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let receiver = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        let packets = (0..4u8)
+            .map(|i| (vec![i; i as usize + 1], receiver.local_addr().unwrap()))
+            .collect::<Vec<_>>();
+
+        let mut sent = 0;
+
+        while sent < packets.len() {
+            sent += socket.send_batch(&packets[sent..]).await.unwrap();
+        }
+
+        let mut buf = [0u8; 1024];
+
+        for (packet, _) in &packets {
+            let (len, source) = receiver.recv_from(&mut buf).unwrap();
+
+            assert_eq!(&buf[..len], packet.as_slice());
+            assert_eq!(source.port(), socket.local_addr().unwrap().port());
+        }
+    }
+
+    #[test]
+    fn test_reflect_loop() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let mut reflector =
+            UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let reflector_addr = reflector.local_addr().unwrap();
+
+        spawn(move || futures::executor::block_on(reflector.reflect_loop()));
+
+        let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        sender
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        for i in 0..100u8 {
+            sender.send_to(&[i; 32], reflector_addr).unwrap();
+        }
+
+        let mut received = vec![];
+
+        let mut buf = [0u8; 1024];
+
+        for _ in 0..100 {
+            let (len, source) = sender.recv_from(&mut buf).unwrap();
+
+            assert_eq!(source, reflector_addr);
+            assert_eq!(len, 32);
+            assert!(buf[..len].iter().all(|b| *b == buf[0]));
+
+            received.push(buf[0]);
+        }
+
+        received.sort();
+
+        assert_eq!(received, (0..100u8).collect::<Vec<_>>());
+    }
+
+    #[futures_test::test]
+    async fn test_v6only() {
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let server = UdpSocket::bind_with(
+            reactor.clone(),
+            "[::]:0".parse().unwrap(),
+            UdpSocketOptions {
+                v6only: Some(false),
+            },
+        )
+        .unwrap();
+
+        let server_addr: std::net::SocketAddr =
+            format!("127.0.0.1:{}", server.local_addr().unwrap().port())
+                .parse()
+                .unwrap();
+
+        let client = UdpSocket::new(reactor.clone(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        client
+            .to_write_stream(None)
+            .send((b"hello".to_vec(), server_addr))
+            .await
+            .unwrap();
+
+        let (buff, _) = server
+            .to_read_stream(1024, None)
+            .try_next()
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(buff, b"hello");
+
+        // A v6only socket doesn't take the ipv4 port, so an ipv4 socket can bind it.
+        let v6only = UdpSocket::bind_with(
+            reactor.clone(),
+            "[::]:0".parse().unwrap(),
+            UdpSocketOptions { v6only: Some(true) },
+        )
+        .unwrap();
+
+        let port = v6only.local_addr().unwrap().port();
+
+        UdpSocket::new(reactor, format!("0.0.0.0:{}", port).parse().unwrap()).unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[futures_test::test]
+    async fn test_from_raw_fd() {
+        use std::os::fd::IntoRawFd;
+
+        _ = pretty_env_logger::try_init();
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let fd = std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .into_raw_fd();
+
+        let socket = UdpSocket::from_raw_fd(reactor.clone(), fd).unwrap();
+
+        let addr = socket.local_addr().unwrap();
+
+        let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        peer.send_to(b"ping", addr).unwrap();
+
+        let (buff, _) = socket
+            .to_read_stream(1024, None)
+            .try_next()
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(buff, b"ping");
+
+        socket
+            .to_write_stream(None)
+            .send((b"pong".to_vec(), peer.local_addr().unwrap()))
+            .await
+            .unwrap();
+
+        let mut buf = [0u8; 4];
+
+        assert_eq!(peer.recv(&mut buf).unwrap(), 4);
+
+        assert_eq!(&buf, b"pong");
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_into_raw_fd() {
+        use std::os::fd::FromRawFd;
+
+        let socket = UdpSocket::new(IoReactor::default(), "127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let addr = socket.local_addr().unwrap();
+
+        let socket = unsafe { std::net::UdpSocket::from_raw_fd(socket.into_raw_fd().unwrap()) };
+
+        assert_eq!(socket.local_addr().unwrap(), addr);
+
+        let peer = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        peer.send_to(b"ping", addr).unwrap();
+
+        socket.set_nonblocking(false).unwrap();
+
+        let mut buf = [0u8; 4];
+
+        assert_eq!(socket.recv(&mut buf).unwrap(), 4);
+
+        // Dropping the std socket closes the fd, a double close would abort debug builds.
+        drop(socket);
+    }
 }
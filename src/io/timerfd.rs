@@ -0,0 +1,104 @@
+//! Linux `timerfd` timers polled by [`IoReactor`], for timeouts finer than the time wheel tick.
+//!
+//! The timer expiration makes the fd readable, so the reactor wakes exactly when it is due
+//! instead of on the next [`tick_duration`](IoReactor::new) boundary.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    mem::size_of,
+    ptr::null_mut,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use libc::*;
+
+use super::{EventName, IoReactor, RawFd};
+
+/// One-shot monotonic timer created by `timerfd_create`, registered to [`IoReactor`].
+#[derive(Debug)]
+pub struct TimerFd {
+    reactor: IoReactor,
+    fd: RawFd,
+}
+
+impl TimerFd {
+    /// Create a disarmed timer bound to `reactor`.
+    pub fn new(mut reactor: IoReactor) -> Result<Self> {
+        let fd = unsafe { timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK | TFD_CLOEXEC) };
+
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        if let Err(err) = reactor.on_open_fd(fd) {
+            unsafe { close(fd) };
+
+            return Err(err);
+        }
+
+        Ok(Self { reactor, fd })
+    }
+
+    /// Arm the timer to expire once after `duration`, replacing the pending expiration if any.
+    pub fn set(&self, duration: Duration) -> Result<()> {
+        // Zero `it_value` disarms the timer, expire as soon as possible instead.
+        let duration = duration.max(Duration::from_nanos(1));
+
+        let mut spec: itimerspec = unsafe { std::mem::zeroed() };
+
+        spec.it_value.tv_sec = duration.as_secs() as _;
+        spec.it_value.tv_nsec = duration.subsec_nanos() as _;
+
+        if unsafe { timerfd_settime(self.fd, 0, &spec, null_mut()) } < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    /// Check if the timer has expired, otherwise register `cx` waker for the expiration.
+    pub fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if let Some(event) = self.reactor.poll_io_event(self.fd, EventName::Read)? {
+            event.message?;
+        }
+
+        let mut expirations = 0u64;
+
+        let len = unsafe {
+            read(
+                self.fd,
+                &mut expirations as *mut u64 as *mut c_void,
+                size_of::<u64>(),
+            )
+        };
+
+        if len >= 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        let err = Error::last_os_error();
+
+        if err.kind() != ErrorKind::WouldBlock {
+            return Poll::Ready(Err(err));
+        }
+
+        self.reactor.once_labeled(
+            self.fd,
+            EventName::Read,
+            cx.waker().clone(),
+            None,
+            "timerfd",
+        )?;
+
+        Poll::Pending
+    }
+}
+
+impl Drop for TimerFd {
+    fn drop(&mut self) {
+        self.reactor.on_close_fd(self.fd);
+
+        unsafe { close(self.fd) };
+    }
+}
@@ -0,0 +1,296 @@
+//! [`rustls`](https://docs.rs/rustls) integration shim.
+//!
+//! This crate does not bundle a TLS implementation, [`TlsStream`] pumps bytes between a
+//! rustls [`Connection`] state machine and any asynchronous transport (e.g. [`TcpStream`](crate::io::socket::tcp::TcpStream)),
+//! exposing the decrypted stream as [`AsyncRead`]/[`AsyncWrite`].
+
+use std::{
+    io::{Error, ErrorKind, Read, Result, Write},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{AsyncRead, AsyncWrite, Future};
+use rustls::Connection;
+
+/// Blocking io adapter over an asynchronous transport, [`Poll::Pending`] is reported as [`ErrorKind::WouldBlock`].
+struct SyncIo<'a, 'b, S> {
+    io: &'a mut S,
+    cx: &'a mut Context<'b>,
+}
+
+impl<'a, 'b, S: AsyncRead + Unpin> Read for SyncIo<'a, 'b, S> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match Pin::new(&mut *self.io).poll_read(self.cx, buf) {
+            Poll::Ready(result) => result,
+            Poll::Pending => Err(ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+impl<'a, 'b, S: AsyncWrite + Unpin> Write for SyncIo<'a, 'b, S> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match Pin::new(&mut *self.io).poll_write(self.cx, buf) {
+            Poll::Ready(result) => result,
+            Poll::Pending => Err(ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match Pin::new(&mut *self.io).poll_flush(self.cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => Err(ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+/// TLS stream over an asynchronous transport `S`.
+pub struct TlsStream<S> {
+    io: S,
+    conn: Connection,
+    eof: bool,
+    close_notify: bool,
+}
+
+impl<S> TlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Wrap transport `io` with rustls client or server connection.
+    pub fn new<C: Into<Connection>>(io: S, conn: C) -> Self {
+        Self {
+            io,
+            conn: conn.into(),
+            eof: false,
+            close_notify: false,
+        }
+    }
+
+    /// Get rustls connection state.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Get underlying transport.
+    pub fn get_ref(&self) -> &S {
+        &self.io
+    }
+
+    /// Consume this stream and return the underlying transport and rustls connection.
+    pub fn into_inner(self) -> (S, Connection) {
+        (self.io, self.conn)
+    }
+
+    /// Return future that drives the TLS handshake to completion.
+    ///
+    /// Calling this is optional, read/write operations drive the handshake implicitly.
+    pub fn handshake(&mut self) -> Handshake<'_, S> {
+        Handshake(self)
+    }
+
+    /// Write buffered TLS records to transport.
+    fn poll_write_tls(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut io = SyncIo {
+            io: &mut self.io,
+            cx,
+        };
+
+        match self.conn.write_tls(&mut io) {
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Poll::Pending,
+            result => Poll::Ready(result),
+        }
+    }
+
+    /// Read TLS records from transport and process them.
+    fn poll_read_tls(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        let mut io = SyncIo {
+            io: &mut self.io,
+            cx,
+        };
+
+        let len = match self.conn.read_tls(&mut io) {
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Poll::Pending,
+            Err(err) => return Poll::Ready(Err(err)),
+            Ok(len) => len,
+        };
+
+        if let Err(err) = self.conn.process_new_packets() {
+            // Try sending alert to peer before surfacing error.
+            _ = self.poll_write_tls(cx);
+
+            return Poll::Ready(Err(Error::new(ErrorKind::InvalidData, err)));
+        }
+
+        Poll::Ready(Ok(len))
+    }
+
+    /// Write all buffered TLS records to transport.
+    fn poll_flush_tls(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while self.conn.wants_write() {
+            match self.poll_write_tls(cx) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    /// Drive TLS handshake, returns [`Poll::Ready`] when the handshake is completed.
+    pub fn poll_handshake(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while self.conn.is_handshaking() {
+            if let Poll::Ready(Err(err)) = self.poll_flush_tls(cx) {
+                return Poll::Ready(Err(err));
+            }
+
+            if !self.conn.is_handshaking() {
+                break;
+            }
+
+            if self.conn.wants_write() {
+                return Poll::Pending;
+            }
+
+            match self.poll_read_tls(cx) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "tls handshake eof",
+                    )))
+                }
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        self.poll_flush_tls(cx)
+    }
+}
+
+/// Future returned by [`TlsStream::handshake`]
+pub struct Handshake<'a, S>(&'a mut TlsStream<S>);
+
+impl<'a, S> Future for Handshake<'a, S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.poll_handshake(cx)
+    }
+}
+
+impl<S> AsyncRead for TlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+
+        if this.conn.is_handshaking() {
+            match this.poll_handshake(cx) {
+                Poll::Ready(Ok(_)) => {}
+                poll => return poll.map_ok(|_| 0),
+            }
+        }
+
+        loop {
+            match this.conn.reader().read(buf) {
+                Ok(len) => return Poll::Ready(Ok(len)),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    if this.eof {
+                        return Poll::Ready(Ok(0));
+                    }
+                }
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+
+            // Key updates and alerts may be queued by the received records.
+            if let Poll::Ready(Err(err)) = this.poll_flush_tls(cx) {
+                return Poll::Ready(Err(err));
+            }
+
+            match this.poll_read_tls(cx) {
+                Poll::Ready(Ok(0)) => this.eof = true,
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<S> AsyncWrite for TlsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+
+        if this.conn.is_handshaking() {
+            match this.poll_handshake(cx) {
+                Poll::Ready(Ok(_)) => {}
+                poll => return poll.map_ok(|_| 0),
+            }
+        }
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        loop {
+            let len = this.conn.writer().write(buf)?;
+
+            match this.poll_flush_tls(cx) {
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                // Plaintext buffer was full, retry now the tls records are flushed.
+                Poll::Ready(Ok(_)) if len == 0 => {}
+                Poll::Pending if len == 0 => return Poll::Pending,
+                // Plaintext accepted by rustls is buffered, report it as written.
+                _ => return Poll::Ready(Ok(len)),
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+
+        this.conn.writer().flush()?;
+
+        match this.poll_flush_tls(cx) {
+            Poll::Ready(Ok(_)) => Pin::new(&mut this.io).poll_flush(cx),
+            poll => poll,
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+
+        if !this.close_notify {
+            this.conn.send_close_notify();
+            this.close_notify = true;
+        }
+
+        match this.poll_flush_tls(cx) {
+            Poll::Ready(Ok(_)) => Pin::new(&mut this.io).poll_close(cx),
+            poll => poll,
+        }
+    }
+}
+
+impl<S> std::fmt::Debug for TlsStream<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsStream")
+            .field("conn", &self.conn)
+            .field("eof", &self.eof)
+            .finish()
+    }
+}
@@ -0,0 +1,581 @@
+//! Io helpers built on top of [`AsyncRead`]/[`AsyncWrite`].
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::{ErrorKind, Result},
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::{AsyncRead, AsyncWrite, Future, Stream};
+
+use super::IoReactor;
+
+/// Copy all data from `reader` to `writer` with a reusable buffer of `buf_size` bytes,
+/// returns the total number of bytes copied.
+///
+/// Each round reads as much as is available (until the buffer is full or the reader is pending),
+/// then writes the whole chunk, retrying short writes before reading more.
+pub fn copy_with<R, W>(reader: R, writer: W, buf_size: usize) -> CopyWith<R, W>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    CopyWith {
+        reader,
+        writer,
+        buff: vec![0; buf_size.max(1)],
+        filled: 0,
+        written: 0,
+        eof: false,
+        total: 0,
+    }
+}
+
+/// Future returned by [`copy_with`]
+#[derive(Debug)]
+pub struct CopyWith<R, W> {
+    reader: R,
+    writer: W,
+    buff: Vec<u8>,
+    filled: usize,
+    written: usize,
+    eof: bool,
+    total: u64,
+}
+
+impl<R, W> CopyWith<R, W>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    /// Read until buffer is full, reader is pending or reaches eof.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while self.filled < self.buff.len() {
+            match Pin::new(&mut self.reader).poll_read(cx, &mut self.buff[self.filled..]) {
+                Poll::Ready(Ok(0)) => {
+                    self.eof = true;
+                    break;
+                }
+                Poll::Ready(Ok(len)) => self.filled += len,
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending if self.filled > 0 => break,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    /// Write all buffered data.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while self.written < self.filled {
+            match Pin::new(&mut self.writer).poll_write(cx, &self.buff[self.written..self.filled]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(len)) => {
+                    self.written += len;
+                    self.total += len as u64;
+                }
+                Poll::Ready(Err(err)) if err.kind() == ErrorKind::Interrupted => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        self.filled = 0;
+        self.written = 0;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R, W> Future for CopyWith<R, W>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    type Output = Result<u64>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        loop {
+            if this.filled == 0 && !this.eof {
+                if let Poll::Ready(Err(err)) = this.poll_fill(cx) {
+                    return Poll::Ready(Err(err));
+                }
+
+                if this.filled == 0 && !this.eof {
+                    return Poll::Pending;
+                }
+            }
+
+            match this.poll_drain(cx) {
+                Poll::Ready(Ok(_)) => {}
+                poll => return poll.map_ok(|_| 0),
+            }
+
+            if this.eof {
+                return match Pin::new(&mut this.writer).poll_flush(cx) {
+                    Poll::Ready(Ok(_)) => Poll::Ready(Ok(this.total)),
+                    poll => poll.map_ok(|_| 0),
+                };
+            }
+        }
+    }
+}
+
+/// Create a future which completes after `duration`, driven by `reactor` time wheel.
+pub fn sleep(reactor: IoReactor, duration: Duration) -> Sleep {
+    Sleep {
+        reactor,
+        duration,
+        timer: None,
+    }
+}
+
+/// Future returned by [`sleep`]
+#[derive(Debug)]
+pub struct Sleep {
+    reactor: IoReactor,
+    duration: Duration,
+    timer: Option<u64>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        match this.timer {
+            Some(id) => match this.reactor.poll_timer(id, cx.waker().clone()) {
+                Poll::Ready(_) => {
+                    this.timer.take();
+
+                    Poll::Ready(())
+                }
+                Poll::Pending => Poll::Pending,
+            },
+            None => {
+                this.timer = Some(this.reactor.add_timer(this.duration, cx.waker().clone()));
+
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.timer.take() {
+            self.reactor.remove_timer(id);
+        }
+    }
+}
+
+/// Create a stream yielding every `period`, driven by `reactor` time wheel, the first item after one `period`.
+///
+/// Deadlines are multiples of `period` from the creation time, so a late tick doesn't delay the following ones.
+/// The next timer is added as soon as a tick fires, whether or not the stream is polled again.
+pub fn interval(reactor: IoReactor, period: Duration) -> Interval {
+    Interval {
+        reactor,
+        period,
+        deadline: Instant::now() + period,
+        timer: None,
+    }
+}
+
+/// Stream returned by [`interval`]
+#[derive(Debug)]
+pub struct Interval {
+    reactor: IoReactor,
+    period: Duration,
+    deadline: Instant,
+    timer: Option<u64>,
+}
+
+impl Interval {
+    fn add_timer(&mut self, cx: &mut Context<'_>) {
+        let timeout = self.deadline.saturating_duration_since(Instant::now());
+
+        self.timer = Some(self.reactor.add_timer(timeout, cx.waker().clone()));
+    }
+}
+
+impl Stream for Interval {
+    type Item = ();
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        match this.timer {
+            Some(id) => match this.reactor.poll_timer(id, cx.waker().clone()) {
+                Poll::Ready(_) => {
+                    this.deadline += this.period;
+
+                    this.add_timer(cx);
+
+                    Poll::Ready(Some(()))
+                }
+                Poll::Pending => Poll::Pending,
+            },
+            None => {
+                this.add_timer(cx);
+
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Interval {
+    fn drop(&mut self) {
+        if let Some(id) = self.timer.take() {
+            self.reactor.remove_timer(id);
+        }
+    }
+}
+
+/// Create a future which completes after `duration` with sub-millisecond precision.
+///
+/// Linux arms a [`TimerFd`](super::timerfd::TimerFd), so the reactor wakes when it is due.
+/// Other systems fall back to [`sleep`], whose precision is the reactor `tick_duration`.
+pub fn precise_sleep(reactor: IoReactor, duration: Duration) -> PreciseSleep {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    return PreciseSleep {
+        reactor,
+        duration,
+        timer: None,
+    };
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    return PreciseSleep(sleep(reactor, duration));
+}
+
+/// Future returned by [`precise_sleep`]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug)]
+pub struct PreciseSleep {
+    reactor: IoReactor,
+    duration: Duration,
+    timer: Option<super::timerfd::TimerFd>,
+}
+
+/// Future returned by [`precise_sleep`]
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+#[derive(Debug)]
+pub struct PreciseSleep(Sleep);
+
+impl Future for PreciseSleep {
+    type Output = Result<()>;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if this.timer.is_none() {
+            let timer = super::timerfd::TimerFd::new(this.reactor.clone())?;
+
+            timer.set(this.duration)?;
+
+            this.timer = Some(timer);
+        }
+
+        this.timer.as_mut().unwrap().poll_expired(cx)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.get_mut().0).poll(cx).map(Ok)
+    }
+}
+
+/// Backoff policy of [`retry`]
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Max attempts, including the first one.
+    pub max_attempts: usize,
+    /// Delay before the second attempt.
+    pub base_delay: Duration,
+    /// Delay growth factor applied after each failed attempt.
+    pub multiplier: f64,
+    /// Random spread of each delay, as a fraction in `[0, 1]`,
+    /// e.g. `0.1` sleeps between 90% and 110% of the delay.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            multiplier: 2.0,
+            jitter: 0.1,
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn jittered(&self, delay: Duration) -> Duration {
+        let jitter = self.jitter.clamp(0.0, 1.0);
+
+        if jitter == 0.0 {
+            return delay;
+        }
+
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+
+        delay.mul_f64(1.0 - jitter + 2.0 * jitter * random)
+    }
+}
+
+/// Run futures created by `make_fut` until one succeeds or `policy.max_attempts` is reached,
+/// sleeping on `reactor` timer between attempts.
+///
+/// Returns the first success or the last error.
+pub async fn retry<F, Fut, T>(reactor: IoReactor, policy: RetryPolicy, mut make_fut: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut delay = policy.base_delay;
+
+    let mut attempt = 1;
+
+    loop {
+        match make_fut().await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt >= policy.max_attempts => return Err(err),
+            Err(err) => {
+                log::debug!(
+                    "attempt({}/{}) failed, retry in {:?}: {}",
+                    attempt,
+                    policy.max_attempts,
+                    delay,
+                    err
+                );
+            }
+        }
+
+        sleep(reactor.clone(), policy.jittered(delay)).await;
+
+        delay = delay.mul_f64(policy.multiplier);
+
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread::spawn,
+        time::Instant,
+    };
+
+    use futures::io::Cursor;
+
+    use crate::Reactor;
+
+    use super::*;
+
+    /// Writer that accepts at most `limit` bytes per write.
+    struct ShortWriter {
+        data: Vec<u8>,
+        limit: usize,
+    }
+
+    impl AsyncWrite for ShortWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<Result<usize>> {
+            let len = buf.len().min(self.limit);
+
+            self.data.extend_from_slice(&buf[..len]);
+
+            Poll::Ready(Ok(len))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[futures_test::test]
+    async fn test_copy_with() {
+        let source = (0..10 * 1024 * 1024)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        let mut writer = ShortWriter {
+            data: vec![],
+            limit: 10000,
+        };
+
+        let copied = copy_with(Cursor::new(&source), &mut writer, 64 * 1024)
+            .await
+            .unwrap();
+
+        assert_eq!(copied, source.len() as u64);
+
+        assert!(writer.data == source);
+    }
+
+    #[futures_test::test]
+    async fn test_retry_backoff() {
+        let reactor = IoReactor::new(Duration::from_millis(10)).unwrap();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(10)).unwrap();
+        });
+
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            multiplier: 2.0,
+            jitter: 0.0,
+        };
+
+        let attempts = AtomicUsize::new(0);
+
+        let start = Instant::now();
+
+        let value = retry(reactor.clone(), policy, || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(ErrorKind::ConnectionRefused.into())
+            } else {
+                Ok("done")
+            }
+        })
+        .await
+        .unwrap();
+
+        let elapsed = start.elapsed();
+
+        assert_eq!(value, "done");
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // 100ms + 200ms, upper bound leaves room for busy test threads.
+        assert!(elapsed >= Duration::from_millis(300), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_precise_sleep() {
+        use futures::{task::noop_waker, FutureExt};
+
+        let mut reactor = IoReactor::default();
+
+        let waker = noop_waker();
+
+        let mut cx = Context::from_waker(&waker);
+
+        let start = Instant::now();
+
+        let mut sleep = precise_sleep(reactor.clone(), Duration::from_micros(500));
+
+        assert!(sleep.poll_unpin(&mut cx).is_pending());
+
+        // Returns when the timer is due, way before the poll timeout and the 1s tick of the default reactor.
+        reactor.poll_once(Duration::from_secs(1)).unwrap();
+
+        assert!(matches!(sleep.poll_unpin(&mut cx), Poll::Ready(Ok(()))));
+
+        let elapsed = start.elapsed();
+
+        assert!(elapsed >= Duration::from_micros(500), "{:?}", elapsed);
+        // Only checks the poll didn't wait for its timeout or the next tick.
+        assert!(elapsed < Duration::from_millis(500), "{:?}", elapsed);
+    }
+
+    #[futures_test::test]
+    async fn test_interval() {
+        use futures::StreamExt;
+
+        let reactor = IoReactor::new(Duration::from_millis(1)).unwrap();
+
+        let mut background = reactor.clone();
+
+        spawn(move || loop {
+            background.poll_once(Duration::from_millis(100)).unwrap();
+        });
+
+        let start = Instant::now();
+
+        let ticks = interval(reactor, Duration::from_millis(50))
+            .map(|_| start.elapsed())
+            .take(3)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(ticks.len(), 3);
+
+        // Never early, a late tick may catch up but never fires before its own deadline.
+        for (i, elapsed) in ticks.iter().enumerate() {
+            assert!(
+                *elapsed >= Duration::from_millis(50) * (i as u32 + 1),
+                "{:?}",
+                ticks
+            );
+        }
+    }
+
+    #[futures_test::test]
+    async fn test_copy_with_reactor_close() {
+        use crate::io::socket::tcp::{TcpAcceptor, TcpStream};
+
+        let reactor = IoReactor::default();
+
+        let mut background = reactor.clone();
+
+        spawn(move || while background.poll_once(Duration::from_millis(100)).is_ok() {});
+
+        let mut acceptor =
+            TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+        let listen_addr = acceptor.local_addr().unwrap();
+
+        let connect = TcpStream::connect(reactor.clone(), listen_addr, None, None);
+
+        let (client, accept) = futures::join!(connect, acceptor.accept());
+
+        let _client = client.unwrap();
+
+        let (server, _) = accept.unwrap();
+
+        let closer = reactor.clone();
+
+        spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+
+            closer.close();
+        });
+
+        let mut writer = ShortWriter {
+            data: vec![],
+            limit: 16,
+        };
+
+        // The relay retries the `Interrupted` read, then stops on the closed reactor.
+        let err = copy_with(server.to_read_stream(None), &mut writer, 1024)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::NotConnected);
+    }
+}
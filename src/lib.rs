@@ -1,8 +1,10 @@
 #![doc = include_str!("../README.md")]
 
+pub mod executor;
 pub mod io;
 pub mod reactor;
 
+pub use executor::ThreadPoolExecutor;
 pub use reactor::*;
 
 pub mod timewheel;
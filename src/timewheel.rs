@@ -1,34 +1,66 @@
-//! Hashed [`timewheel`](https://blog.acolyer.org/2015/11/23/hashed-and-hierarchical-timing-wheels/)
+//! Hashed and hierarchical [`timewheel`](https://blog.acolyer.org/2015/11/23/hashed-and-hierarchical-timing-wheels/)
 //! implementation for [`IoReactor`](crate::io::IoReactor)
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::task::Poll;
 // Time wheel algorithem impl
 #[derive(Debug)]
 struct Slot<T> {
-    round: u64,
+    id: u64,
+    /// Absolute tick at which this entry fires.
+    expires: u64,
     t: T,
 }
 
+/// Handle of an entry added by [`TimeWheel::add`], cancels it with [`TimeWheel::remove`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimeoutHandle {
+    id: u64,
+    expires: u64,
+}
+
 /// Timewheel implementation for [`IoReactor`](crate::io::IoReactor)
+///
+/// Each level has `steps` slots, a slot of level `n` spans `steps^n` ticks.
+/// Long timeouts are parked in coarse levels and cascade down to finer levels as they approach,
+/// like the linux kernel timer wheel.
 #[derive(Debug)]
 pub struct TimeWheel<T> {
-    hashed: HashMap<u64, Vec<Slot<T>>>,
+    levels: Vec<HashMap<u64, Vec<Slot<T>>>>,
+    /// Number of pending entries by expiration tick, see [`next_expiration`](Self::next_expiration).
+    expirations: BTreeMap<u64, usize>,
     steps: u64,
     tick: u64,
+    next_id: u64,
 }
 
 impl<T> TimeWheel<T> {
     // create new hashed time wheel instance
     pub fn new(steps: u64) -> Self {
+        Self::with_levels(steps, 1)
+    }
+
+    /// Create new time wheel with `levels` cascading levels of `steps` slots each.
+    ///
+    /// Timeouts up to `steps^levels` ticks are placed without revisiting,
+    /// longer ones are re-placed each time their slot of the top level comes around.
+    pub fn with_levels(steps: u64, levels: usize) -> Self {
         TimeWheel {
-            steps: steps,
-            hashed: HashMap::new(),
+            steps: steps.max(1),
+            levels: (0..levels.max(1)).map(|_| HashMap::new()).collect(),
+            expirations: BTreeMap::new(),
             tick: 0,
+            next_id: 0,
         }
     }
 
-    pub fn add(&mut self, timeout: u64, value: T) {
+    /// Number of ticks spanned by one slot of `level`.
+    fn span(&self, level: usize) -> u64 {
+        self.steps.saturating_pow(level as u32)
+    }
+
+    /// Add `value` firing after `timeout` ticks, the returned handle cancels it.
+    pub fn add(&mut self, timeout: u64, value: T) -> TimeoutHandle {
         log::trace!(
             "add timeout({}) steps({}) tick({})",
             timeout,
@@ -36,51 +68,402 @@ impl<T> TimeWheel<T> {
             self.tick
         );
 
-        let slot = (timeout + self.tick) % self.steps;
-        let round = timeout / self.steps;
+        let handle = TimeoutHandle {
+            id: self.next_id,
+            expires: self.tick.saturating_add(timeout),
+        };
+
+        self.next_id += 1;
+
+        *self.expirations.entry(handle.expires).or_default() += 1;
+
+        self.place(Slot {
+            id: handle.id,
+            expires: handle.expires,
+            t: value,
+        });
+
+        handle
+    }
+
+    /// Drop an entry expiring at `expires` from [`expirations`](Self::expirations) once it fired or was removed.
+    fn untrack(expirations: &mut BTreeMap<u64, usize>, expires: u64) {
+        if let Some(count) = expirations.get_mut(&expires) {
+            *count -= 1;
+
+            if *count == 0 {
+                expirations.remove(&expires);
+            }
+        }
+    }
+
+    /// Put `slot` in the finest level able to hold it.
+    fn place(&mut self, slot: Slot<T>) {
+        let delta = slot.expires.saturating_sub(self.tick);
+
+        let top = self.levels.len() - 1;
+
+        let level = (0..top)
+            .find(|level| delta < self.span(level + 1))
+            .unwrap_or(top);
+
+        let index = (slot.expires / self.span(level)) % self.steps;
 
         log::trace!(
-            "add timeout({}) to slot({}) with round({}), current tick is {}",
-            timeout,
-            slot,
-            round,
+            "add expires({}) to level({}) slot({}), current tick is {}",
+            slot.expires,
+            level,
+            index,
             self.tick
         );
 
-        let slots = self.hashed.entry(slot).or_insert(Vec::new());
+        self.levels[level].entry(index).or_default().push(slot);
+    }
+
+    /// Cancel the entry of `handle`, returns false if it has already fired or been removed.
+    ///
+    /// Only visits the slot holding the entry at each level, unlike [`cancel`](Self::cancel).
+    pub fn remove(&mut self, handle: TimeoutHandle) -> bool {
+        for level in 0..self.levels.len() {
+            let index = (handle.expires / self.span(level)) % self.steps;
+
+            let Some(slots) = self.levels[level].get_mut(&index) else {
+                continue;
+            };
+
+            if let Some(pos) = slots.iter().position(|slot| slot.id == handle.id) {
+                slots.remove(pos);
+
+                if slots.is_empty() {
+                    self.levels[level].remove(&index);
+                }
+
+                Self::untrack(&mut self.expirations, handle.expires);
+
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Cancel all pending entries equal to `value`, returns true if any entry was removed.
+    pub fn cancel(&mut self, value: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        let mut removed = false;
+
+        let Self {
+            levels,
+            expirations,
+            ..
+        } = self;
+
+        for level in levels {
+            level.retain(|_, slots| {
+                slots.retain(|slot| {
+                    if slot.t != *value {
+                        return true;
+                    }
+
+                    Self::untrack(expirations, slot.expires);
+
+                    removed = true;
+
+                    false
+                });
+
+                !slots.is_empty()
+            });
+        }
+
+        removed
+    }
+
+    /// Number of [`tick`](Self::tick) calls until the earliest pending entry fires, [`None`] if no entry is pending.
+    ///
+    /// Reads the first key of the ordered expirations index, without visiting the wheel slots.
+    pub fn next_expiration(&self) -> Option<u64> {
+        self.expirations
+            .keys()
+            .next()
+            .map(|expires| expires.saturating_sub(self.tick) + 1)
+    }
+
+    /// Call [`tick`](Self::tick) `ticks` times, returns the entries fired by all of them.
+    ///
+    /// Stretches without expiring entries are skipped at once, only the coarse slots cascading
+    /// within them are visited, so the cost doesn't grow with the number of idle ticks.
+    pub fn advance(&mut self, ticks: u64) -> Vec<T> {
+        let mut fired = vec![];
+
+        let mut remaining = ticks;
+
+        while remaining > 0 {
+            // `tick` calls before the one firing the earliest entry.
+            let idle = self
+                .next_expiration()
+                .map_or(remaining, |next| (next - 1).min(remaining));
+
+            if idle > 0 {
+                self.skip(idle);
+
+                remaining -= idle;
+
+                continue;
+            }
+
+            if let Poll::Ready(values) = self.tick() {
+                fired.extend(values);
+            }
+
+            remaining -= 1;
+        }
+
+        fired
+    }
+
+    /// Move `ticks` ticks forward without firing anything, the caller makes sure no entry expires in between.
+    ///
+    /// Coarse slots that [`tick`](Self::tick) would have cascaded are re-placed relative to the new tick.
+    fn skip(&mut self, ticks: u64) {
+        let from = self.tick;
+
+        self.tick = self.tick.saturating_add(ticks);
+
+        let mut cascaded = vec![];
+
+        for level in 1..self.levels.len() {
+            let span = self.span(level);
+
+            let steps = self.steps;
+
+            let end = self.tick;
+
+            self.levels[level].retain(|index, slots| {
+                // First multiple of `span` at or after `from` visiting slot `index`.
+                let first = from.div_ceil(span);
+
+                let visit = first
+                    .checked_add((index + steps - first % steps) % steps)
+                    .and_then(|visit| visit.checked_mul(span));
+
+                match visit {
+                    Some(visit) if visit < end => {
+                        cascaded.append(slots);
+
+                        false
+                    }
+                    _ => true,
+                }
+            });
+        }
 
-        slots.push(Slot { t: value, round });
+        for slot in cascaded {
+            self.place(slot);
+        }
     }
 
     pub fn tick(&mut self) -> Poll<Vec<T>> {
-        let step = self.tick % self.steps;
+        let now = self.tick;
+
+        // Cascade coarse levels first, so entries demoted to a finer level due now are handled in this tick.
+        for level in (1..self.levels.len()).rev() {
+            let span = self.span(level);
+
+            if !now.is_multiple_of(span) {
+                continue;
+            }
+
+            if let Some(slots) = self.levels[level].remove(&((now / span) % self.steps)) {
+                for slot in slots {
+                    self.place(slot);
+                }
+            }
+        }
 
         self.tick += 1;
 
-        if let Some(slots) = self.hashed.remove(&step) {
-            let mut current: Vec<T> = vec![];
+        let mut current: Vec<T> = vec![];
+
+        if let Some(slots) = self.levels[0].remove(&(now % self.steps)) {
             let mut reserved: Vec<Slot<T>> = vec![];
 
             for slot in slots {
-                if slot.round == 0 {
+                if slot.expires <= now {
+                    Self::untrack(&mut self.expirations, slot.expires);
+
                     current.push(slot.t);
                 } else {
-                    reserved.push(Slot::<T> {
-                        t: slot.t,
-                        round: slot.round - 1,
-                    });
+                    reserved.push(slot);
                 }
             }
 
             if !reserved.is_empty() {
-                self.hashed.insert(step, reserved);
+                self.levels[0].insert(now % self.steps, reserved);
             }
+        }
 
-            if !current.is_empty() {
-                return Poll::Ready(current);
-            }
+        if !current.is_empty() {
+            return Poll::Ready(current);
         }
 
         Poll::Pending
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tick `wheel` until `value` fires, returns the number of ticks.
+    fn ticks_until(wheel: &mut TimeWheel<&'static str>, value: &str) -> u64 {
+        let start = wheel.tick;
+
+        loop {
+            if let Poll::Ready(values) = wheel.tick() {
+                if values.contains(&value) {
+                    return wheel.tick - start;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cascade_levels() {
+        // 10ms tick, 64^4 ticks (~46h) covered without revisiting.
+        let mut wheel = TimeWheel::with_levels(64, 4);
+
+        let ms_10 = 1;
+        let s_10 = 10 * 100;
+        let h_2 = 2 * 3600 * 100;
+
+        wheel.add(ms_10, "10ms");
+        wheel.add(s_10, "10s");
+        wheel.add(h_2, "2h");
+
+        assert_eq!(wheel.levels[0].values().flatten().count(), 1);
+        assert_eq!(wheel.levels[1].values().flatten().count(), 1);
+        assert_eq!(wheel.levels[3].values().flatten().count(), 1);
+
+        // Entry fires on the tick following its timeout, as single-level wheel does.
+        assert_eq!(ticks_until(&mut wheel, "10ms"), ms_10 + 1);
+        assert_eq!(ticks_until(&mut wheel, "10s"), s_10 - ms_10);
+        assert_eq!(ticks_until(&mut wheel, "2h"), h_2 - s_10);
+
+        assert!(wheel.levels.iter().all(|level| level.is_empty()));
+    }
+
+    #[test]
+    fn test_single_level_rounds() {
+        let mut wheel = TimeWheel::new(8);
+
+        wheel.add(3, "short");
+        wheel.add(20, "long");
+
+        assert!(wheel.cancel(&"short"));
+        assert!(!wheel.cancel(&"short"));
+
+        assert_eq!(ticks_until(&mut wheel, "long"), 21);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut wheel = TimeWheel::with_levels(8, 2);
+
+        let short = wheel.add(3, "short");
+        let long = wheel.add(20, "long");
+
+        wheel.add(30, "kept");
+
+        assert!(wheel.remove(short));
+        assert!(!wheel.remove(short));
+
+        // Cascaded from level 1 to level 0 before removal.
+        for _ in 0..17 {
+            assert!(wheel.tick().is_pending());
+        }
+
+        assert!(wheel.remove(long));
+
+        assert_eq!(ticks_until(&mut wheel, "kept"), 31 - 17);
+
+        assert!(wheel.levels.iter().all(|level| level.is_empty()));
+
+        assert_eq!(wheel.next_expiration(), None);
+    }
+
+    #[test]
+    fn test_next_expiration() {
+        let mut wheel = TimeWheel::with_levels(8, 2);
+
+        assert_eq!(wheel.next_expiration(), None);
+
+        let long = wheel.add(100, "long");
+        let short = wheel.add(20, "short");
+
+        wheel.add(20, "short");
+
+        assert_eq!(wheel.next_expiration(), Some(21));
+
+        // Another entry still expires on the same tick.
+        assert!(wheel.remove(short));
+        assert_eq!(wheel.next_expiration(), Some(21));
+
+        assert!(wheel.cancel(&"short"));
+        assert_eq!(wheel.next_expiration(), Some(101));
+
+        for _ in 0..10 {
+            assert!(wheel.tick().is_pending());
+        }
+
+        assert_eq!(wheel.next_expiration(), Some(91));
+
+        assert_eq!(ticks_until(&mut wheel, "long"), 91);
+        assert_eq!(wheel.next_expiration(), None);
+
+        assert!(!wheel.remove(long));
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut ticked = TimeWheel::with_levels(8, 3);
+        let mut advanced = TimeWheel::with_levels(8, 3);
+
+        // Spread over all levels and beyond the wheel span (8^3 ticks).
+        for (i, timeout) in [0, 1, 7, 8, 9, 63, 64, 65, 200, 511, 512, 513, 1500, 4000]
+            .into_iter()
+            .enumerate()
+        {
+            ticked.add(timeout, i);
+            advanced.add(timeout, i);
+        }
+
+        for chunk in [1, 3, 50, 7, 600, 2, 1000, 4000] {
+            let mut expected = vec![];
+
+            for _ in 0..chunk {
+                if let Poll::Ready(values) = ticked.tick() {
+                    expected.extend(values);
+                }
+            }
+
+            assert_eq!(advanced.advance(chunk), expected);
+            assert_eq!(advanced.tick, ticked.tick);
+            assert_eq!(advanced.next_expiration(), ticked.next_expiration());
+        }
+
+        assert_eq!(advanced.next_expiration(), None);
+        assert!(advanced.levels.iter().all(|level| level.is_empty()));
+
+        // Idle ticks are skipped at once.
+        advanced.add(u64::MAX / 2, 0);
+
+        assert!(advanced.advance(u64::MAX / 4).is_empty());
+        assert_eq!(
+            advanced.next_expiration(),
+            Some(u64::MAX / 2 - u64::MAX / 4 + 1)
+        );
+    }
+}
@@ -1,4 +1,6 @@
-use std::{io::SeekFrom, path::PathBuf, task::Poll, time::Duration};
+use std::{
+    fs::OpenOptions, io::SeekFrom, path::PathBuf, task::Poll, thread::spawn, time::Duration,
+};
 
 use futures::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, FutureExt};
 use futures_test::task::noop_context;
@@ -62,3 +64,317 @@ fn test_file_rw() {
         }
     }
 }
+
+#[futures_test::test]
+async fn test_file_reopen() {
+    _ = pretty_env_logger::try_init();
+
+    let reactor = IoReactor::default();
+
+    let mut background = reactor.clone();
+
+    spawn(move || loop {
+        background.poll_once(Duration::from_millis(100)).unwrap();
+    });
+
+    let dir: PathBuf = env!("CARGO_TARGET_TMPDIR").into();
+
+    let path_a = dir.join("test_reopen_a.log");
+    let path_b = dir.join("test_reopen_b.log");
+
+    _ = std::fs::remove_file(&path_a);
+    _ = std::fs::remove_file(&path_b);
+
+    let mut ops = OpenOptions::new();
+
+    ops.create(true).append(true);
+
+    let mut file = File::create(reactor.clone(), &path_a).unwrap();
+
+    file.to_write_stream(None)
+        .write_all(b"first line\n")
+        .await
+        .unwrap();
+
+    file.reopen(&path_b, &ops).await.unwrap();
+
+    file.to_write_stream(None)
+        .write_all(b"second line\n")
+        .await
+        .unwrap();
+
+    // Failed reopen keeps the current file.
+    assert!(file
+        .reopen(dir.join("missing").join("test.log"), &ops)
+        .await
+        .is_err());
+
+    file.to_write_stream(None)
+        .write_all(b"third line\n")
+        .await
+        .unwrap();
+
+    drop(file);
+
+    assert_eq!(std::fs::read(&path_a).unwrap(), b"first line\n");
+    assert_eq!(
+        std::fs::read(&path_b).unwrap(),
+        b"second line\nthird line\n"
+    );
+}
+
+#[cfg(target_family = "unix")]
+#[test]
+fn test_fifo_read_wakeup() {
+    use std::{ffi::CString, io::Write, os::unix::ffi::OsStrExt, task::Context};
+
+    use futures_test::task::new_count_waker;
+
+    _ = pretty_env_logger::try_init();
+
+    let mut reactor = IoReactor::default();
+
+    let dir: PathBuf = env!("CARGO_TARGET_TMPDIR").into();
+
+    let path = dir.join("test_fifo");
+
+    _ = std::fs::remove_file(&path);
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+
+    assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+    // Read/write open of a fifo doesn't block, and the file itself keeps the fifo writable,
+    // so reading the empty fifo blocks instead of returning eof.
+    let file = File::open(reactor.clone(), &path).unwrap();
+
+    let mut read_stream = file.to_read_stream(None);
+
+    let (waker, count) = new_count_waker();
+
+    let mut cx = Context::from_waker(&waker);
+
+    let mut buff = [0u8; 16];
+
+    let mut read = read_stream.read(&mut buff);
+
+    assert!(read.poll_unpin(&mut cx).is_pending());
+
+    OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .write_all(b"hello")
+        .unwrap();
+
+    reactor.poll_once(Duration::from_secs(1)).unwrap();
+
+    assert_eq!(count.get(), 1);
+
+    match read.poll_unpin(&mut cx) {
+        Poll::Ready(result) => assert_eq!(result.unwrap(), 5),
+        Poll::Pending => panic!("unexpect pending"),
+    }
+
+    drop(read);
+
+    assert_eq!(&buff[..5], b"hello");
+}
+
+#[futures_test::test]
+async fn test_open_with() {
+    _ = pretty_env_logger::try_init();
+
+    let reactor = IoReactor::default();
+
+    let mut background = reactor.clone();
+
+    spawn(move || loop {
+        background.poll_once(Duration::from_millis(100)).unwrap();
+    });
+
+    let dir: PathBuf = env!("CARGO_TARGET_TMPDIR").into();
+
+    let path = dir.join("test_open_with");
+
+    _ = std::fs::remove_file(&path);
+
+    // Missing file without `create`.
+    assert_eq!(
+        File::open_with(reactor.clone(), &path, OpenOptions::new().write(true))
+            .err()
+            .unwrap()
+            .kind(),
+        std::io::ErrorKind::NotFound
+    );
+
+    let file = File::open_with(
+        reactor.clone(),
+        &path,
+        OpenOptions::new().write(true).create_new(true),
+    )
+    .unwrap();
+
+    file.to_write_stream(None)
+        .write_all(b"hello")
+        .await
+        .unwrap();
+
+    drop(file);
+
+    assert_eq!(
+        File::open_with(
+            reactor.clone(),
+            &path,
+            OpenOptions::new().write(true).create_new(true)
+        )
+        .err()
+        .unwrap()
+        .kind(),
+        std::io::ErrorKind::AlreadyExists
+    );
+
+    // Read only, contents are kept and writes fail.
+    let file = File::open_with(reactor.clone(), &path, OpenOptions::new().read(true)).unwrap();
+
+    let mut buff = vec![];
+
+    file.to_read_stream(None)
+        .read_to_end(&mut buff)
+        .await
+        .unwrap();
+
+    assert_eq!(buff, b"hello");
+
+    assert!(file.to_write_stream(None).write_all(b"x").await.is_err());
+
+    drop(file);
+
+    #[cfg(target_family = "unix")]
+    {
+        let file =
+            File::open_with(reactor.clone(), &path, OpenOptions::new().append(true)).unwrap();
+
+        file.to_write_stream(None)
+            .write_all(b" world")
+            .await
+            .unwrap();
+
+        drop(file);
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello world");
+    }
+
+    // `create` opens the existing file, `truncate` clears it.
+    let file = File::open_with(
+        reactor.clone(),
+        &path,
+        OpenOptions::new().write(true).create(true).truncate(true),
+    )
+    .unwrap();
+
+    drop(file);
+
+    assert!(std::fs::read(&path).unwrap().is_empty());
+
+    // Mode bits of created files.
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        let path = dir.join("test_open_with_mode");
+
+        _ = std::fs::remove_file(&path);
+
+        let file = File::open_with(
+            reactor.clone(),
+            &path,
+            OpenOptions::new().write(true).create(true).mode(0o600),
+        )
+        .unwrap();
+
+        drop(file);
+
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+    }
+}
+
+#[futures_test::test]
+async fn test_read_write_at() {
+    _ = pretty_env_logger::try_init();
+
+    let reactor = IoReactor::default();
+
+    let mut background = reactor.clone();
+
+    spawn(move || loop {
+        background.poll_once(Duration::from_millis(100)).unwrap();
+    });
+
+    let dir: PathBuf = env!("CARGO_TARGET_TMPDIR").into();
+
+    let file = File::create(reactor.clone(), dir.join("test_read_write_at")).unwrap();
+
+    let mut write_stream = file.to_write_stream(None);
+
+    write_stream.write_all(b"hello world").await.unwrap();
+
+    assert_eq!(file.write_at(b"positional", 100).await.unwrap(), 10);
+
+    let mut buff = [0u8; 10];
+
+    assert_eq!(file.read_at(&mut buff, 100).await.unwrap(), 10);
+
+    assert_eq!(&buff, b"positional");
+
+    // Positional io leaves the cursor after the sequential write.
+    assert_eq!(write_stream.seek(SeekFrom::Current(0)).await.unwrap(), 11);
+
+    let mut read_stream = file.to_read_stream(None);
+
+    read_stream.seek(SeekFrom::Start(0)).await.unwrap();
+
+    let mut buff = [0u8; 11];
+
+    read_stream.read_exact(&mut buff).await.unwrap();
+
+    assert_eq!(&buff, b"hello world");
+}
+
+#[futures_test::test]
+async fn test_set_len() {
+    _ = pretty_env_logger::try_init();
+
+    let reactor = IoReactor::default();
+
+    let mut background = reactor.clone();
+
+    spawn(move || loop {
+        background.poll_once(Duration::from_millis(100)).unwrap();
+    });
+
+    let dir: PathBuf = env!("CARGO_TARGET_TMPDIR").into();
+
+    let path = dir.join("test_set_len");
+
+    let file = File::create(reactor.clone(), &path).unwrap();
+
+    let mut write_stream = file.to_write_stream(None);
+
+    write_stream.write_all(b"hello").await.unwrap();
+
+    file.set_len(4096).unwrap();
+
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), 4096);
+
+    assert_eq!(write_stream.seek(SeekFrom::Current(0)).await.unwrap(), 5);
+
+    assert_eq!(write_stream.seek(SeekFrom::End(0)).await.unwrap(), 4096);
+
+    file.set_len(2).unwrap();
+
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), 2);
+}
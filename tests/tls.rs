@@ -0,0 +1,176 @@
+#![cfg(feature = "rustls")]
+
+use std::{sync::Arc, thread::spawn, time::Duration};
+
+use futures::{AsyncReadExt, AsyncWriteExt, TryStreamExt};
+use reactors::{
+    io::{
+        socket::tcp::{TcpAcceptor, TcpStream},
+        tls::TlsStream,
+        IoReactor,
+    },
+    Reactor,
+};
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+    ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection,
+};
+
+static CA: &[u8] = include_bytes!("certs/ca.der");
+static CERT: &[u8] = include_bytes!("certs/localhost.der");
+static KEY: &[u8] = include_bytes!("certs/localhost.key.der");
+
+fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn server_config() -> Arc<ServerConfig> {
+    let config = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![CertificateDer::from(CERT.to_vec())],
+            PrivateKeyDer::try_from(KEY.to_vec()).unwrap(),
+        )
+        .unwrap();
+
+    Arc::new(config)
+}
+
+fn client_config() -> Arc<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+
+    roots.add(CertificateDer::from(CA.to_vec())).unwrap();
+
+    let config = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    Arc::new(config)
+}
+
+#[futures_test::test]
+async fn test_tls_handshake() {
+    _ = pretty_env_logger::try_init();
+
+    let reactor = IoReactor::default();
+
+    let mut background = reactor.clone();
+
+    spawn(move || loop {
+        background.poll_once(Duration::from_millis(100)).unwrap();
+    });
+
+    let listen_addr = "127.0.0.1:1901".parse().unwrap();
+
+    let mut acceptor = TcpAcceptor::new(reactor.clone(), listen_addr, None).unwrap();
+
+    let server = spawn(move || {
+        futures::executor::block_on(async move {
+            let (conn, _) = acceptor.try_next().await.unwrap().unwrap();
+
+            let mut stream = TlsStream::new(conn, ServerConnection::new(server_config()).unwrap());
+
+            let mut buff = [0u8; 11];
+
+            stream.read_exact(&mut buff).await.unwrap();
+
+            assert_eq!(&buff, b"hello world");
+
+            stream.write_all(&buff).await.unwrap();
+
+            stream.close().await.unwrap();
+        })
+    });
+
+    let conn = TcpStream::connect(reactor.clone(), listen_addr, None, None)
+        .await
+        .unwrap();
+
+    let mut stream = TlsStream::new(
+        conn,
+        ClientConnection::new(
+            client_config(),
+            ServerName::try_from("localhost").unwrap().to_owned(),
+        )
+        .unwrap(),
+    );
+
+    stream.handshake().await.unwrap();
+
+    assert!(!stream.connection().is_handshaking());
+
+    stream.write_all(b"hello world").await.unwrap();
+
+    let mut buff = vec![];
+
+    stream.read_to_end(&mut buff).await.unwrap();
+
+    assert_eq!(&buff, b"hello world");
+
+    server.join().unwrap();
+}
+
+#[futures_test::test]
+async fn test_tls_write_buffer_limit() {
+    _ = pretty_env_logger::try_init();
+
+    let reactor = IoReactor::default();
+
+    let mut background = reactor.clone();
+
+    spawn(move || loop {
+        background.poll_once(Duration::from_millis(100)).unwrap();
+    });
+
+    let mut acceptor =
+        TcpAcceptor::new(reactor.clone(), "127.0.0.1:0".parse().unwrap(), None).unwrap();
+
+    let listen_addr = acceptor.local_addr().unwrap();
+
+    let payload = vec![0x5au8; 4 * 1024 * 1024];
+
+    let expected = payload.len();
+
+    let server = spawn(move || {
+        futures::executor::block_on(async move {
+            let (conn, _) = acceptor.try_next().await.unwrap().unwrap();
+
+            let mut stream = TlsStream::new(conn, ServerConnection::new(server_config()).unwrap());
+
+            stream.handshake().await.unwrap();
+
+            // Let the client fill the socket and its rustls buffers.
+            std::thread::sleep(Duration::from_millis(200));
+
+            let mut buff = vec![];
+
+            stream.read_to_end(&mut buff).await.unwrap();
+
+            buff.len()
+        })
+    });
+
+    let conn = TcpStream::connect(reactor.clone(), listen_addr, None, None)
+        .await
+        .unwrap();
+
+    let mut client = ClientConnection::new(
+        client_config(),
+        ServerName::try_from("localhost").unwrap().to_owned(),
+    )
+    .unwrap();
+
+    client.set_buffer_limit(Some(1024));
+
+    let mut stream = TlsStream::new(conn, client);
+
+    stream.write_all(&payload).await.unwrap();
+
+    stream.close().await.unwrap();
+
+    assert_eq!(server.join().unwrap(), expected);
+}
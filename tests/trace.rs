@@ -0,0 +1,85 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::spawn,
+    time::Duration,
+};
+
+use futures::{SinkExt, TryStreamExt};
+use log::{Log, Metadata, Record};
+use reactors::{
+    io::{socket::udp::UdpSocket, IoReactor},
+    Reactor,
+};
+
+/// Counts log records emitted by this crate.
+struct CountingLogger(AtomicUsize);
+
+impl Log for CountingLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if record
+            .module_path()
+            .is_some_and(|path| path.starts_with("reactors"))
+        {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CountingLogger = CountingLogger(AtomicUsize::new(0));
+
+async fn echo(server: &UdpSocket, client: &UdpSocket, server_addr: std::net::SocketAddr) {
+    client
+        .to_write_stream(None)
+        .send((b"hello".to_vec(), server_addr))
+        .await
+        .unwrap();
+
+    let (buff, _) = server
+        .to_read_stream(1024, None)
+        .try_next()
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(buff, b"hello");
+}
+
+#[futures_test::test]
+async fn test_set_trace() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let reactor = IoReactor::default();
+
+    reactor.set_trace(false);
+
+    let server_addr = "127.0.0.1:1916".parse().unwrap();
+
+    let server = UdpSocket::new(reactor.clone(), server_addr).unwrap();
+
+    let client = UdpSocket::new(reactor.clone(), "127.0.0.1:1917".parse().unwrap()).unwrap();
+
+    let mut background = reactor.clone();
+
+    spawn(move || loop {
+        background.poll_once(Duration::from_millis(100)).unwrap();
+    });
+
+    let start = LOGGER.0.load(Ordering::SeqCst);
+
+    echo(&server, &client, server_addr).await;
+
+    assert_eq!(LOGGER.0.load(Ordering::SeqCst), start);
+
+    reactor.set_trace(true);
+
+    echo(&server, &client, server_addr).await;
+
+    assert!(LOGGER.0.load(Ordering::SeqCst) > start);
+}
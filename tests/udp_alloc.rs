@@ -0,0 +1,59 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use futures::StreamExt;
+use futures_test::task::noop_context;
+use reactors::io::{socket::udp::UdpSocket, IoReactor};
+
+/// Counts allocations made by threads with tracking enabled.
+struct CountingAlloc;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static TRACK: Cell<bool> = const { Cell::new(false) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if TRACK.try_with(|track| track.get()).unwrap_or(false) {
+            ALLOCS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[test]
+fn test_idle_poll_does_not_allocate() {
+    let reactor = IoReactor::default();
+
+    let socket = UdpSocket::new(reactor, "127.0.0.1:0".parse().unwrap()).unwrap();
+
+    let mut reader = socket.to_read_stream(64 * 1024, None);
+
+    let mut cx = noop_context();
+
+    // First poll registers the read interest.
+    assert!(reader.poll_next_unpin(&mut cx).is_pending());
+
+    TRACK.with(|track| track.set(true));
+
+    for _ in 0..1000 {
+        assert!(reader.poll_next_unpin(&mut cx).is_pending());
+    }
+
+    TRACK.with(|track| track.set(false));
+
+    assert!(ALLOCS.load(Ordering::SeqCst) < 10);
+}